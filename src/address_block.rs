@@ -2,9 +2,11 @@ use errors::*;
 use protection::Protection;
 use std::str::FromStr;
 use types::*;
-use utils::get_child_text;
+use utils::{add_child_text, add_optional_child_text, get_child_text, new_element};
+use xml_fragment::XmlFragment;
 use xmltree;
 
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub enum Usage {
     Registers,
@@ -12,12 +14,32 @@ pub enum Usage {
     Reserved,
 }
 
+/// The names of the child elements read by `from_element`.
+const ELEMENT_NAMES: &'static [&'static str] = &["offset", "size", "usage", "protection"];
+
+/// The names of the child elements in the order required by the schema, including the ones not
+/// read by `from_element`.
+const SCHEMA_ORDER: &'static [&'static str] = &["offset", "size", "usage", "protection"];
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub struct AddresBlock {
     pub offset: ScaledNonNegativeInteger,
     pub size: ScaledNonNegativeInteger,
     pub usage: Usage,
     pub protection: Option<Protection>,
+    pub unknown_elements: Vec<XmlFragment>,
+}
+
+impl Usage {
+    /// Returns the string used for this value in SVD files.
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            Usage::Registers => "registers",
+            Usage::Buffer => "buffer",
+            Usage::Reserved => "reserved",
+        }
+    }
 }
 
 impl FromStr for Usage {
@@ -49,6 +71,8 @@ impl AddresBlock {
             None => None,
         };
 
+        let unknown_elements = XmlFragment::unknown_children(element, &[ELEMENT_NAMES]);
+
         let offset = try!(offset.parse());
         let size = try!(size.parse());
         let usage = try!(usage.parse());
@@ -58,7 +82,18 @@ impl AddresBlock {
             size: size,
             usage: usage,
             protection: protection,
+            unknown_elements: unknown_elements,
         })
 
     }
+
+    pub fn to_element(&self) -> xmltree::Element {
+        let mut element = new_element("addressBlock");
+        add_child_text(&mut element, "offset", format!("0x{:X}", self.offset));
+        add_child_text(&mut element, "size", format!("0x{:X}", self.size));
+        add_child_text(&mut element, "usage", self.usage.as_str());
+        add_optional_child_text(&mut element, "protection", &self.protection);
+        XmlFragment::add_to_element(&mut element, &self.unknown_elements, SCHEMA_ORDER);
+        element
+    }
 }
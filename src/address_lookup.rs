@@ -0,0 +1,233 @@
+use cluster::Cluster;
+use device::Device;
+use dim_element_group::DimElementGroup;
+use peripheral::Peripheral;
+use register::Register;
+use register_or_cluster::RegisterOrCluster;
+use utils::offset_address;
+
+/// An element of a cluster array, or a cluster that is not an array.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ClusterInstance<'a> {
+    pub cluster: &'a Cluster,
+    pub index: Option<String>,
+}
+
+/// The register found at an address by `Device::lookup_address`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AddressMatch<'a> {
+    pub peripheral: &'a Peripheral,
+    /// The index of the peripheral if it is an element of a peripheral array.
+    pub peripheral_index: Option<String>,
+    /// The clusters containing the register, from the outermost to the innermost.
+    pub clusters: Vec<ClusterInstance<'a>>,
+    pub register: &'a Register,
+    /// The index of the register if it is an element of a register array.
+    pub register_index: Option<String>,
+    /// The offset of the address inside the register, in address units.
+    pub offset: u64,
+}
+
+impl<'a> AddressMatch<'a> {
+    /// Returns the dotted name of the register, for example `RCC.AHB1ENR` or `DMA1.CH3.CCR`.
+    pub fn path(&self) -> String {
+        let mut path = DimElementGroup::element_name(&self.peripheral.name, &self.peripheral_index);
+        for instance in &self.clusters {
+            path.push('.');
+            path.push_str(&DimElementGroup::element_name(&instance.cluster.name, &instance.index));
+        }
+        path.push('.');
+        path.push_str(&DimElementGroup::element_name(&self.register.name, &self.register_index));
+        path
+    }
+}
+
+/// Returns the first register containing `address`. The registers are searched in the order they
+/// appear in the device.
+pub fn lookup_address(device: &Device, address: u64) -> Option<AddressMatch> {
+    let unit_bits = if device.address_unit_bits.0 == 0 {
+        8
+    } else {
+        device.address_unit_bits.0
+    };
+
+    for peripheral in &device.peripherals {
+        if let Some(ref registers) = peripheral.registers {
+            for (peripheral_index, offset) in peripheral.dim_element.elements() {
+                let base_address = match peripheral.base_address.0.checked_add(offset) {
+                    Some(base_address) => base_address,
+                    None => continue,
+                };
+                let mut clusters = vec![];
+                if let Some((register, register_index, offset)) =
+                       lookup_in(registers, base_address, address, unit_bits, &mut clusters) {
+                    return Some(AddressMatch {
+                        peripheral: peripheral,
+                        peripheral_index: peripheral_index,
+                        clusters: clusters,
+                        register: register,
+                        register_index: register_index,
+                        offset: offset,
+                    });
+                }
+            }
+        }
+    }
+
+    None
+}
+
+fn lookup_in<'a>(registers: &'a [RegisterOrCluster],
+                 base_address: u64,
+                 address: u64,
+                 unit_bits: u64,
+                 clusters: &mut Vec<ClusterInstance<'a>>)
+                 -> Option<(&'a Register, Option<String>, u64)> {
+    for r_or_c in registers {
+        match *r_or_c {
+            RegisterOrCluster::Register(ref r) => {
+                let size = r.size().saturating_add(unit_bits - 1) / unit_bits;
+                for (index, offset) in r.dim_element.elements() {
+                    let start = match offset_address(base_address, r.address_offset.0, offset) {
+                        Some(start) => start,
+                        None => continue,
+                    };
+                    if address >= start && address - start < size {
+                        return Some((r, index, address - start));
+                    }
+                }
+            }
+            RegisterOrCluster::Cluster(ref c) => {
+                for (index, offset) in c.dim_element.elements() {
+                    let cluster_address =
+                        match offset_address(base_address, c.address_offset.0, offset) {
+                            Some(cluster_address) => cluster_address,
+                            None => continue,
+                        };
+                    clusters.push(ClusterInstance {
+                        cluster: c,
+                        index: index,
+                    });
+                    let found =
+                        lookup_in(&c.registers, cluster_address, address, unit_bits, clusters);
+                    if found.is_some() {
+                        return found;
+                    }
+                    clusters.pop();
+                }
+            }
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use device::Device;
+
+    fn device(address_unit_bits: u32, peripherals: &str) -> Device {
+        let xml = format!("<device><name>TEST</name><version>1.0</version>\
+                           <description>Test</description>\
+                           <addressUnitBits>{}</addressUnitBits><width>32</width>\
+                           <peripherals>{}</peripherals></device>",
+                          address_unit_bits,
+                          peripherals);
+        Device::from_reader(xml.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn clusters() {
+        let d = device(8,
+                       "<peripheral><name>DMA</name><baseAddress>0x1000</baseAddress><registers>
+                            <register><name>ISR</name><addressOffset>0x0</addressOffset></register>
+                            <cluster>
+                                <dim>3</dim><dimIncrement>0x14</dimIncrement>
+                                <dimIndex>1-3</dimIndex>
+                                <name>CH%s</name><addressOffset>0x8</addressOffset>
+                                <register><name>CCR</name><addressOffset>0x0</addressOffset>
+                                </register>
+                                <register><name>CNDTR</name><addressOffset>0x4</addressOffset>
+                                </register>
+                            </cluster>
+                        </registers></peripheral>");
+
+        let found = d.lookup_address(0x1000 + 0x8 + 2 * 0x14 + 0x4 + 1).unwrap();
+        assert_eq!("DMA.CH3.CNDTR", found.path());
+        assert_eq!(1, found.clusters.len());
+        assert_eq!(Some("3"), found.clusters[0].index.as_ref().map(|i| &**i));
+        assert_eq!(1, found.offset);
+
+        assert_eq!("DMA.ISR", d.lookup_address(0x1003).unwrap().path());
+        // Between ISR and the first channel, after the last channel and before the peripheral
+        assert!(d.lookup_address(0x1004).is_none());
+        assert!(d.lookup_address(0x1000 + 0x8 + 3 * 0x14).is_none());
+        assert!(d.lookup_address(0xfff).is_none());
+    }
+
+    #[test]
+    fn arrays() {
+        let d = device(8,
+                       "<peripheral>
+                            <dim>2</dim><dimIncrement>0x400</dimIncrement>
+                            <dimIndex>A,B</dimIndex>
+                            <name>GPIO%s</name><baseAddress>0x2000</baseAddress><registers>
+                            <register><name>MODER</name><addressOffset>0x0</addressOffset>
+                            </register>
+                            <register><name>AFR</name><addressOffset>0x0</addressOffset>
+                            </register>
+                            <register>
+                                <dim>2</dim><dimIncrement>0x4</dimIncrement>
+                                <name>IDR%s</name><addressOffset>0x10</addressOffset>
+                            </register>
+                        </registers></peripheral>");
+
+        let found = d.lookup_address(0x2000 + 0x400 + 0x14).unwrap();
+        assert_eq!("GPIOB.IDR1", found.path());
+        assert_eq!(Some("B"), found.peripheral_index.as_ref().map(|i| &**i));
+        assert_eq!(Some("1"), found.register_index.as_ref().map(|i| &**i));
+        assert!(found.clusters.is_empty());
+
+        // The first of the registers at the same address is returned
+        assert_eq!("GPIOA.MODER", d.lookup_address(0x2000).unwrap().path());
+    }
+
+    #[test]
+    fn address_units() {
+        let d = device(16,
+                       "<peripheral><name>ADC</name><baseAddress>0x100</baseAddress><registers>
+                            <register><name>DR</name><addressOffset>0x0</addressOffset>
+                            </register>
+                            <register><name>SR</name><addressOffset>0x2</addressOffset>
+                                <size>8</size>
+                            </register>
+                        </registers></peripheral>");
+
+        // A 32 bits register spans two 16 bits address units, an 8 bits register spans one
+        let found = d.lookup_address(0x101).unwrap();
+        assert_eq!("ADC.DR", found.path());
+        assert_eq!(1, found.offset);
+        assert_eq!("ADC.SR", d.lookup_address(0x102).unwrap().path());
+        assert!(d.lookup_address(0x103).is_none());
+    }
+
+    #[test]
+    fn address_overflow() {
+        let d = device(8,
+                       "<peripheral><name>TOP</name><baseAddress>0xFFFFFFFFFFFFFFF0</baseAddress>
+                            <registers>
+                            <register><name>SR</name><addressOffset>0x20</addressOffset>
+                            </register>
+                            <cluster><name>CH</name><addressOffset>0x20</addressOffset>
+                                <register><name>CCR</name><addressOffset>0x0</addressOffset>
+                                </register>
+                            </cluster>
+                            <register><name>CR</name><addressOffset>0xC</addressOffset>
+                                <size>64</size>
+                            </register>
+                        </registers></peripheral>");
+
+        assert_eq!("TOP.CR", d.lookup_address(u64::max_value()).unwrap().path());
+        assert!(d.lookup_address(0x10).is_none());
+    }
+}
@@ -0,0 +1,822 @@
+extern crate svd;
+
+use std::env;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::process;
+use svd::device::Device;
+use svd::is_similar::IsSimilarOptions;
+use svd::peripheral::PeripheralsGroup;
+use svd::validate::{self, Finding, Severity};
+
+const USAGE: &'static str = "Usage: svd COMMAND [ARGS]
+
+Commands:
+    diff    print the differences between two versions of a device
+    fmt     rewrite SVD files in a canonical form
+    info    print a summary of a device
+    lint    check devices for errors and suspicious constructs
+
+Run `svd COMMAND --help` for the arguments of a command. A file named `-` is the standard input.
+
+Exit status: 0 on success, 1 if an error occurred, 2 if the arguments are invalid.";
+
+const DIFF_USAGE: &'static str = "Usage: svd diff OLD NEW
+
+Prints the peripherals, registers, clusters, fields and enumerated values that were added, removed,
+renamed or changed between the SVD files OLD and NEW. The elements are matched by name, then by
+address.";
+
+const FMT_USAGE: &'static str = "Usage: svd fmt [--check] FILE...
+
+Rewrites the SVD files FILE in a canonical form: the elements are in the order of the CMSIS-SVD
+schema, the addresses are in hexadecimal, the descriptions are trimmed and on a single line, and
+the indentation is two spaces. A FILE named `-` is read from the standard input and written to
+the standard output. The derivedFrom attributes and the unknown elements are kept, the XML
+comments are not.
+
+Options:
+    --check    don't write the files, list those that are not in the canonical form and exit
+               with status 1 if there is any";
+
+const INFO_USAGE: &'static str = "Usage: svd info FILE
+
+Prints the vendor, CPU and size of the device described by the SVD file FILE, how many
+peripherals, registers, fields and interrupts it has, its peripherals and the groups of similar
+peripherals that share a struct in the generated code.";
+
+const LINT_USAGE: &'static str = "Usage: svd lint [OPTIONS] FILE...
+
+Applies the rules documented in the `validate` module of the svd crate to the SVD files FILE.
+
+Options:
+    --enable RULES         apply only these rules, separated by commas
+    --disable RULES        don't apply these rules, separated by commas
+    --allow RULE:PATH      ignore the findings of RULE for the element PATH. RULE can be `*` for
+                           every rule and PATH can end with `*` to match every path starting with
+                           what precedes
+    --allow-file FILE      read `RULE:PATH` suppressions from FILE, one per line. Empty lines and
+                           lines starting with `#` are ignored
+    --format FORMAT        output format: text (default), json or sarif
+    --fail-on SEVERITY     exit with status 1 if there is a finding of at least this severity:
+                           warning, error (default) or never";
+
+#[derive(Debug, PartialEq)]
+enum CliError {
+    /// The arguments are invalid, the usage of the command is printed after the message.
+    Usage(String, &'static str),
+    Failure(String),
+}
+
+/// The arguments of a command.
+#[derive(Debug)]
+struct Args {
+    /// The options in the order they were given, with their value if they take one.
+    options: Vec<(String, Option<String>)>,
+    positional: Vec<String>,
+}
+
+impl Args {
+    /// Returns the values given to the option `name`.
+    fn values(&self, name: &str) -> Vec<&str> {
+        self.options
+            .iter()
+            .filter(|&&(ref option, _)| option == name)
+            .filter_map(|&(_, ref value)| value.as_ref().map(|v| &**v))
+            .collect()
+    }
+
+    fn has(&self, name: &str) -> bool {
+        self.options.iter().any(|&(ref option, _)| option == name)
+    }
+}
+
+/// Parses the arguments of a command accepting the options `flags` and the options with a value
+/// `valued`, given either as `--option value` or `--option=value`. Returns `None` if the help was
+/// requested.
+fn parse_args(args: &[String],
+              usage: &'static str,
+              flags: &[&str],
+              valued: &[&str])
+              -> Result<Option<Args>, CliError> {
+    let mut parsed = Args {
+        options: vec![],
+        positional: vec![],
+    };
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg == "-h" || arg == "--help" {
+            return Ok(None);
+        } else if !arg.starts_with('-') || arg == "-" {
+            parsed.positional.push(arg.clone());
+        } else if flags.contains(&&**arg) {
+            parsed.options.push((arg.clone(), None));
+        } else {
+            let (name, value) = match arg.find('=') {
+                Some(i) => (&arg[..i], Some(arg[i + 1..].to_string())),
+                None => (&**arg, None),
+            };
+            if !valued.contains(&name) {
+                return Err(CliError::Usage(format!("unknown option `{}`", name), usage));
+            }
+            let value = match value.or_else(|| args.next().cloned()) {
+                Some(value) => value,
+                None => return Err(CliError::Usage(format!("`{}` needs a value", name), usage)),
+            };
+            parsed.options.push((name.to_string(), Some(value)));
+        }
+    }
+    Ok(Some(parsed))
+}
+
+/// Parses the SVD file `path`, without resolving the `derivedFrom` attributes.
+fn read_device(path: &str) -> Result<Device, CliError> {
+    let device = if path == "-" {
+        Device::parse(io::stdin())
+    } else {
+        let f = try!(File::open(path)
+            .map_err(|e| CliError::Failure(format!("cannot open {}: {}", path, e))));
+        Device::parse(f)
+    };
+    device.map_err(|e| CliError::Failure(format!("cannot parse {}: {}", path, e)))
+}
+
+/// Returns `device` with the `derivedFrom` attributes and the register properties propagated,
+/// like `Device::from_reader` does.
+fn propagated(device: &Device) -> Device {
+    let mut device = device.clone();
+    device.propagate_derived_from();
+    device.propagate_register_properties();
+    device
+}
+
+fn diff(args: &[String]) -> Result<bool, CliError> {
+    let args = match try!(parse_args(args, DIFF_USAGE, &[], &[])) {
+        Some(args) => args,
+        None => {
+            println!("{}", DIFF_USAGE);
+            return Ok(true);
+        }
+    };
+    if args.positional.len() != 2 {
+        return Err(CliError::Usage("expected OLD and NEW".to_string(), DIFF_USAGE));
+    }
+
+    let old = propagated(&try!(read_device(&args.positional[0])));
+    let new = propagated(&try!(read_device(&args.positional[1])));
+    for change in old.diff(&new) {
+        println!("{}", change);
+    }
+    Ok(true)
+}
+
+fn fmt(args: &[String]) -> Result<bool, CliError> {
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    fmt_to(args, &mut out)
+}
+
+/// Runs the fmt command, writing the files that are not in the canonical form with `--check`, or
+/// the formatted standard input, to `out`.
+fn fmt_to<W: Write>(args: &[String], out: &mut W) -> Result<bool, CliError> {
+    let args = match try!(parse_args(args, FMT_USAGE, &["--check"], &[])) {
+        Some(args) => args,
+        None => {
+            println!("{}", FMT_USAGE);
+            return Ok(true);
+        }
+    };
+    if args.positional.is_empty() {
+        return Err(CliError::Usage("expected at least one FILE".to_string(), FMT_USAGE));
+    }
+    let check = args.has("--check");
+
+    let mut success = true;
+    for file in &args.positional {
+        let mut content = vec![];
+        let read = if file == "-" {
+            io::stdin().read_to_end(&mut content)
+        } else {
+            File::open(file).and_then(|mut f| f.read_to_end(&mut content))
+        };
+        try!(read.map_err(|e| CliError::Failure(format!("cannot read {}: {}", file, e))));
+        let mut device = try!(Device::parse(&*content)
+            .map_err(|e| CliError::Failure(format!("cannot parse {}: {}", file, e))));
+        device.normalize_descriptions();
+        let mut formatted = vec![];
+        try!(device.to_writer(&mut formatted)
+            .map_err(|e| CliError::Failure(format!("cannot format {}: {}", file, e))));
+
+        if check {
+            if formatted != content {
+                try!(writeln!(out, "{}", file).map_err(|e| {
+                    CliError::Failure(format!("cannot write to the standard output: {}", e))
+                }));
+                success = false;
+            }
+        } else if file == "-" {
+            try!(out.write_all(&formatted)
+                .map_err(|e| {
+                    CliError::Failure(format!("cannot write to the standard output: {}", e))
+                }));
+        } else if formatted != content {
+            try!(File::create(file)
+                .and_then(|mut f| f.write_all(&formatted))
+                .map_err(|e| CliError::Failure(format!("cannot write {}: {}", file, e))));
+        }
+    }
+    Ok(success)
+}
+
+fn info(args: &[String]) -> Result<bool, CliError> {
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    info_to(args, &mut out)
+}
+
+/// Runs the info command, writing the summary of the device to `out`.
+fn info_to<W: Write>(args: &[String], out: &mut W) -> Result<bool, CliError> {
+    let args = match try!(parse_args(args, INFO_USAGE, &[], &[])) {
+        Some(args) => args,
+        None => {
+            println!("{}", INFO_USAGE);
+            return Ok(true);
+        }
+    };
+    if args.positional.len() != 1 {
+        return Err(CliError::Usage("expected one FILE".to_string(), INFO_USAGE));
+    }
+
+    let declared = try!(read_device(&args.positional[0]));
+    let device = propagated(&declared);
+
+    let mut lines = vec![format!("Device: {} (version {})", device.name, device.version)];
+    if let Some(ref vendor) = device.vendor {
+        lines.push(format!("Vendor: {}", vendor));
+    }
+    if let Some(ref cpu) = device.cpu {
+        let cpu: Vec<_> = cpu.name().into_iter().chain(cpu.revision()).collect();
+        if !cpu.is_empty() {
+            lines.push(format!("CPU: {}", cpu.join(" ")));
+        }
+    }
+    lines.push(format!("Width: {} bits, address unit: {} bits",
+                       device.width,
+                       device.address_unit_bits));
+    lines.push(format!("Peripherals: {}, registers: {}, fields: {}, interrupts: {}",
+                       device.peripherals.len(),
+                       device.registers().count(),
+                       device.fields().count(),
+                       device.interrupts().len()));
+
+    lines.push(String::new());
+    lines.push("Peripherals:".to_string());
+    let name_width = device.peripherals.iter().map(|p| p.name.len()).max().unwrap_or(0);
+    for (peripheral, declared) in device.peripherals.iter().zip(&declared.peripherals) {
+        // The sizes of the address blocks are in address units
+        let blocks_size = if peripheral.address_blocks.is_empty() {
+            String::new()
+        } else {
+            let size = peripheral.address_blocks
+                .iter()
+                .fold(0u64, |size, b| size.saturating_add(b.size.0));
+            format!("0x{:X} units", size)
+        };
+        let mut line = format!("    {:width$}  0x{:08X}  {:>12}",
+                               peripheral.name,
+                               peripheral.base_address,
+                               blocks_size,
+                               width = name_width);
+        if let Some(ref derived_from) = declared.derived_from {
+            line.push_str(&format!("  derived from {}", derived_from));
+        }
+        lines.push(line.trim_right().to_string());
+    }
+
+    let (groups, _) = PeripheralsGroup::from_peripherals(&device.peripherals,
+                                                         &IsSimilarOptions::new());
+    if !groups.is_empty() {
+        lines.push(String::new());
+        lines.push("Groups of similar peripherals:".to_string());
+        for group in &groups {
+            let names: Vec<_> = group.peripherals().iter().map(|p| &*p.name).collect();
+            lines.push(format!("    {}: {}", group.struct_name(), names.join(", ")));
+        }
+    }
+
+    let written: io::Result<()> = lines.iter().map(|line| writeln!(out, "{}", line)).collect();
+    try!(written.map_err(|e| CliError::Failure(format!("cannot write the summary: {}", e))));
+    Ok(true)
+}
+
+/// A suppression of findings given with `--allow`.
+struct Suppression {
+    rule: String,
+    path: String,
+}
+
+impl Suppression {
+    fn parse(s: &str) -> Result<Suppression, CliError> {
+        match s.find(':') {
+            Some(i) => {
+                Ok(Suppression {
+                    rule: s[..i].trim().to_string(),
+                    path: s[i + 1..].trim().to_string(),
+                })
+            }
+            None => {
+                Err(CliError::Usage(format!("expected RULE:PATH, got `{}`", s), LINT_USAGE))
+            }
+        }
+    }
+
+    fn matches(&self, finding: &Finding) -> bool {
+        let rule_matches = self.rule == "*" || self.rule == finding.rule;
+        let path_matches = if self.path.ends_with('*') {
+            finding.path.starts_with(&self.path[..self.path.len() - 1])
+        } else {
+            self.path == finding.path
+        };
+        rule_matches && path_matches
+    }
+}
+
+/// Returns the rules named in the values of the option `name`, or an error if a rule doesn't
+/// exist.
+fn rules(args: &Args, name: &str) -> Result<Vec<String>, CliError> {
+    let mut rules = vec![];
+    for rule in args.values(name).iter().flat_map(|v| v.split(',')) {
+        let rule = rule.trim();
+        if !validate::RULES.contains(&rule) {
+            return Err(CliError::Usage(format!("unknown rule `{}`", rule), LINT_USAGE));
+        }
+        rules.push(rule.to_string());
+    }
+    Ok(rules)
+}
+
+fn lint(args: &[String]) -> Result<bool, CliError> {
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    lint_to(args, &mut out)
+}
+
+/// Runs the lint command, writing the findings to `out`.
+fn lint_to<W: Write>(args: &[String], out: &mut W) -> Result<bool, CliError> {
+    let args = match try!(parse_args(args,
+                                     LINT_USAGE,
+                                     &[],
+                                     &["--enable", "--disable", "--allow", "--allow-file",
+                                       "--format", "--fail-on"])) {
+        Some(args) => args,
+        None => {
+            println!("{}", LINT_USAGE);
+            return Ok(true);
+        }
+    };
+    if args.positional.is_empty() {
+        return Err(CliError::Usage("expected at least one FILE".to_string(), LINT_USAGE));
+    }
+
+    let enabled = try!(rules(&args, "--enable"));
+    let disabled = try!(rules(&args, "--disable"));
+    let mut suppressions = vec![];
+    for allow in args.values("--allow") {
+        suppressions.push(try!(Suppression::parse(allow)));
+    }
+    for path in args.values("--allow-file") {
+        let mut content = String::new();
+        try!(File::open(path)
+            .and_then(|mut f| f.read_to_string(&mut content))
+            .map_err(|e| CliError::Failure(format!("cannot read {}: {}", path, e))));
+        for line in content.lines().map(|l| l.trim()) {
+            if !line.is_empty() && !line.starts_with('#') {
+                suppressions.push(try!(Suppression::parse(line)));
+            }
+        }
+    }
+    let format = args.values("--format").last().cloned().unwrap_or("text");
+    if !["text", "json", "sarif"].contains(&format) {
+        return Err(CliError::Usage(format!("unknown format `{}`", format), LINT_USAGE));
+    }
+    let fail_on = match args.values("--fail-on").last().cloned().unwrap_or("error") {
+        "never" => None,
+        severity => {
+            Some(try!(severity.parse::<Severity>()
+                .map_err(|e| CliError::Usage(e.to_string(), LINT_USAGE))))
+        }
+    };
+
+    let mut findings = vec![];
+    let mut success = true;
+    for file in &args.positional {
+        let device = match read_device(file) {
+            Ok(device) => propagated(&device),
+            Err(CliError::Failure(message)) | Err(CliError::Usage(message, _)) => {
+                writeln!(io::stderr(), "svd: {}", message).ok();
+                success = false;
+                continue;
+            }
+        };
+        for finding in device.validate() {
+            let selected = (enabled.is_empty() || enabled.iter().any(|r| r == finding.rule)) &&
+                           !disabled.iter().any(|r| r == finding.rule) &&
+                           !suppressions.iter().any(|s| s.matches(&finding));
+            if selected {
+                findings.push((file.clone(), finding));
+            }
+        }
+    }
+
+    let written = match format {
+        "json" => write_json(out, &findings),
+        "sarif" => write_sarif(out, &findings),
+        _ => {
+            findings.iter()
+                .map(|&(ref file, ref finding)| writeln!(out, "{}: {}", file, finding))
+                .collect()
+        }
+    };
+    try!(written.map_err(|e| CliError::Failure(format!("cannot write the findings: {}", e))));
+
+    if let Some(fail_on) = fail_on {
+        if findings.iter().any(|&(_, ref finding)| finding.severity >= fail_on) {
+            success = false;
+        }
+    }
+    Ok(success)
+}
+
+/// Returns `s` as a JSON string.
+fn json_string(s: &str) -> String {
+    let mut json = "\"".to_string();
+    for c in s.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if (c as u32) < 0x20 => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+fn write_json<W: Write>(w: &mut W, findings: &[(String, Finding)]) -> io::Result<()> {
+    try!(writeln!(w, "["));
+    for (i, &(ref file, ref finding)) in findings.iter().enumerate() {
+        try!(writeln!(w,
+                      "  {{\"file\": {}, \"path\": {}, \"rule\": {}, \"severity\": {}, \
+                       \"message\": {}}}{}",
+                      json_string(file),
+                      json_string(&finding.path),
+                      json_string(finding.rule),
+                      json_string(finding.severity.as_str()),
+                      json_string(&finding.message),
+                      if i + 1 < findings.len() { "," } else { "" }));
+    }
+    writeln!(w, "]")
+}
+
+/// Writes the findings as a SARIF 2.1.0 log.
+fn write_sarif<W: Write>(w: &mut W, findings: &[(String, Finding)]) -> io::Result<()> {
+    let rules: Vec<_> = validate::RULES.iter()
+        .map(|rule| format!("{{\"id\": {}}}", json_string(rule)))
+        .collect();
+    let results: Vec<_> = findings.iter()
+        .map(|&(ref file, ref finding)| {
+            format!("{{\"ruleId\": {}, \"level\": {}, \"message\": {{\"text\": {}}}, \
+                     \"locations\": [{{\"physicalLocation\": {{\"artifactLocation\": \
+                     {{\"uri\": {}}}}}, \"logicalLocations\": [{{\"fullyQualifiedName\": \
+                     {}}}]}}]}}",
+                    json_string(finding.rule),
+                    json_string(finding.severity.as_str()),
+                    json_string(&finding.message),
+                    json_string(file),
+                    json_string(&finding.path))
+        })
+        .collect();
+
+    try!(writeln!(w, "{{"));
+    try!(writeln!(w, "  \"version\": \"2.1.0\","));
+    try!(writeln!(w,
+                  "  \"$schema\": \"https://json.schemastore.org/sarif-2.1.0.json\","));
+    try!(writeln!(w, "  \"runs\": [{{"));
+    try!(writeln!(w,
+                  "    \"tool\": {{\"driver\": {{\"name\": \"svd\", \"rules\": [{}]}}}},",
+                  rules.join(", ")));
+    try!(writeln!(w, "    \"results\": [{}]", results.join(",\n      ")));
+    try!(writeln!(w, "  }}]"));
+    writeln!(w, "}}")
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let result = match args.first().map(|a| &**a) {
+        Some("diff") => diff(&args[1..]),
+        Some("fmt") => fmt(&args[1..]),
+        Some("info") => info(&args[1..]),
+        Some("lint") => lint(&args[1..]),
+        Some("-h") | Some("--help") | Some("help") => {
+            println!("{}", USAGE);
+            Ok(true)
+        }
+        Some(command) => Err(CliError::Usage(format!("unknown command `{}`", command), USAGE)),
+        None => Err(CliError::Usage("missing command".to_string(), USAGE)),
+    };
+
+    match result {
+        Ok(true) => {}
+        Ok(false) => process::exit(1),
+        Err(CliError::Usage(message, usage)) => {
+            writeln!(io::stderr(), "svd: {}\n\n{}", message, usage).ok();
+            process::exit(2);
+        }
+        Err(CliError::Failure(message)) => {
+            writeln!(io::stderr(), "svd: {}", message).ok();
+            process::exit(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::path::PathBuf;
+    use super::*;
+
+    /// The registers of GPIOA overlap, and GPIOA.2ND has an invalid name.
+    const INVALID: &'static [u8] = b"<device><name>TEST</name><version>1.0</version>\
+        <description>Test</description><addressUnitBits>8</addressUnitBits><width>32</width>\
+        <peripherals><peripheral><name>GPIOA</name><baseAddress>0x0</baseAddress><registers>\
+        <register><name>MODER</name><addressOffset>0x0</addressOffset></register>\
+        <register><name>2ND</name><addressOffset>0x4</addressOffset></register>\
+        <register><name>OTYPER</name><addressOffset>0x4</addressOffset></register>\
+        </registers></peripheral></peripherals></device>";
+
+    /// Writes `content` to a file of the temporary directory, which is named after `name` and
+    /// the current process.
+    fn temp_file(name: &str, content: &[u8]) -> PathBuf {
+        let path = env::temp_dir().join(format!("svd-{}-{}", process::id(), name));
+        File::create(&path).unwrap().write_all(content).unwrap();
+        path
+    }
+
+    fn strings(args: &[&str]) -> Vec<String> {
+        args.iter().map(|a| a.to_string()).collect()
+    }
+
+    fn parse(args: &[&str]) -> Result<Option<Args>, CliError> {
+        let args: Vec<_> = args.iter().map(|a| a.to_string()).collect();
+        parse_args(&args, LINT_USAGE, &["--check"], &["--enable", "--format"])
+    }
+
+    fn usage_message(result: Result<Option<Args>, CliError>) -> String {
+        match result {
+            Err(CliError::Usage(message, _)) => message,
+            result => panic!("expected a usage error, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn positional() {
+        let args = parse(&["a.svd", "-", "b.svd"]).unwrap().unwrap();
+        assert_eq!(vec!["a.svd", "-", "b.svd"], args.positional);
+        assert!(args.options.is_empty());
+    }
+
+    #[test]
+    fn options() {
+        let args = parse(&["--enable=a,b", "a.svd", "--check", "--format", "json", "--enable", "c"])
+            .unwrap()
+            .unwrap();
+        assert_eq!(vec!["a.svd"], args.positional);
+        assert_eq!(vec!["a,b", "c"], args.values("--enable"));
+        assert_eq!(vec!["json"], args.values("--format"));
+        assert!(args.has("--check"));
+        assert!(!args.has("--disable"));
+        // A flag given a value is an unknown option
+        assert_eq!("unknown option `--check`", usage_message(parse(&["--check=yes"])));
+    }
+
+    #[test]
+    fn invalid() {
+        assert_eq!("unknown option `--bogus`", usage_message(parse(&["--bogus"])));
+        assert_eq!("`--format` needs a value", usage_message(parse(&["a.svd", "--format"])));
+        assert!(parse(&["a.svd", "--help", "--bogus"]).unwrap().is_none());
+        assert!(parse(&["-h"]).unwrap().is_none());
+    }
+
+    #[test]
+    fn fmt_files() {
+        let file = temp_file("fmt.svd", INVALID);
+        let path = file.to_str().unwrap();
+        let mut out = vec![];
+        assert_eq!(Ok(false), fmt_to(&strings(&["--check", path]), &mut out));
+        assert_eq!(format!("{}\n", path).into_bytes(), out);
+        let mut content = vec![];
+        File::open(&file).unwrap().read_to_end(&mut content).unwrap();
+        assert_eq!(INVALID, &content[..]);
+
+        // Formatting is idempotent, and the formatted file passes the check
+        assert_eq!(Ok(true), fmt_to(&strings(&[path]), &mut vec![]));
+        let mut formatted = vec![];
+        File::open(&file).unwrap().read_to_end(&mut formatted).unwrap();
+        assert!(formatted != INVALID);
+        let mut out = vec![];
+        assert_eq!(Ok(true), fmt_to(&strings(&["--check", path]), &mut out));
+        assert!(out.is_empty());
+        assert_eq!(Ok(true), fmt_to(&strings(&[path]), &mut vec![]));
+        let mut reformatted = vec![];
+        File::open(&file).unwrap().read_to_end(&mut reformatted).unwrap();
+        assert_eq!(formatted, reformatted);
+
+        assert!(fmt_to(&strings(&["--check"]), &mut vec![]).is_err());
+        match fmt_to(&strings(&["missing.svd"]), &mut vec![]) {
+            Err(CliError::Failure(message)) => assert!(message.starts_with("cannot read")),
+            result => panic!("expected a failure, got {:?}", result),
+        }
+        let invalid = temp_file("fmt-invalid.svd", b"<device>");
+        match fmt_to(&strings(&[invalid.to_str().unwrap()]), &mut vec![]) {
+            Err(CliError::Failure(message)) => assert!(message.starts_with("cannot parse")),
+            result => panic!("expected a failure, got {:?}", result),
+        }
+        fs::remove_file(file).ok();
+        fs::remove_file(invalid).ok();
+    }
+
+    #[test]
+    fn info_summary() {
+        let file = temp_file("summary.svd",
+                             b"<device><vendor>ACME</vendor><name>TEST</name>\
+            <version>1.0</version><description>Test</description>\
+            <cpu><name>CM4</name><revision>r0p1</revision></cpu>\
+            <addressUnitBits>8</addressUnitBits><width>32</width><peripherals>\
+            <peripheral><name>GPIOA</name><baseAddress>0x40020000</baseAddress>\
+            <addressBlock><offset>0x0</offset><size>0x400</size><usage>registers</usage>\
+            </addressBlock><interrupt><name>EXTI0</name><value>6</value></interrupt><registers>\
+            <register><name>MODER</name><addressOffset>0x0</addressOffset><fields>\
+            <field><name>MODER0</name><bitRange>[1:0]</bitRange></field>\
+            <field><name>MODER1</name><bitRange>[3:2]</bitRange></field></fields></register>\
+            <register><name>IDR</name><addressOffset>0x10</addressOffset></register>\
+            </registers></peripheral>\
+            <peripheral derivedFrom=\"GPIOA\"><name>GPIOB</name>\
+            <baseAddress>0x40020400</baseAddress></peripheral>\
+            <peripheral><name>RAM</name><baseAddress>0x0</baseAddress>\
+            <addressBlock><offset>0x0</offset><size>0xFFFFFFFFFFFFFFFF</size>\
+            <usage>buffer</usage></addressBlock>\
+            <addressBlock><offset>0x0</offset><size>0x10</size><usage>buffer</usage>\
+            </addressBlock></peripheral></peripherals></device>");
+        let mut out = vec![];
+        assert_eq!(Ok(true), info_to(&strings(&[file.to_str().unwrap()]), &mut out));
+        // The sizes of the address blocks saturate
+        assert_eq!("Device: TEST (version 1.0)
+Vendor: ACME
+CPU: CM4 r0p1
+Width: 32 bits, address unit: 8 bits
+Peripherals: 3, registers: 4, fields: 4, interrupts: 1
+
+Peripherals:
+    GPIOA  0x40020000   0x400 units
+    GPIOB  0x40020400   0x400 units  derived from GPIOA
+    RAM    0x00000000  0xFFFFFFFFFFFFFFFF units
+
+Groups of similar peripherals:
+    GPIO: GPIOA, GPIOB
+",
+                   String::from_utf8(out).unwrap());
+        fs::remove_file(file).ok();
+    }
+
+    #[test]
+    fn info_files() {
+        let file = temp_file("info.svd", INVALID);
+        let file = file.to_str().unwrap();
+        let mut out = vec![];
+        assert_eq!(Ok(true), info_to(&strings(&[file]), &mut out));
+        // The line of the CPU is omitted without CPU, and so is the list of groups without groups
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.starts_with("Device: TEST (version 1.0)\nWidth: "), "{}", out);
+        assert!(out.ends_with("Peripherals:\n    GPIOA  0x00000000\n"), "{}", out);
+        assert_eq!(Ok(true), info(&strings(&["--help"])));
+        assert!(info(&[]).is_err());
+        assert!(info(&strings(&[file, file])).is_err());
+        match info(&strings(&["missing.svd"])) {
+            Err(CliError::Failure(message)) => assert!(message.starts_with("cannot open")),
+            result => panic!("expected a failure, got {:?}", result),
+        }
+        fs::remove_file(file).ok();
+    }
+
+    #[test]
+    fn suppressions() {
+        let finding = |path: &str, rule| {
+            Finding {
+                path: path.to_string(),
+                severity: Severity::Error,
+                rule: rule,
+                message: String::new(),
+            }
+        };
+        let allow = |s| Suppression::parse(s).unwrap();
+        let overlap = finding("GPIOA.OTYPER", "register-overlap");
+        assert!(allow("register-overlap:GPIOA.OTYPER").matches(&overlap));
+        assert!(allow(" register-overlap : GPIOA.OTYPER ").matches(&overlap));
+        assert!(allow("*:GPIOA.OTYPER").matches(&overlap));
+        assert!(allow("register-overlap:GPIOA.*").matches(&overlap));
+        assert!(allow("*:*").matches(&overlap));
+        assert!(!allow("register-overlap:GPIOA").matches(&overlap));
+        assert!(!allow("register-overlap:GPIOB.*").matches(&overlap));
+        assert!(!allow("invalid-name:GPIOA.OTYPER").matches(&overlap));
+        match Suppression::parse("register-overlap") {
+            Err(CliError::Usage(message, _)) => {
+                assert_eq!("expected RULE:PATH, got `register-overlap`", message)
+            }
+            _ => panic!("expected a usage error"),
+        }
+    }
+
+    #[test]
+    fn rule_names() {
+        let args = parse(&["--enable", "invalid-name, register-overlap", "--enable=field-overlap"])
+            .unwrap()
+            .unwrap();
+        assert_eq!(vec!["invalid-name", "register-overlap", "field-overlap"],
+                   rules(&args, "--enable").unwrap());
+        assert!(rules(&args, "--disable").unwrap().is_empty());
+        let args = parse(&["--enable", "invalid-name,bogus"]).unwrap().unwrap();
+        assert!(rules(&args, "--enable").is_err());
+    }
+
+    #[test]
+    fn json() {
+        assert_eq!(r#""a \"b\" \\ \n\t\u0001""#, json_string("a \"b\" \\ \n\t\u{1}"));
+
+        let finding = Finding {
+            path: "GPIOA.OTYPER".to_string(),
+            severity: Severity::Warning,
+            rule: "misaligned-register",
+            message: "not \"aligned\"".to_string(),
+        };
+        let mut written = vec![];
+        write_json(&mut written, &[("a.svd".to_string(), finding.clone())]).unwrap();
+        assert_eq!("[\n  {\"file\": \"a.svd\", \"path\": \"GPIOA.OTYPER\", \
+                    \"rule\": \"misaligned-register\", \"severity\": \"warning\", \
+                    \"message\": \"not \\\"aligned\\\"\"}\n]\n",
+                   String::from_utf8(written).unwrap());
+
+        let mut written = vec![];
+        write_json(&mut written, &[]).unwrap();
+        assert_eq!(b"[\n]\n"[..], written[..]);
+
+        let mut written = vec![];
+        write_sarif(&mut written, &[("a.svd".to_string(), finding)]).unwrap();
+        let sarif = String::from_utf8(written).unwrap();
+        assert!(sarif.contains(r#""ruleId": "misaligned-register", "level": "warning""#));
+        assert!(sarif.contains(r#""artifactLocation": {"uri": "a.svd"}"#));
+        assert!(sarif.contains(r#""fullyQualifiedName": "GPIOA.OTYPER""#));
+    }
+
+    #[test]
+    fn lint_status() {
+        let file = temp_file("lint.svd", INVALID);
+        let lint_file = |args: &[&str]| {
+            let mut args = strings(args);
+            args.push(file.to_str().unwrap().to_string());
+            lint_to(&args, &mut vec![])
+        };
+
+        assert_eq!(Ok(false), lint_file(&[]));
+        assert_eq!(Ok(true), lint_file(&["--fail-on", "never"]));
+        assert_eq!(Ok(true), lint_file(&["--disable", "register-overlap,invalid-name"]));
+        assert_eq!(Ok(true),
+                   lint_file(&["--enable", "misaligned-register", "--fail-on", "warning"]));
+        assert_eq!(Ok(true),
+                   lint_file(&["--allow", "*:GPIOA.OTYPER", "--allow=invalid-name:GPIOA.*"]));
+
+        let allow_file = temp_file("lint.allow",
+                                   b"# Known problems\n\nregister-overlap:GPIOA.OTYPER\n");
+        let allow = format!("--allow-file={}", allow_file.to_str().unwrap());
+        assert_eq!(Ok(false), lint_file(&[&allow]));
+        assert_eq!(Ok(true), lint_file(&[&allow, "--disable", "invalid-name"]));
+
+        assert!(lint_file(&["--format", "xml"]).is_err());
+        assert!(lint_file(&["--fail-on", "always"]).is_err());
+        assert!(lint_to(&[], &mut vec![]).is_err());
+
+        let mut written = vec![];
+        let args = strings(&["--enable=invalid-name", file.to_str().unwrap()]);
+        assert_eq!(Ok(false), lint_to(&args, &mut written));
+        assert_eq!(format!("{}: error: GPIOA.2ND: \"2ND\" is not a valid identifier \
+                            [invalid-name]\n",
+                           file.to_str().unwrap()),
+                   String::from_utf8(written).unwrap());
+
+        fs::remove_file(&file).ok();
+        fs::remove_file(&allow_file).ok();
+    }
+}
@@ -1,11 +1,271 @@
 extern crate svd;
 
-use svd::codegen::rust;
+use std::env;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::process;
+use svd::codegen::rust::CodeGenerator;
+use svd::device::Device;
+
+const USAGE: &'static str = "Usage: svd2rust [OPTIONS] [INPUT] [OUTPUT]
+
+Generates the Rust code to access the registers of the device described by the SVD file INPUT
+and writes it to OUTPUT. INPUT and OUTPUT default to the standard input and output, `-` can also
+be used for them.
+
+Options:
+    --no-field              don't generate structs to access the fields of the registers
+    --no-group-fields       don't group similar fields behind an accessor with an index
+    --no-group-registers    don't group the registers repeated with a numeric suffix behind an
+                            accessor with an index
+    --no-bool-field         don't use `bool` for single bit fields
+    --no-group-peripherals  don't group similar peripherals behind a common struct
+    --ignore-register-names
+                            group peripherals whose registers have different names but the same
+                            offsets and contents
+    --ignore-reset-values   ignore the reset values when grouping peripherals
+    --ignore-modified-write-values
+                            ignore the modifiedWriteValues when grouping peripherals
+    --allow-register-superset
+                            group a peripheral with peripherals having the same registers and
+                            more
+    --enumerated-values-by-value
+                            compare the enumerated values by value instead of by name when
+                            grouping peripherals
+    --no-doc                don't generate doc comments from the descriptions
+    --check                 don't write OUTPUT, fail if it is not up to date
+    -h, --help              print this help
+
+Exit status: 0 on success, 1 if an error occurred or if OUTPUT is out of date with --check, 2 if
+the arguments are invalid.";
+
+#[derive(Debug)]
+struct Options {
+    input: Option<String>,
+    output: Option<String>,
+    with_field: bool,
+    group_fields: bool,
+    group_registers: bool,
+    bool_field: bool,
+    group_peripherals: bool,
+    ignore_register_names: bool,
+    ignore_reset_values: bool,
+    ignore_modified_write_values: bool,
+    allow_register_superset: bool,
+    enumerated_values_by_value: bool,
+    with_doc: bool,
+    check: bool,
+}
+
+/// Returns the options, or `None` if the help was requested.
+fn parse_args<I: Iterator<Item = String>>(args: I) -> Result<Option<Options>, String> {
+    let mut options = Options {
+        input: None,
+        output: None,
+        with_field: true,
+        group_fields: true,
+        group_registers: true,
+        bool_field: true,
+        group_peripherals: true,
+        ignore_register_names: false,
+        ignore_reset_values: false,
+        ignore_modified_write_values: false,
+        allow_register_superset: false,
+        enumerated_values_by_value: false,
+        with_doc: true,
+        check: false,
+    };
+    let mut paths = vec![];
+
+    for arg in args {
+        match &*arg {
+            "--no-field" => options.with_field = false,
+            "--no-group-fields" => options.group_fields = false,
+            "--no-group-registers" => options.group_registers = false,
+            "--no-bool-field" => options.bool_field = false,
+            "--no-group-peripherals" => options.group_peripherals = false,
+            "--ignore-register-names" => options.ignore_register_names = true,
+            "--ignore-reset-values" => options.ignore_reset_values = true,
+            "--ignore-modified-write-values" => options.ignore_modified_write_values = true,
+            "--allow-register-superset" => options.allow_register_superset = true,
+            "--enumerated-values-by-value" => options.enumerated_values_by_value = true,
+            "--no-doc" => options.with_doc = false,
+            "--check" => options.check = true,
+            "-h" | "--help" => return Ok(None),
+            "-" => paths.push(None),
+            _ if arg.starts_with('-') => return Err(format!("unknown option `{}`", arg)),
+            _ => paths.push(Some(arg)),
+        }
+    }
+
+    if paths.len() > 2 {
+        return Err("too many arguments".to_string());
+    }
+    let mut paths = paths.into_iter();
+    options.input = paths.next().and_then(|p| p);
+    options.output = paths.next().and_then(|p| p);
+
+    if options.check && options.output.is_none() {
+        return Err("--check needs an OUTPUT file".to_string());
+    }
+
+    Ok(Some(options))
+}
+
+/// Generates the code. Returns false if `--check` was given and the output file is out of date.
+fn run(options: &Options) -> Result<bool, String> {
+    let input_name = options.input.as_ref().map_or("<stdin>", |i| &**i);
+    let device = match options.input {
+        Some(ref input) => {
+            let f = try!(File::open(input)
+                .map_err(|e| format!("cannot open {}: {}", input_name, e)));
+            Device::from_reader(f)
+        }
+        None => Device::from_reader(io::stdin()),
+    };
+    let device = try!(device.map_err(|e| format!("cannot parse {}: {}", input_name, e)));
+
+    let mut code = vec![];
+    {
+        let mut code_generator = CodeGenerator::new(&mut code)
+            .with_field(options.with_field)
+            .group_fields(options.group_fields)
+            .group_registers(options.group_registers)
+            .bool_field(options.bool_field)
+            .group_peripherals(options.group_peripherals)
+            .ignore_register_names(options.ignore_register_names)
+            .ignore_reset_values(options.ignore_reset_values)
+            .ignore_modified_write_values(options.ignore_modified_write_values)
+            .allow_register_superset(options.allow_register_superset)
+            .enumerated_values_by_value(options.enumerated_values_by_value)
+            .with_doc(options.with_doc);
+        try!(code_generator.generate_device(&device)
+            .map_err(|e| format!("cannot generate the code for {}: {}", input_name, e)));
+    }
+
+    match options.output {
+        Some(ref output) if options.check => {
+            let mut existing = vec![];
+            let read = File::open(output).and_then(|mut f| f.read_to_end(&mut existing));
+            match read {
+                Ok(_) => Ok(existing == code),
+                Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(false),
+                Err(e) => Err(format!("cannot read {}: {}", output, e)),
+            }
+        }
+        Some(ref output) => {
+            try!(File::create(output)
+                .and_then(|mut f| f.write_all(&code))
+                .map_err(|e| format!("cannot write {}: {}", output, e)));
+            Ok(true)
+        }
+        None => {
+            let stdout = io::stdout();
+            try!(stdout.lock()
+                .write_all(&code)
+                .map_err(|e| format!("cannot write to the standard output: {}", e)));
+            Ok(true)
+        }
+    }
+}
 
 fn main() {
-    let input = std::io::stdin();
-    let mut output = std::io::stdout();
-    let d = svd::device::Device::from_reader(input).unwrap();
-    let mut code_generator = rust::CodeGenerator::new(&mut output);
-    code_generator.generate_device(&d).unwrap();
+    let options = match parse_args(env::args().skip(1)) {
+        Ok(Some(options)) => options,
+        Ok(None) => {
+            println!("{}", USAGE);
+            return;
+        }
+        Err(message) => {
+            writeln!(io::stderr(), "svd2rust: {}\n\n{}", message, USAGE).ok();
+            process::exit(2);
+        }
+    };
+
+    match run(&options) {
+        Ok(true) => {}
+        Ok(false) => {
+            writeln!(io::stderr(),
+                     "svd2rust: {} is out of date",
+                     options.output.as_ref().unwrap())
+                .ok();
+            process::exit(1);
+        }
+        Err(message) => {
+            writeln!(io::stderr(), "svd2rust: {}", message).ok();
+            process::exit(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs::{self, File};
+    use std::io::Write;
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Option<Options>, String> {
+        parse_args(args.iter().map(|a| a.to_string()))
+    }
+
+    #[test]
+    fn paths() {
+        let options = parse(&[]).unwrap().unwrap();
+        assert_eq!((None, None), (options.input, options.output));
+
+        let options = parse(&["-", "out.rs"]).unwrap().unwrap();
+        assert_eq!((None, Some("out.rs".to_string())), (options.input, options.output));
+
+        let options = parse(&["in.svd", "-"]).unwrap().unwrap();
+        assert_eq!((Some("in.svd".to_string()), None), (options.input, options.output));
+
+        assert_eq!("too many arguments", parse(&["a", "b", "c"]).unwrap_err());
+    }
+
+    #[test]
+    fn flags() {
+        assert!(parse(&["--help"]).unwrap().is_none());
+        assert!(parse(&["in.svd", "-h"]).unwrap().is_none());
+        assert_eq!("unknown option `--bogus`", parse(&["--bogus"]).unwrap_err());
+
+        let options = parse(&["--no-field", "--allow-register-superset", "--no-doc"])
+            .unwrap()
+            .unwrap();
+        assert!(!options.with_field && options.allow_register_superset && !options.with_doc);
+        assert!(options.group_fields && options.group_registers && !options.check);
+    }
+
+    #[test]
+    fn check() {
+        assert_eq!("--check needs an OUTPUT file", parse(&["--check"]).unwrap_err());
+        assert_eq!("--check needs an OUTPUT file",
+                   parse(&["--check", "in.svd", "-"]).unwrap_err());
+
+        let dir = env::temp_dir();
+        let input = dir.join(format!("svd2rust-check-{}.svd", process::id()));
+        let output = dir.join(format!("svd2rust-check-{}.rs", process::id()));
+        File::create(&input)
+            .unwrap()
+            .write_all(b"<device><name>TEST</name><version>1.0</version>\
+                         <description>Test</description><addressUnitBits>8</addressUnitBits>\
+                         <width>32</width><peripherals/></device>")
+            .unwrap();
+        let args = |check| {
+            let mut args = vec![input.to_str().unwrap(), output.to_str().unwrap()];
+            if check {
+                args.insert(0, "--check");
+            }
+            parse(&args).unwrap().unwrap()
+        };
+
+        assert_eq!(Ok(false), run(&args(true)));
+        assert_eq!(Ok(true), run(&args(false)));
+        assert_eq!(Ok(true), run(&args(true)));
+        File::create(&output).unwrap().write_all(b"// edited").unwrap();
+        assert_eq!(Ok(false), run(&args(true)));
+
+        fs::remove_file(&input).ok();
+        fs::remove_file(&output).ok();
+    }
 }
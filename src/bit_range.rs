@@ -1,8 +1,13 @@
 use errors::*;
 use types::*;
-use utils::get_child_text;
+use utils::{add_child_text, get_child_text};
 use xmltree;
 
+/// The names of the elements that can describe a bit range.
+pub const ELEMENT_NAMES: &'static [&'static str] = &["bitOffset", "bitWidth", "lsb", "msb",
+                                                     "bitRange"];
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub struct BitRange {
     pub lsb: u32,
@@ -17,20 +22,25 @@ impl BitRange {
         let msb = get_child_text(element, "msb");
         let bit_range = get_child_text(element, "bitRange");
         if let (Some(offset), Some(width)) = (offset, width) {
-            let offset: ScaledNonNegativeInteger = try!(offset.parse());
-            let offset = offset.0 as u32;
+            let lsb = try!(parse_bit_number(&offset));
             let width: ScaledNonNegativeInteger = try!(width.parse());
-            let width = width.0 as u32;
-
-            Ok(BitRange {
-                lsb: offset,
-                msb: offset + width - 1,
-            })
+            let msb = (lsb as u64 + width.0).checked_sub(1);
+            match msb {
+                Some(msb) if msb <= u32::max_value() as u64 => {
+                    Ok(BitRange {
+                        lsb: lsb,
+                        msb: msb as u32,
+                    })
+                }
+                _ => {
+                    Err(ErrorKind::UnexpectedValue("a bitWidth ending the field in 32 bits",
+                                                   width.0.to_string())
+                        .into())
+                }
+            }
         } else if let (Some(lsb), Some(msb)) = (lsb, msb) {
-            let lsb: ScaledNonNegativeInteger = try!(lsb.parse());
-            let lsb = lsb.0 as u32;
-            let msb: ScaledNonNegativeInteger = try!(msb.parse());
-            let msb = msb.0 as u32;
+            let lsb = try!(parse_bit_number(&lsb));
+            let msb = try!(parse_bit_number(&msb));
 
             Ok(BitRange {
                 lsb: lsb,
@@ -44,8 +54,8 @@ impl BitRange {
                     .into())
             } else {
                 let colon_pos = colon_pos.unwrap();
-                let msb: u32 = try!(bit_range[1..colon_pos].parse());
-                let lsb: u32 = try!(bit_range[colon_pos + 1..bit_range.len() - 1].parse());
+                let msb = try!(parse_bit_number(&bit_range[1..colon_pos]));
+                let lsb = try!(parse_bit_number(&bit_range[colon_pos + 1..bit_range.len() - 1]));
                 Ok(BitRange {
                     lsb: lsb,
                     msb: msb,
@@ -58,7 +68,101 @@ impl BitRange {
         }
     }
 
+    /// Appends the elements describing this range to `element`.
+    pub fn add_to_element(&self, element: &mut xmltree::Element) {
+        if self.msb >= self.lsb {
+            add_child_text(element, "bitOffset", self.lsb);
+            add_child_text(element, "bitWidth", self.width());
+        } else {
+            add_child_text(element, "lsb", self.lsb);
+            add_child_text(element, "msb", self.msb);
+        }
+    }
+
+    /// Returns the number of bits of the range, or 0 if `msb` is lower than `lsb`.
     pub fn width(&self) -> u32 {
-        self.msb - self.lsb + 1
+        if self.msb < self.lsb {
+            0
+        } else {
+            self.msb - self.lsb + 1
+        }
+    }
+
+    /// Returns the mask of the bits of the range, for example `0b1100` for `[3:2]`.
+    pub fn mask(&self) -> u64 {
+        if self.lsb >= 64 {
+            0
+        } else if self.width() >= 64 {
+            !0 << self.lsb
+        } else {
+            ((1 << self.width()) - 1) << self.lsb
+        }
+    }
+}
+
+/// Parses the number of a bit, which must fit in 32 bits.
+fn parse_bit_number(s: &str) -> Result<u32> {
+    let n: ScaledNonNegativeInteger = try!(s.parse());
+    if n.0 > u32::max_value() as u64 {
+        Err(ErrorKind::UnexpectedValue("a bit number that fits in 32 bits", s.to_string()).into())
+    } else {
+        Ok(n.0 as u32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use utils::new_element;
+    use xml_fragment::parse_element;
+
+    fn parse(xml: &str) -> BitRange {
+        BitRange::from_element(&parse_element(xml.as_bytes()).unwrap()).unwrap()
+    }
+
+    fn round_trip(bit_range: BitRange) -> BitRange {
+        let mut element = new_element("field");
+        bit_range.add_to_element(&mut element);
+        BitRange::from_element(&element).unwrap()
+    }
+
+    #[test]
+    fn forms() {
+        let bit_range = BitRange { lsb: 4, msb: 7 };
+        assert_eq!(bit_range, parse("<f><bitOffset>4</bitOffset><bitWidth>4</bitWidth></f>"));
+        assert_eq!(bit_range, parse("<f><lsb>4</lsb><msb>7</msb></f>"));
+        assert_eq!(bit_range, parse("<f><bitRange>[7:4]</bitRange></f>"));
+        let element = parse_element(&b"<f><bitRange>7:4</bitRange></f>"[..]).unwrap();
+        assert!(BitRange::from_element(&element).is_err());
+    }
+
+    #[test]
+    fn bit_numbers_overflow() {
+        let max = u32::max_value();
+        assert_eq!(BitRange { lsb: max, msb: max },
+                   parse("<f><bitOffset>4294967295</bitOffset><bitWidth>1</bitWidth></f>"));
+        assert_eq!(BitRange { lsb: 0, msb: max },
+                   parse("<f><bitRange>[4294967295:0]</bitRange></f>"));
+        for xml in &["<f><bitRange>[4294967296:0]</bitRange></f>",
+                     "<f><bitRange>[7:4294967296]</bitRange></f>",
+                     "<f><lsb>4294967296</lsb><msb>4294967297</msb></f>",
+                     "<f><lsb>0</lsb><msb>4294967296</msb></f>",
+                     "<f><bitOffset>4294967296</bitOffset><bitWidth>1</bitWidth></f>",
+                     "<f><bitOffset>4294967295</bitOffset><bitWidth>2</bitWidth></f>",
+                     "<f><bitOffset>0</bitOffset><bitWidth>0</bitWidth></f>"] {
+            let element = parse_element(xml.as_bytes()).unwrap();
+            match BitRange::from_element(&element) {
+                Err(Error(ErrorKind::UnexpectedValue(..), _)) => {}
+                result => panic!("expected an unexpected value for {}, got {:?}", xml, result),
+            }
+        }
+    }
+
+    #[test]
+    fn write() {
+        for &(lsb, msb) in &[(0, 0), (4, 7), (0, 63), (7, 4)] {
+            let bit_range = BitRange { lsb: lsb, msb: msb };
+            assert_eq!(bit_range, round_trip(bit_range));
+        }
     }
 }
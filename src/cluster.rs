@@ -1,22 +1,41 @@
-use dim_element_group::DimElementGroup;
+use dim_element_group::{self, DimElementGroup};
 use errors::*;
-use is_similar::{IsSimilar, IsSimilarOptions};
+use is_similar::{Difference, ExplainDifferences, IsSimilar, IsSimilarOptions,
+                 explain_subset_differences, is_similar_subset};
 use register_or_cluster::RegisterOrCluster;
-use register_properties_group::RegisterPropertiesGroup;
+use register_properties_group::{self, RegisterPropertiesGroup};
 use types::*;
-use utils::get_child_text;
+use utils::{add_child_text, add_optional_child_text, element_path_segment, get_child_text,
+            new_element};
+use xml_fragment::XmlFragment;
 use xmltree;
 
+/// The names of the child elements read by `from_element`.
+const ELEMENT_NAMES: &'static [&'static str] = &["name", "description", "alternateCluster",
+                                                 "headerStructName", "addressOffset", "register",
+                                                 "cluster"];
+
+/// The names of the child elements in the order required by the schema, including the ones not
+/// read by `from_element`.
+const SCHEMA_ORDER: &'static [&'static str] = &["dim", "dimIncrement", "dimIndex", "dimName",
+                                                "dimArrayIndex", "name", "description",
+                                                "alternateCluster", "headerStructName",
+                                                "addressOffset", "size", "access", "protection",
+                                                "resetValue", "resetMask", "register", "cluster"];
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Cluster {
     pub derived_from: Option<IdentifierType>,
     pub dim_element: DimElementGroup,
     pub name: IdentifierType,
+    pub description: Option<String>,
     pub alternate_cluster: Option<IdentifierType>,
     pub header_struct_name: Option<IdentifierType>,
     pub address_offset: ScaledNonNegativeInteger,
     pub register_properties: RegisterPropertiesGroup,
     pub registers: Vec<RegisterOrCluster>,
+    pub unknown_elements: Vec<XmlFragment>,
 }
 
 impl Cluster {
@@ -24,6 +43,7 @@ impl Cluster {
         let derived_from = element.attributes.get("derivedFrom").cloned();
         let dim_element = try!(DimElementGroup::from_element(element));
         let name = get_mandatory_child_text!(element, "cluster", "name");
+        let description = get_child_text(element, "description");
         let alternate_cluster = get_child_text(element, "alternateCluster");
         let header_struct_name = get_child_text(element, "headerStructName");
         let address_offset = get_mandatory_child_text!(element, "cluster", "addressOffset");
@@ -31,23 +51,51 @@ impl Cluster {
         let registers: Result<Vec<_>> = element.children
             .iter()
             .filter(|e| e.name == "register" || e.name == "cluster")
-            .map(RegisterOrCluster::from_element)
+            .enumerate()
+            .map(|(i, e)| RegisterOrCluster::from_element(e).in_element(element_path_segment(e, i)))
             .collect();
         let registers = try!(registers);
+        let known_names = [ELEMENT_NAMES,
+                           dim_element_group::ELEMENT_NAMES,
+                           register_properties_group::ELEMENT_NAMES];
+        let unknown_elements = XmlFragment::unknown_children(element, &known_names);
         let address_offset = try!(address_offset.parse());
 
         Ok(Cluster {
             derived_from: derived_from,
             dim_element: dim_element,
             name: name,
+            description: description,
             alternate_cluster: alternate_cluster,
             header_struct_name: header_struct_name,
             address_offset: address_offset,
             register_properties: register_properties,
             registers: registers,
+            unknown_elements: unknown_elements,
         })
     }
 
+    pub fn to_element(&self) -> xmltree::Element {
+        let mut element = new_element("cluster");
+        if let Some(ref derived_from) = self.derived_from {
+            element.attributes.insert("derivedFrom".to_string(), derived_from.clone());
+        }
+        self.dim_element.add_to_element(&mut element);
+        add_child_text(&mut element, "name", &self.name);
+        add_optional_child_text(&mut element, "description", &self.description);
+        add_optional_child_text(&mut element, "alternateCluster", &self.alternate_cluster);
+        add_optional_child_text(&mut element, "headerStructName", &self.header_struct_name);
+        add_child_text(&mut element,
+                       "addressOffset",
+                       format!("0x{:X}", self.address_offset));
+        self.register_properties.add_to_element(&mut element);
+        for r_or_c in &self.registers {
+            element.children.push(r_or_c.to_element());
+        }
+        XmlFragment::add_to_element(&mut element, &self.unknown_elements, SCHEMA_ORDER);
+        element
+    }
+
     pub fn propagate_register_properties(&mut self,
                                          register_properties: &RegisterPropertiesGroup) {
         self.register_properties = self.register_properties.merge(register_properties);
@@ -59,6 +107,7 @@ impl Cluster {
     pub fn merge_derived_from(&mut self, derived_from: &Cluster) {
         self.derived_from = derived_from.derived_from.clone();
         self.dim_element.merge_derived_from(&derived_from.dim_element);
+        merge_option_field!(self.description, derived_from.description);
         merge_option_field!(self.alternate_cluster, derived_from.alternate_cluster);
         merge_option_field!(self.header_struct_name, derived_from.header_struct_name);
         self.register_properties = self.register_properties
@@ -93,8 +142,61 @@ impl Cluster {
 
 impl<'a, 'b> IsSimilar<&'a Cluster> for &'b Cluster {
     fn is_similar(self, other: &Cluster, options: &IsSimilarOptions) -> bool {
-        self.name == other.name && self.address_offset == other.address_offset &&
-        self.register_properties == other.register_properties &&
-        self.registers.is_similar(&other.registers, options)
+        let registers_similar = if options.allow_register_superset() {
+            is_similar_subset(&self.registers, &other.registers, options)
+        } else {
+            self.registers.is_similar(&other.registers, options)
+        };
+        (options.ignore_register_names() || self.name == other.name) &&
+        self.address_offset == other.address_offset &&
+        self.register_properties.is_similar(&other.register_properties, options) &&
+        registers_similar
+    }
+}
+
+impl<'a, 'b> ExplainDifferences<&'a Cluster> for &'b Cluster {
+    fn explain_differences(self, other: &Cluster, options: &IsSimilarOptions) -> Vec<Difference> {
+        let name = &*self.name;
+        let mut differences = vec![];
+        if !options.ignore_register_names() && self.name != other.name {
+            differences.push(Difference::new(name, "name"));
+        }
+        if self.address_offset != other.address_offset {
+            differences.push(Difference::new(name, "addressOffset"));
+        }
+        differences.extend(self.register_properties
+            .explain_differences(&other.register_properties, options)
+            .into_iter()
+            .map(|d| Difference::new(name, d.property)));
+        let registers_differences = if options.allow_register_superset() {
+            explain_subset_differences(&self.registers, &other.registers, options)
+        } else {
+            self.registers.explain_differences(&other.registers, options)
+        };
+        differences.extend(Difference::nested(name, "registers", registers_differences));
+        differences
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use xml_fragment::parse_element;
+
+    #[test]
+    fn unknown_elements_in_schema_order() {
+        let xml = r#"<cluster>
+                         <name>CH</name>
+                         <description>A channel</description>
+                         <addressOffset>0x8</addressOffset>
+                         <register>
+                             <name>CCR</name>
+                             <addressOffset>0x0</addressOffset>
+                         </register>
+                     </cluster>"#;
+        let cluster = Cluster::from_element(&parse_element(xml.as_bytes()).unwrap()).unwrap();
+        let element = cluster.to_element();
+        let names: Vec<_> = element.children.iter().map(|c| &*c.name).collect();
+        assert_eq!(vec!["name", "description", "addressOffset", "register"], names);
     }
 }
@@ -1,3 +1,4 @@
+use std::fmt;
 use std::io;
 
 #[derive(Debug)]
@@ -13,3 +14,12 @@ impl From<io::Error> for CodegenError {
         CodegenError::IoError(e)
     }
 }
+
+impl fmt::Display for CodegenError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CodegenError::IoError(ref e) => write!(f, "{}", e),
+            CodegenError::UnsupportedFeature => write!(f, "the device uses an unsupported feature"),
+        }
+    }
+}
@@ -3,7 +3,7 @@ use device::Device;
 use field::{Field, FieldsGroup};
 use is_similar::IsSimilarOptions;
 use peripheral::{Peripheral, PeripheralsGroup};
-use register::Register;
+use register::{Register, RegistersGroup};
 use register_or_cluster::RegisterOrCluster;
 use std::fmt::Display;
 use std::io;
@@ -33,8 +33,10 @@ pub struct CodeGenerator<W: Write> {
     out: W,
     with_field: bool,
     group_fields: bool,
+    group_registers: bool,
     bool_field: bool,
     group_peripherals: bool,
+    similar_options: IsSimilarOptions,
     with_doc: bool,
 }
 
@@ -45,8 +47,10 @@ impl<W: Write> CodeGenerator<W> {
             out: out,
             with_field: true,
             group_fields: true,
+            group_registers: true,
             bool_field: true,
             group_peripherals: true,
+            similar_options: IsSimilarOptions::new(),
             with_doc: true,
         }
     }
@@ -63,6 +67,13 @@ impl<W: Write> CodeGenerator<W> {
         self
     }
 
+    /// If true, the registers repeated with a numeric suffix are grouped in structs that can be
+    /// accessed with an index.
+    pub fn group_registers(mut self, group_registers: bool) -> CodeGenerator<W> {
+        self.group_registers = group_registers;
+        self
+    }
+
     /// If true, use `bool` for single bit fields.
     pub fn bool_field(mut self, bool_field: bool) -> CodeGenerator<W> {
         self.bool_field = bool_field;
@@ -75,6 +86,39 @@ impl<W: Write> CodeGenerator<W> {
         self
     }
 
+    /// If true, the registers are compared by their offset and their content only when grouping
+    /// peripherals.
+    pub fn ignore_register_names(mut self, value: bool) -> CodeGenerator<W> {
+        self.similar_options.set_ignore_register_names(value);
+        self
+    }
+
+    /// If true, the reset values are ignored when grouping peripherals.
+    pub fn ignore_reset_values(mut self, value: bool) -> CodeGenerator<W> {
+        self.similar_options.set_ignore_reset_values(value);
+        self
+    }
+
+    /// If true, the `modifiedWriteValues` are ignored when grouping peripherals.
+    pub fn ignore_modified_write_values(mut self, value: bool) -> CodeGenerator<W> {
+        self.similar_options.set_ignore_modified_write_values(value);
+        self
+    }
+
+    /// If true, a peripheral can be grouped with peripherals having more registers, the struct of
+    /// the group then has the registers of the peripheral whose registers include all the others.
+    pub fn allow_register_superset(mut self, value: bool) -> CodeGenerator<W> {
+        self.similar_options.set_allow_register_superset(value);
+        self
+    }
+
+    /// If true, the enumerated values are compared by value instead of by name when grouping
+    /// peripherals.
+    pub fn enumerated_values_by_value(mut self, value: bool) -> CodeGenerator<W> {
+        self.similar_options.set_enumerated_values_by_value(value);
+        self
+    }
+
     /// If true, add doc comment with content from the description fields
     pub fn with_doc(mut self, with_doc: bool) -> CodeGenerator<W> {
         self.with_doc = with_doc;
@@ -116,7 +160,7 @@ impl<W: Write> CodeGenerator<W> {
         self.indent();
 
         if self.group_peripherals {
-            let mut options = IsSimilarOptions::new();
+            let mut options = self.similar_options.clone();
             options.set_ignore_fields(!self.with_field);
             let (groups, individuals) = PeripheralsGroup::from_peripherals(&d.peripherals,
                                                                            &options);
@@ -159,16 +203,18 @@ impl<W: Write> CodeGenerator<W> {
                         peripheral.base_address.0);
         }
 
-        for peripheral in pg.peripherals() {
-            if let Some(ref registers) = peripheral.registers {
-                for r in registers {
-                    if let RegisterOrCluster::Register(ref r) = *r {
-                        try!(self.generate_register_for_group(r, pg));
-                    } else {
-                        return Err(CodegenError::UnsupportedFeature);
-                    }
-                }
-                break;
+        if let Some(registers) = pg.registers() {
+            let (groups, individuals) = try!(self.registers_groups(registers));
+            for g in &groups {
+                try!(self.generate_registers_group(g));
+                write_line!(self, "impl {} {{", pg.struct_name());
+                self.indent();
+                try!(self.generate_registers_group_accessor(g, "&self, ", "self.base_address"));
+                self.deindent();
+                write_line!(self, "}}");
+            }
+            for r in &individuals {
+                try!(self.generate_register_for_group(r, pg));
             }
         }
         self.deindent();
@@ -191,18 +237,92 @@ impl<W: Write> CodeGenerator<W> {
                                          peripheral: &Peripheral)
                                          -> Result<(), CodegenError> {
         if let Some(ref registers) = peripheral.registers {
-            for r in registers {
-                if let RegisterOrCluster::Register(ref r) = *r {
-                    try!(self.generate_register(r, peripheral));
-                } else {
-                    return Err(CodegenError::UnsupportedFeature);
-                }
+            let (groups, individuals) = try!(self.registers_groups(registers));
+            for g in &groups {
+                try!(self.generate_registers_group(g));
+                let base_address = format!("0x{:x}", peripheral.base_address.0);
+                try!(self.generate_registers_group_accessor(g, "", &base_address));
+            }
+            for r in &individuals {
+                try!(self.generate_register(r, peripheral));
             }
         }
 
         Ok(())
     }
 
+    /// Returns the groups of registers found in `registers` if `group_registers` is set, and the
+    /// other registers.
+    fn registers_groups(&self,
+                        registers: &[RegisterOrCluster])
+                        -> Result<(Vec<RegistersGroup>, Vec<Register>), CodegenError> {
+        let mut plain_registers = vec![];
+        for r in registers {
+            if let RegisterOrCluster::Register(ref r) = *r {
+                plain_registers.push(r);
+            } else {
+                return Err(CodegenError::UnsupportedFeature);
+            }
+        }
+
+        if self.group_registers {
+            Ok(RegistersGroup::from_registers(plain_registers))
+        } else {
+            Ok((vec![], plain_registers.into_iter().cloned().collect()))
+        }
+    }
+
+    /// Generates the struct of an element of the group and its registers.
+    pub fn generate_registers_group(&mut self, g: &RegistersGroup) -> Result<(), CodegenError> {
+        write_line!(self, "#[derive(Copy, Clone, PartialEq, Eq)]");
+        write_line!(self, "pub struct {} {{ pub base_address: usize }}", g.name());
+        for r in g.registers() {
+            try!(self.generate_register_for_struct(r, g.name()));
+        }
+        Ok(())
+    }
+
+    /// Generates the function returning an element of the group from its index, which is the
+    /// suffix of the names of its registers. `self_param` is the parameter added before the index
+    /// and `base_address` the expression of the address that the group is relative to.
+    pub fn generate_registers_group_accessor(&mut self,
+                                             g: &RegistersGroup,
+                                             self_param: &str,
+                                             base_address: &str)
+                                             -> Result<(), CodegenError> {
+        let end = g.first_index() + g.count();
+        write_line!(self,
+                    "pub fn {}({}index: usize) -> {} {{",
+                    g.accessor_name(),
+                    self_param,
+                    g.name());
+        self.indent();
+        if g.first_index() == 0 {
+            write_line!(self, "assert!(index < {});", end);
+            write_line!(self,
+                        "{} {{ base_address: {} + 0x{:x} + index * 0x{:x} }}",
+                        g.name(),
+                        base_address,
+                        g.address_offset(),
+                        g.increment());
+        } else {
+            write_line!(self,
+                        "assert!(index >= {} && index < {});",
+                        g.first_index(),
+                        end);
+            write_line!(self,
+                        "{} {{ base_address: {} + 0x{:x} + (index - {}) * 0x{:x} }}",
+                        g.name(),
+                        base_address,
+                        g.address_offset(),
+                        g.first_index(),
+                        g.increment());
+        }
+        self.deindent();
+        write_line!(self, "}}");
+        Ok(())
+    }
+
     pub fn generate_register(&mut self, r: &Register, p: &Peripheral) -> Result<(), CodegenError> {
         let address = p.base_address.0 + r.address_offset.0;
         let ty = try!(self.generate_fields(r));
@@ -244,8 +364,17 @@ impl<W: Write> CodeGenerator<W> {
                                        r: &Register,
                                        pg: &PeripheralsGroup)
                                        -> Result<(), CodegenError> {
+        self.generate_register_for_struct(r, pg.struct_name())
+    }
+
+    /// Generates the functions to access the register `r` as methods of the struct `struct_name`,
+    /// which has a `base_address`.
+    pub fn generate_register_for_struct(&mut self,
+                                        r: &Register,
+                                        struct_name: &str)
+                                        -> Result<(), CodegenError> {
         let ty = try!(self.generate_fields(r));
-        write_line!(self, "impl {} {{", pg.struct_name());
+        write_line!(self, "impl {} {{", struct_name);
         self.indent();
         if r.is_read() {
             try!(self.generate_doc(&r.description));
@@ -438,3 +567,88 @@ impl<W: Write> CodeGenerator<W> {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use device::Device;
+    use field::Field;
+    use peripheral::Peripheral;
+    use register::Register;
+    use std::env;
+    use std::fs::{self, File};
+    use std::io::Write;
+    use std::process::{self, Command};
+    use super::*;
+
+    /// Compiles `code` as a library and returns the error messages of the compiler if it fails.
+    fn compile(code: &[u8]) -> Result<(), String> {
+        let dir = env::temp_dir();
+        let source = dir.join(format!("svd-codegen-{}.rs", process::id()));
+        let mut f = File::create(&source).unwrap();
+        f.write_all(b"extern crate core;\n").unwrap();
+        f.write_all(code).unwrap();
+        drop(f);
+        let rustc = env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+        let output = Command::new(rustc)
+            .args(&["--crate-type", "lib", "--emit", "metadata", "--out-dir"])
+            .arg(&dir)
+            .arg(&source)
+            .output()
+            .unwrap();
+        fs::remove_file(&source).ok();
+        if output.status.success() {
+            Ok(())
+        } else {
+            Err(String::from_utf8_lossy(&output.stderr).into_owned())
+        }
+    }
+
+    fn register(name: &str, offset: u64) -> Register {
+        Register::builder(name, offset)
+            .field(Field::builder("V", 0..=7).build().unwrap())
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn registers_groups_compile() {
+        // `DR` is also the name of the registers of the group DR1/SR1/DR2/SR2
+        let usart = Peripheral::builder("USART1", 0x4001_3800)
+            .register(register("DR", 0x0))
+            .register(register("DR1", 0x4))
+            .register(register("SR1", 0x8))
+            .register(register("DR2", 0xc))
+            .register(register("SR2", 0x10));
+        let dma = |name: &str, base_address| {
+            let mut dma = Peripheral::builder(name, base_address);
+            for channel in 1..4 {
+                let offset = 0x8 + (channel - 1) * 0x14;
+                dma = dma.register(register(&format!("CCR{}", channel), offset))
+                    .register(register(&format!("CNDTR{}", channel), offset + 0x4));
+            }
+            dma.build().unwrap()
+        };
+        let device = Device::builder("STM32X")
+            .version("1.0")
+            .description("An example device")
+            .peripheral(usart.build().unwrap())
+            .peripheral(dma("DMA1", 0x4002_0000))
+            .peripheral(dma("DMA2", 0x4002_0400))
+            .build()
+            .unwrap();
+
+        for &group_peripherals in &[true, false] {
+            let mut code = vec![];
+            CodeGenerator::new(&mut code)
+                .group_peripherals(group_peripherals)
+                .generate_device(&device)
+                .unwrap();
+            let text = String::from_utf8_lossy(&code).into_owned();
+            assert!(text.contains("pub fn ch("), "{}", text);
+            assert!(!text.contains("pub struct DR_SR"), "{}", text);
+            if let Err(errors) = compile(&code) {
+                panic!("{}\n{}", errors, text);
+            }
+        }
+    }
+}
@@ -1,13 +1,67 @@
 use errors::*;
+use utils::new_element;
+use xml_fragment::XmlFragment;
 use xmltree;
 
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Cpu {
-
+    /// The content of the `cpu` element, which is not interpreted yet.
+    pub unknown_elements: Vec<XmlFragment>,
 }
 
 impl Cpu {
-    pub fn from_element(_element: &xmltree::Element) -> Result<Cpu> {
-        Ok(Cpu {})
+    pub fn from_element(element: &xmltree::Element) -> Result<Cpu> {
+        Ok(Cpu { unknown_elements: XmlFragment::unknown_children(element, &[]) })
+    }
+
+    pub fn to_element(&self) -> xmltree::Element {
+        let mut element = new_element("cpu");
+        XmlFragment::add_to_element(&mut element, &self.unknown_elements, &[]);
+        element
+    }
+
+    /// The name of the processor, for example `CM4`.
+    pub fn name(&self) -> Option<&str> {
+        self.child_text("name")
+    }
+
+    /// The revision of the processor, for example `r0p1`.
+    pub fn revision(&self) -> Option<&str> {
+        self.child_text("revision")
+    }
+
+    fn child_text(&self, name: &str) -> Option<&str> {
+        self.unknown_elements
+            .iter()
+            .find(|e| e.name() == name)
+            .and_then(|e| e.0.text.as_ref())
+            .map(|text| text.trim())
+            .filter(|text| !text.is_empty())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use xml_fragment::parse_element;
+
+    #[test]
+    fn unknown_elements() {
+        let xml = "<cpu><name>CM4</name><revision>r0p1</revision><acme:core \
+                   xmlns:acme=\"http://acme.com\"/></cpu>";
+        let cpu = Cpu::from_element(&parse_element(xml.as_bytes()).unwrap()).unwrap();
+        assert_eq!(vec!["name", "revision", "acme:core"],
+                   cpu.unknown_elements.iter().map(|e| e.name()).collect::<Vec<_>>());
+        assert_eq!(cpu, Cpu::from_element(&cpu.to_element()).unwrap());
+    }
+
+    #[test]
+    fn name_revision() {
+        let cpu = |xml: &str| Cpu::from_element(&parse_element(xml.as_bytes()).unwrap()).unwrap();
+        let cm4 = cpu("<cpu><name> CM4 </name><revision>r0p1</revision></cpu>");
+        assert_eq!((Some("CM4"), Some("r0p1")), (cm4.name(), cm4.revision()));
+        let empty = cpu("<cpu><name></name><endian>little</endian></cpu>");
+        assert_eq!((None, None), (empty.name(), empty.revision()));
     }
 }
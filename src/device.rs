@@ -1,18 +1,52 @@
+use address_lookup::{self, AddressMatch};
+use cluster::Cluster;
 use cpu::Cpu;
+use diff::{self, Change};
+use enumerated_values::EnumeratedValues;
 use errors::*;
+use field::Field;
+use instances::{Fields, Registers};
 use interrupt::Interrupt;
+use lenient::{self, Warning};
 use peripheral::Peripheral;
-use register_properties_group::RegisterPropertiesGroup;
+use register::Register;
+use register_properties_group::{self, RegisterPropertiesGroup};
+use resolve::{self, ResolvedPath};
 use std::collections::HashMap;
-use std::io::Read;
+use std::io::{Read, Write};
 use types::*;
-use utils::get_child_text;
+use utils::{add_child_text, add_optional_child_text, element_path_segment, get_child_text,
+            new_element};
+use validate::{self, Finding};
+use visit::{self, VisitorMut};
+use writer;
+use xml_fragment::{self, XmlFragment};
 use xmltree;
 
 pub type PeripheralsMap<'a, 'b> = HashMap<&'a str, &'b Peripheral>;
 
+/// The names of the child elements read by `from_element`.
+const ELEMENT_NAMES: &'static [&'static str] = &["vendor", "vendorID", "name", "series", "version",
+                                                 "description", "licenseText", "cpu",
+                                                 "headerSystemFilename", "headerDefinitionsPrefix",
+                                                 "addressUnitBits", "width", "peripherals",
+                                                 "vendorExtensions"];
+
+/// The names of the child elements in the order required by the schema, including the ones not
+/// read by `from_element`.
+const SCHEMA_ORDER: &'static [&'static str] = &["vendor", "vendorID", "name", "series", "version",
+                                                "description", "licenseText", "cpu",
+                                                "headerSystemFilename", "headerDefinitionsPrefix",
+                                                "addressUnitBits", "width", "size", "access",
+                                                "protection", "resetValue", "resetMask",
+                                                "peripherals", "vendorExtensions"];
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Device {
+    /// The `schemaVersion` attribute of the file, written back as is. `1.1` is written when it is
+    /// not set.
+    pub schema_version: Option<String>,
     pub vendor: Option<String>,
     pub vendor_id: Option<IdentifierType>,
     pub name: IdentifierType,
@@ -27,25 +61,65 @@ pub struct Device {
     pub width: ScaledNonNegativeInteger,
     pub register_properties: RegisterPropertiesGroup,
     pub peripherals: Vec<Peripheral>,
+    pub vendor_extensions: Option<XmlFragment>,
+    pub unknown_elements: Vec<XmlFragment>,
 }
 
 impl Device {
+    /// Returns a builder for a device named `name`. The address unit of the device defaults to 8
+    /// bits, its width to 32 bits and its schema version to `1.1`.
+    pub fn builder<S: Into<String>>(name: S) -> DeviceBuilder {
+        DeviceBuilder {
+            device: Device {
+                schema_version: Some("1.1".to_string()),
+                vendor: None,
+                vendor_id: None,
+                name: name.into(),
+                series: None,
+                version: String::new(),
+                description: String::new(),
+                license_text: None,
+                cpu: None,
+                header_system_filename: None,
+                header_definition_prefix: None,
+                address_unit_bits: ScaledNonNegativeInteger(8),
+                width: ScaledNonNegativeInteger(32),
+                register_properties: RegisterPropertiesGroup::default(),
+                peripherals: vec![],
+                vendor_extensions: None,
+                unknown_elements: vec![],
+            },
+        }
+    }
+
     pub fn from_reader<R: Read>(r: R) -> Result<Device> {
-        let element = try!(xmltree::Element::parse(r));
-        let mut d = try!(Device::from_element(&element));
+        let element = try!(xml_fragment::parse_element(r));
+        let mut d = try!(Device::from_element(&element).in_element("device"));
         d.propagate_derived_from();
         d.propagate_register_properties();
         Ok(d)
     }
 
+    /// Like `from_reader`, but applies the fallbacks documented in the `lenient` module instead of
+    /// failing on recoverable problems, and returns the warnings for the applied fallbacks.
+    pub fn from_reader_lenient<R: Read>(r: R) -> Result<(Device, Vec<Warning>)> {
+        let mut element = try!(xml_fragment::parse_element(r));
+        let warnings = lenient::sanitize(&mut element);
+        let mut d = try!(Device::from_element(&element).in_element("device"));
+        d.propagate_derived_from();
+        d.propagate_register_properties();
+        Ok((d, warnings))
+    }
+
     pub fn parse<R: Read>(r: R) -> Result<Device> {
-        let element = try!(xmltree::Element::parse(r));
-        Device::from_element(&element).map_err(|e| e.into())
+        let element = try!(xml_fragment::parse_element(r));
+        Device::from_element(&element).in_element("device")
     }
 
     pub fn from_element(element: &xmltree::Element) -> Result<Device> {
+        let schema_version = element.attributes.get("schemaVersion").cloned();
         let vendor = get_child_text(element, "vendor");
-        let vendor_id = get_child_text(element, "vendor_id");
+        let vendor_id = get_child_text(element, "vendorID");
         let name = get_mandatory_child_text!(element, "device", "name");
         let series = get_child_text(element, "series");
         let version = get_mandatory_child_text!(element, "device", "version");
@@ -65,13 +139,24 @@ impl Device {
             None => return Err(ErrorKind::MissingField("device", "peripherals").into()),
         };
 
+        let vendor_extensions = XmlFragment::from_child(element, "vendorExtensions");
+        let known_names = [ELEMENT_NAMES, register_properties_group::ELEMENT_NAMES];
+        let unknown_elements = XmlFragment::unknown_children(element, &known_names);
+
         let address_unit_bits = try!(address_unit_bits.parse());
         let width = try!(width.parse());
-        let peripherals: Result<Vec<_>> =
-            peripherals.children.iter().map(Peripheral::from_element).collect();
+        let peripherals: Result<Vec<_>> = peripherals.children
+            .iter()
+            .enumerate()
+            .map(|(i, e)| {
+                Peripheral::from_element(e)
+                    .in_element(format!("peripherals/{}", element_path_segment(e, i)))
+            })
+            .collect();
         let peripherals = try!(peripherals);
 
         Ok(Device {
+            schema_version: schema_version,
             vendor: vendor,
             vendor_id: vendor_id,
             name: name,
@@ -86,10 +171,58 @@ impl Device {
             width: width,
             register_properties: register_properties,
             peripherals: peripherals,
+            vendor_extensions: vendor_extensions,
+            unknown_elements: unknown_elements,
         })
 
     }
 
+    /// Writes the device as an SVD file.
+    pub fn to_writer<W: Write>(&self, mut w: W) -> Result<()> {
+        try!(writer::write_document(&mut w, &self.to_element()));
+        Ok(())
+    }
+
+    pub fn to_element(&self) -> xmltree::Element {
+        let mut element = new_element("device");
+        let schema_version = self.schema_version.clone().unwrap_or_else(|| "1.1".to_string());
+        element.attributes.insert("schemaVersion".to_string(), schema_version);
+        element.attributes
+            .insert("xmlns:xs".to_string(),
+                    "http://www.w3.org/2001/XMLSchema-instance".to_string());
+        element.attributes.insert("xs:noNamespaceSchemaLocation".to_string(),
+                                  "CMSIS-SVD.xsd".to_string());
+        add_optional_child_text(&mut element, "vendor", &self.vendor);
+        add_optional_child_text(&mut element, "vendorID", &self.vendor_id);
+        add_child_text(&mut element, "name", &self.name);
+        add_optional_child_text(&mut element, "series", &self.series);
+        add_child_text(&mut element, "version", &self.version);
+        add_child_text(&mut element, "description", &self.description);
+        add_optional_child_text(&mut element, "licenseText", &self.license_text);
+        if let Some(ref cpu) = self.cpu {
+            element.children.push(cpu.to_element());
+        }
+        add_optional_child_text(&mut element,
+                                "headerSystemFilename",
+                                &self.header_system_filename);
+        add_optional_child_text(&mut element,
+                                "headerDefinitionsPrefix",
+                                &self.header_definition_prefix);
+        add_child_text(&mut element, "addressUnitBits", self.address_unit_bits);
+        add_child_text(&mut element, "width", self.width);
+        self.register_properties.add_to_element(&mut element);
+        let mut peripherals_element = new_element("peripherals");
+        for peripheral in &self.peripherals {
+            peripherals_element.children.push(peripheral.to_element());
+        }
+        element.children.push(peripherals_element);
+        XmlFragment::add_to_element(&mut element, &self.unknown_elements, SCHEMA_ORDER);
+        if let Some(ref vendor_extensions) = self.vendor_extensions {
+            element.children.push(vendor_extensions.0.clone());
+        }
+        element
+    }
+
     pub fn peripherals_map(&self) -> PeripheralsMap {
         self.peripherals.iter().map(|p| (&*p.name, p)).collect()
     }
@@ -102,6 +235,10 @@ impl Device {
 
     pub fn propagate_derived_from(&mut self) {
         for i in 0..self.peripherals.len() {
+            if self.peripherals[i].original_derived_from.is_none() {
+                let derived_from = self.peripherals[i].derived_from.clone();
+                self.peripherals[i].original_derived_from = derived_from;
+            }
             while self.peripherals[i].derived_from.is_some() {
                 let mut peripheral_derived_from = None;
                 if let Some(ref derived_from) = self.peripherals[i].derived_from {
@@ -122,8 +259,495 @@ impl Device {
         }
     }
 
+    /// Returns the register at `address`, taking clusters and arrays into account.
+    pub fn lookup_address(&self, address: u64) -> Option<AddressMatch> {
+        address_lookup::lookup_address(self, address)
+    }
+
+    /// Returns the elements named by a dotted path such as `TIM2.CCMR1.OC1M` or `DMA1.CH[3].CCR`.
+    /// See `resolve::resolve` for the syntax of the path.
+    pub fn resolve(&self, path: &str) -> Option<ResolvedPath> {
+        resolve::resolve(self, path)
+    }
+
+    /// Returns the peripheral named by a path such as `TIM2`.
+    pub fn resolve_peripheral(&self, path: &str) -> Option<&Peripheral> {
+        match self.resolve(path) {
+            Some(ref r) if r.clusters.is_empty() && r.register.is_none() => Some(r.peripheral),
+            _ => None,
+        }
+    }
+
+    /// Returns the register named by a path such as `TIM2.CCMR1`.
+    pub fn resolve_register(&self, path: &str) -> Option<&Register> {
+        match self.resolve(path) {
+            Some(ResolvedPath { register: Some((register, _)), field: None, .. }) => Some(register),
+            _ => None,
+        }
+    }
+
+    /// Returns the field named by a path such as `TIM2.CCMR1.OC1M`.
+    pub fn resolve_field(&self, path: &str) -> Option<&Field> {
+        self.resolve(path).and_then(|r| r.field)
+    }
+
+    /// Returns every register of the device as it exists in its address space: each element of
+    /// the register, cluster and peripheral arrays is a distinct register instance. This should be
+    /// called on a device whose `derivedFrom` have been propagated, as done by `from_reader`.
+    pub fn registers(&self) -> Registers {
+        Registers::new(self)
+    }
+
+    /// Returns the fields of every register instance returned by `registers`.
+    pub fn fields(&self) -> Fields {
+        Fields::new(self)
+    }
+
+    /// Returns the differences between this device and `new`, as documented in the `diff` module.
+    pub fn diff(&self, new: &Device) -> Vec<Change> {
+        diff::diff(self, new)
+    }
+
+    /// Returns the problems found by the rules documented in the `validate` module.
+    pub fn validate(&self) -> Vec<Finding> {
+        validate::validate(self)
+    }
+
     /// Returns the interrupts of all the peripherals of the device.
     pub fn interrupts(&self) -> Vec<&Interrupt> {
         self.peripherals.iter().flat_map(|p| &p.interrupts).collect()
     }
+
+    /// Trims the descriptions of the device and of its elements, and replaces the whitespace
+    /// sequences inside them, line breaks included, by a single space.
+    pub fn normalize_descriptions(&mut self) {
+        DescriptionsNormalizer.visit_device_mut(self);
+    }
+}
+
+struct DescriptionsNormalizer;
+
+impl DescriptionsNormalizer {
+    fn normalize(description: &mut String) {
+        *description = description.split_whitespace().collect::<Vec<_>>().join(" ");
+    }
+
+    fn normalize_option(description: &mut Option<String>) {
+        if let Some(ref mut description) = *description {
+            DescriptionsNormalizer::normalize(description);
+        }
+    }
+}
+
+impl VisitorMut for DescriptionsNormalizer {
+    fn visit_device_mut(&mut self, device: &mut Device) {
+        DescriptionsNormalizer::normalize(&mut device.description);
+        visit::walk_device_mut(self, device);
+    }
+
+    fn visit_peripheral_mut(&mut self, peripheral: &mut Peripheral) {
+        DescriptionsNormalizer::normalize_option(&mut peripheral.description);
+        for interrupt in &mut peripheral.interrupts {
+            DescriptionsNormalizer::normalize_option(&mut interrupt.description);
+        }
+        visit::walk_peripheral_mut(self, peripheral);
+    }
+
+    fn visit_cluster_mut(&mut self, cluster: &mut Cluster) {
+        DescriptionsNormalizer::normalize_option(&mut cluster.description);
+        visit::walk_cluster_mut(self, cluster);
+    }
+
+    fn visit_register_mut(&mut self, register: &mut Register) {
+        DescriptionsNormalizer::normalize_option(&mut register.description);
+        visit::walk_register_mut(self, register);
+    }
+
+    fn visit_field_mut(&mut self, field: &mut Field) {
+        DescriptionsNormalizer::normalize_option(&mut field.description);
+        visit::walk_field_mut(self, field);
+    }
+
+    fn visit_enumerated_values_mut(&mut self, enumerated_values: &mut EnumeratedValues) {
+        for enumerated_value in enumerated_values.enumerated_values_mut() {
+            DescriptionsNormalizer::normalize_option(&mut enumerated_value.description);
+        }
+    }
+}
+
+/// A builder for a `Device`, returned by `Device::builder`.
+#[derive(Clone, Debug)]
+pub struct DeviceBuilder {
+    device: Device,
+}
+
+impl DeviceBuilder {
+    pub fn vendor<S: Into<String>>(mut self, vendor: S) -> DeviceBuilder {
+        self.device.vendor = Some(vendor.into());
+        self
+    }
+
+    pub fn vendor_id<S: Into<String>>(mut self, vendor_id: S) -> DeviceBuilder {
+        self.device.vendor_id = Some(vendor_id.into());
+        self
+    }
+
+    pub fn series<S: Into<String>>(mut self, series: S) -> DeviceBuilder {
+        self.device.series = Some(series.into());
+        self
+    }
+
+    pub fn version<S: Into<String>>(mut self, version: S) -> DeviceBuilder {
+        self.device.version = version.into();
+        self
+    }
+
+    pub fn description<S: Into<String>>(mut self, description: S) -> DeviceBuilder {
+        self.device.description = description.into();
+        self
+    }
+
+    pub fn license_text<S: Into<String>>(mut self, license_text: S) -> DeviceBuilder {
+        self.device.license_text = Some(license_text.into());
+        self
+    }
+
+    pub fn cpu(mut self, cpu: Cpu) -> DeviceBuilder {
+        self.device.cpu = Some(cpu);
+        self
+    }
+
+    pub fn header_system_filename<S: Into<String>>(mut self,
+                                                   header_system_filename: S)
+                                                   -> DeviceBuilder {
+        self.device.header_system_filename = Some(header_system_filename.into());
+        self
+    }
+
+    pub fn header_definition_prefix<S: Into<String>>(mut self,
+                                                     header_definition_prefix: S)
+                                                     -> DeviceBuilder {
+        self.device.header_definition_prefix = Some(header_definition_prefix.into());
+        self
+    }
+
+    pub fn address_unit_bits(mut self, address_unit_bits: u64) -> DeviceBuilder {
+        self.device.address_unit_bits = ScaledNonNegativeInteger(address_unit_bits);
+        self
+    }
+
+    pub fn width(mut self, width: u64) -> DeviceBuilder {
+        self.device.width = ScaledNonNegativeInteger(width);
+        self
+    }
+
+    register_properties_setters!(DeviceBuilder, device);
+
+    pub fn peripheral(mut self, peripheral: Peripheral) -> DeviceBuilder {
+        self.device.peripherals.push(peripheral);
+        self
+    }
+
+    /// Returns the device, or an error if its name, version or description is empty, if its
+    /// address unit is 0 bits, or if a peripheral is derived from a peripheral that doesn't exist.
+    pub fn build(self) -> Result<Device> {
+        if self.device.name.is_empty() {
+            return Err(ErrorKind::MissingField("device", "name").into());
+        }
+        if self.device.version.is_empty() {
+            return Err(ErrorKind::MissingField("device", "version").into());
+        }
+        if self.device.description.is_empty() {
+            return Err(ErrorKind::MissingField("device", "description").into());
+        }
+        if self.device.address_unit_bits.0 == 0 {
+            return Err(ErrorKind::UnexpectedValue("a non zero addressUnitBits", "0".to_string())
+                .into());
+        }
+        for peripheral in &self.device.peripherals {
+            if let Some(ref derived_from) = peripheral.derived_from {
+                if !self.device.peripherals.iter().any(|p| p.name == *derived_from) {
+                    return Err(ErrorKind::UnexpectedValue("the name of a peripheral",
+                                                          derived_from.clone())
+                        .into());
+                }
+            }
+        }
+        Ok(self.device)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &'static str = r#"
+        <device schemaVersion="1.1">
+            <vendor>ACME</vendor>
+            <vendorID>ACME</vendorID>
+            <name>TEST</name>
+            <version>1.0</version>
+            <description>Test device &amp; friends</description>
+            <cpu>
+                <name>CM4</name>
+                <revision>r0p1</revision>
+                <endian>little</endian>
+                <mpuPresent>true</mpuPresent>
+                <fpuPresent>true</fpuPresent>
+                <nvicPrioBits>4</nvicPrioBits>
+                <vendorSystickConfig>false</vendorSystickConfig>
+            </cpu>
+            <addressUnitBits>8</addressUnitBits>
+            <width>32</width>
+            <size>32</size>
+            <access>read-write</access>
+            <resetValue>0x00000000</resetValue>
+            <resetMask>0xFFFFFFFF</resetMask>
+            <peripherals>
+                <peripheral>
+                    <name>DMA1</name>
+                    <description>DMA controller</description>
+                    <groupName>DMA</groupName>
+                    <baseAddress>0x40020000</baseAddress>
+                    <addressBlock>
+                        <offset>0x0</offset>
+                        <size>0x400</size>
+                        <usage>registers</usage>
+                    </addressBlock>
+                    <interrupt>
+                        <name>DMA1_CH1</name>
+                        <value>11</value>
+                    </interrupt>
+                    <registers>
+                        <register>
+                            <name>ISR</name>
+                            <addressOffset>0x0</addressOffset>
+                            <access>read-only</access>
+                            <fields>
+                                <field>
+                                    <name>GIF1</name>
+                                    <bitOffset>0</bitOffset>
+                                    <bitWidth>1</bitWidth>
+                                </field>
+                                <field>
+                                    <name>TCIF1</name>
+                                    <bitRange>[1:1]</bitRange>
+                                    <readAction>clear</readAction>
+                                </field>
+                            </fields>
+                        </register>
+                        <cluster>
+                            <dim>7</dim>
+                            <dimIncrement>0x14</dimIncrement>
+                            <dimIndex>1-7</dimIndex>
+                            <name>CH%s</name>
+                            <description>
+                                DMA   channel
+                            </description>
+                            <addressOffset>0x8</addressOffset>
+                            <register>
+                                <name>CCR</name>
+                                <addressOffset>0x0</addressOffset>
+                                <fields>
+                                    <field>
+                                        <name>PL</name>
+                                        <lsb>12</lsb>
+                                        <msb>13</msb>
+                                        <writeConstraint>
+                                            <range>
+                                                <minimum>0</minimum>
+                                                <maximum>3</maximum>
+                                            </range>
+                                        </writeConstraint>
+                                        <enumeratedValues>
+                                            <name>PL</name>
+                                            <usage>read-write</usage>
+                                            <enumeratedValue>
+                                                <name>Low</name>
+                                                <description>Low &lt; Medium</description>
+                                                <value>0</value>
+                                            </enumeratedValue>
+                                            <enumeratedValue>
+                                                <name>High</name>
+                                                <value>#1x</value>
+                                            </enumeratedValue>
+                                            <enumeratedValue>
+                                                <name>Other</name>
+                                                <isDefault>true</isDefault>
+                                            </enumeratedValue>
+                                        </enumeratedValues>
+                                    </field>
+                                </fields>
+                            </register>
+                        </cluster>
+                    </registers>
+                </peripheral>
+                <peripheral derivedFrom="DMA1">
+                    <name>DMA2</name>
+                    <baseAddress>0x40020400</baseAddress>
+                    <interrupt>
+                        <name>DMA2_CH1</name>
+                        <value>-0x2</value>
+                    </interrupt>
+                    <vendorExtensions>
+                        <acme:secure xmlns:acme="http://acme.com">true <b>bold</b></acme:secure>
+                    </vendorExtensions>
+                </peripheral>
+            </peripherals>
+        </device>"#;
+
+    const MISSING_BIT_RANGE: &'static str = r#"
+        <device>
+            <name>TEST</name>
+            <version>1.0</version>
+            <description>Test device</description>
+            <addressUnitBits>8</addressUnitBits>
+            <width>32</width>
+            <peripherals>
+                <peripheral>
+                    <name>TIM2</name>
+                    <baseAddress>0x40000000</baseAddress>
+                    <registers>
+                        <register>
+                            <name>CR1</name>
+                            <addressOffset>0x0</addressOffset>
+                            <fields>
+                                <field>
+                                    <name>CEN</name>
+                                    <bitOffset>0</bitOffset>
+                                    <bitWidth>1</bitWidth>
+                                </field>
+                                <field><name>UDIS</name></field>
+                            </fields>
+                        </register>
+                    </registers>
+                </peripheral>
+            </peripherals>
+        </device>"#;
+
+    #[test]
+    fn write_round_trip() {
+        let d = Device::parse(EXAMPLE.as_bytes()).unwrap();
+        let mut written = vec![];
+        d.to_writer(&mut written).unwrap();
+        assert_eq!(d, Device::parse(&*written).unwrap());
+
+        let d = Device::from_reader(EXAMPLE.as_bytes()).unwrap();
+        let mut written = vec![];
+        d.to_writer(&mut written).unwrap();
+        assert_eq!(d, Device::from_reader(&*written).unwrap());
+    }
+
+    #[test]
+    fn schema_version() {
+        let d = Device::parse(EXAMPLE.replace("schemaVersion=\"1.1\"", "schemaVersion=\"1.3\"")
+                                  .as_bytes())
+            .unwrap();
+        assert_eq!(Some("1.3"), d.schema_version.as_ref().map(|v| &**v));
+        assert_eq!(Some(&"1.3".to_string()), d.to_element().attributes.get("schemaVersion"));
+
+        let d = Device::builder("TEST").version("1.0").description("Test").build().unwrap();
+        assert_eq!(Some(&"1.1".to_string()), d.to_element().attributes.get("schemaVersion"));
+    }
+
+    #[test]
+    fn builder() {
+        use address_block::Usage;
+
+        let moder = Register::builder("MODER", 0x0)
+            .field(Field::builder("MODER0", 0..=1).build().unwrap())
+            .build()
+            .unwrap();
+        let gpioa = Peripheral::builder("GPIOA", 0x4002_0000)
+            .address_block(0x0, 0x400, Usage::Registers)
+            .register(moder)
+            .build()
+            .unwrap();
+        let gpiob = Peripheral::builder("GPIOB", 0x4002_0400)
+            .derived_from("GPIOA")
+            .build()
+            .unwrap();
+        let d = Device::builder("STM32X")
+            .vendor("ACME")
+            .version("1.0")
+            .description("An example device")
+            .address_unit_bits(8)
+            .width(32)
+            .peripheral(gpioa)
+            .peripheral(gpiob.clone())
+            .build()
+            .unwrap();
+
+        let mut written = vec![];
+        d.to_writer(&mut written).unwrap();
+        assert_eq!(d, Device::parse(&*written).unwrap());
+        let d = Device::from_reader(&*written).unwrap();
+        assert!(d.resolve_field("GPIOB.MODER.MODER0").is_some());
+
+        let device = || Device::builder("STM32X").version("1.0").description("An example device");
+        assert!(Device::builder("STM32X").description("An example device").build().is_err());
+        assert!(Device::builder("STM32X").version("1.0").build().is_err());
+        assert!(Device::builder("")
+            .version("1.0")
+            .description("An example device")
+            .build()
+            .is_err());
+        assert!(device().address_unit_bits(0).build().is_err());
+        // GPIOB is derived from a peripheral that is not in the device
+        assert!(device().peripheral(gpiob).build().is_err());
+    }
+
+    #[test]
+    fn normalize_descriptions() {
+        use register_or_cluster::RegisterOrCluster;
+
+        let mut d = Device::parse(EXAMPLE.as_bytes()).unwrap();
+        d.description = "  Test device\n      & friends ".to_string();
+        d.peripherals[0].interrupts[0].description = Some("\tChannel 1 ".to_string());
+        d.normalize_descriptions();
+        assert_eq!("Test device & friends", d.description);
+        assert_eq!(Some("Channel 1".to_string()),
+                   d.peripherals[0].interrupts[0].description);
+        assert_eq!(Some("Low < Medium".to_string()),
+                   d.resolve_field("DMA1.CH1.CCR.PL")
+                       .and_then(|f| f.enumerated_values[0].find(0))
+                       .and_then(|v| v.description.clone()));
+        match d.peripherals[0].registers.as_ref().unwrap()[1] {
+            RegisterOrCluster::Cluster(ref c) => {
+                assert_eq!(Some("DMA channel".to_string()), c.description);
+                let names: Vec<_> = c.to_element().children.into_iter().map(|c| c.name).collect();
+                assert_eq!(&["dim", "dimIncrement", "dimIndex", "name", "description",
+                             "addressOffset"][..],
+                           &names[..6]);
+            }
+            ref r => panic!("expected a cluster, got {:?}", r),
+        }
+        assert_eq!(Some(&"1.1".to_string()), d.to_element().attributes.get("schemaVersion"));
+
+        let mut written = vec![];
+        d.to_writer(&mut written).unwrap();
+        let mut rewritten = vec![];
+        Device::parse(&*written).unwrap().to_writer(&mut rewritten).unwrap();
+        assert_eq!(written, rewritten);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip() {
+        use serde_json;
+
+        let d = Device::from_reader(EXAMPLE.as_bytes()).unwrap();
+        let json = serde_json::to_string(&d).unwrap();
+        assert!(json.contains(r#""base_address":1073872896"#));
+        assert!(json.contains(r#""access":"read-write""#));
+        assert_eq!(d, serde_json::from_str(&json).unwrap());
+    }
+
+    #[test]
+    fn error_element_path() {
+        let e = Device::from_reader(MISSING_BIT_RANGE.as_bytes()).unwrap_err();
+        assert_eq!(Some("device/peripherals/peripheral[TIM2]/registers/register[CR1]/fields/\
+                         field[UDIS]"),
+                   e.element_path());
+    }
 }
@@ -0,0 +1,570 @@
+//! Comparison of two versions of a device.
+//!
+//! The peripherals, registers, clusters, fields and enumerated values are matched by name. The
+//! elements left without a match are then matched by address (by bit range for the fields and by
+//! value for the enumerated values), and reported as renamed.
+
+use access::Access;
+use bit_range::BitRange;
+use device::Device;
+use enumerated_values::{EnumeratedValue, EnumeratedValueData};
+use field::Field;
+use peripheral::Peripheral;
+use register::Register;
+use register_or_cluster::RegisterOrCluster;
+use std::fmt;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    /// The element was matched by its address and has a new name.
+    Renamed { old: String },
+    /// The base address of a peripheral or the address offset of a register or cluster changed.
+    AddressChanged { old: u64, new: u64 },
+    SizeChanged { old: u64, new: u64 },
+    AccessChanged {
+        old: Option<Access>,
+        new: Option<Access>,
+    },
+    ResetValueChanged { old: Option<u64>, new: Option<u64> },
+    /// The field has more bits.
+    FieldWidened { old: BitRange, new: BitRange },
+    /// The field has fewer bits.
+    FieldNarrowed { old: BitRange, new: BitRange },
+    /// The field has the same width at another position.
+    FieldMoved { old: BitRange, new: BitRange },
+    /// The value of an enumerated value changed.
+    ValueChanged {
+        old: EnumeratedValueData,
+        new: EnumeratedValueData,
+    },
+}
+
+/// A difference between two devices, as returned by `Device::diff`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Change {
+    /// The dotted path of the element, with its new name unless it was removed.
+    pub path: String,
+    pub kind: ChangeKind,
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fn access_str(access: &Option<Access>) -> &'static str {
+            access.map_or("unspecified", |a| a.as_str())
+        }
+
+        fn reset_value_str(reset_value: &Option<u64>) -> String {
+            reset_value.map_or("unspecified".to_string(), |v| format!("0x{:X}", v))
+        }
+
+        fn value_str(value: &EnumeratedValueData) -> String {
+            match *value {
+                EnumeratedValueData::IsDefault(is_default) => format!("isDefault {}", is_default),
+                EnumeratedValueData::Value { value, do_not_care } => {
+                    EnumeratedValueData::value_str(value, do_not_care)
+                }
+            }
+        }
+
+        try!(write!(f, "{}: ", self.path));
+        match self.kind {
+            ChangeKind::Added => write!(f, "added"),
+            ChangeKind::Removed => write!(f, "removed"),
+            ChangeKind::Renamed { ref old } => write!(f, "renamed from {}", old),
+            ChangeKind::AddressChanged { old, new } => {
+                write!(f, "address moved from 0x{:X} to 0x{:X}", old, new)
+            }
+            ChangeKind::SizeChanged { old, new } => {
+                write!(f, "size changed from {} to {} bits", old, new)
+            }
+            ChangeKind::AccessChanged { ref old, ref new } => {
+                write!(f,
+                       "access changed from {} to {}",
+                       access_str(old),
+                       access_str(new))
+            }
+            ChangeKind::ResetValueChanged { ref old, ref new } => {
+                write!(f,
+                       "reset value changed from {} to {}",
+                       reset_value_str(old),
+                       reset_value_str(new))
+            }
+            ChangeKind::FieldWidened { old, new } |
+            ChangeKind::FieldNarrowed { old, new } |
+            ChangeKind::FieldMoved { old, new } => {
+                let what = match self.kind {
+                    ChangeKind::FieldWidened { .. } => "widened",
+                    ChangeKind::FieldNarrowed { .. } => "narrowed",
+                    _ => "moved",
+                };
+                write!(f,
+                       "field {} from [{}:{}] to [{}:{}]",
+                       what,
+                       old.msb,
+                       old.lsb,
+                       new.msb,
+                       new.lsb)
+            }
+            ChangeKind::ValueChanged { ref old, ref new } => {
+                write!(f,
+                       "value changed from {} to {}",
+                       value_str(old),
+                       value_str(new))
+            }
+        }
+    }
+}
+
+/// Returns the differences between `old` and `new`. Use it on devices returned by
+/// `Device::from_reader` to take derived elements and inherited register properties into account.
+pub fn diff(old: &Device, new: &Device) -> Vec<Change> {
+    let mut changes = vec![];
+    let pairs = match_elements(&old.peripherals,
+                               &new.peripherals,
+                               |p| &*p.name,
+                               |p| p.base_address.0);
+    for pair in pairs {
+        match pair {
+            (Some(old), Some(new)) => diff_peripherals(old, new, &mut changes),
+            (Some(old), None) => changes.push(change(&old.name, ChangeKind::Removed)),
+            (None, Some(new)) => changes.push(change(&new.name, ChangeKind::Added)),
+            (None, None) => {}
+        }
+    }
+    changes
+}
+
+fn change<S: Into<String>>(path: S, kind: ChangeKind) -> Change {
+    Change {
+        path: path.into(),
+        kind: kind,
+    }
+}
+
+/// Pairs the elements of `old` and `new` with the same name, then the remaining elements with
+/// the same key. The elements without a match are paired with `None`.
+fn match_elements<'a, T, N, K, V>(old: &'a [T],
+                                  new: &'a [T],
+                                  name: N,
+                                  key: K)
+                                  -> Vec<(Option<&'a T>, Option<&'a T>)>
+    where N: Fn(&T) -> &str,
+          K: Fn(&T) -> V,
+          V: PartialEq
+{
+    let mut new_matched = vec![false; new.len()];
+    let mut old_matches: Vec<Option<usize>> = old.iter()
+        .map(|o| {
+            let found = new.iter()
+                .enumerate()
+                .position(|(i, n)| !new_matched[i] && name(n) == name(o));
+            if let Some(i) = found {
+                new_matched[i] = true;
+            }
+            found
+        })
+        .collect();
+
+    for (o, old_match) in old.iter().zip(&mut old_matches) {
+        if old_match.is_none() {
+            *old_match = new.iter()
+                .enumerate()
+                .position(|(i, n)| !new_matched[i] && key(n) == key(o));
+            if let Some(i) = *old_match {
+                new_matched[i] = true;
+            }
+        }
+    }
+
+    let mut pairs: Vec<_> = old.iter()
+        .zip(old_matches)
+        .map(|(o, i)| (Some(o), i.map(|i| &new[i])))
+        .collect();
+    pairs.extend(new.iter().zip(new_matched).filter(|&(_, matched)| !matched).map(|(n, _)| {
+        (None, Some(n))
+    }));
+    pairs
+}
+
+fn diff_names(path: &str, old: &str, new: &str, changes: &mut Vec<Change>) {
+    if old != new {
+        changes.push(change(path,
+                            ChangeKind::Renamed { old: old.to_string() }));
+    }
+}
+
+fn diff_peripherals(old: &Peripheral, new: &Peripheral, changes: &mut Vec<Change>) {
+    let path = &*new.name;
+    diff_names(path, &old.name, &new.name, changes);
+    if old.base_address != new.base_address {
+        changes.push(change(path,
+                            ChangeKind::AddressChanged {
+                                old: old.base_address.0,
+                                new: new.base_address.0,
+                            }));
+    }
+
+    let empty = vec![];
+    diff_registers(path,
+                   old.registers.as_ref().unwrap_or(&empty),
+                   new.registers.as_ref().unwrap_or(&empty),
+                   changes);
+}
+
+fn diff_registers(prefix: &str,
+                  old: &[RegisterOrCluster],
+                  new: &[RegisterOrCluster],
+                  changes: &mut Vec<Change>) {
+    let address_offset = |r_or_c: &RegisterOrCluster| match *r_or_c {
+        RegisterOrCluster::Register(ref r) => r.address_offset.0,
+        RegisterOrCluster::Cluster(ref c) => c.address_offset.0,
+    };
+
+    for pair in match_elements(old, new, |r| r.name(), address_offset) {
+        match pair {
+            (Some(&RegisterOrCluster::Register(ref old)),
+             Some(&RegisterOrCluster::Register(ref new))) => {
+                let path = format!("{}.{}", prefix, new.name);
+                diff_names(&path, &old.name, &new.name, changes);
+                diff_register(&path, old, new, changes);
+            }
+            (Some(&RegisterOrCluster::Cluster(ref old)),
+             Some(&RegisterOrCluster::Cluster(ref new))) => {
+                let path = format!("{}.{}", prefix, new.name);
+                diff_names(&path, &old.name, &new.name, changes);
+                if old.address_offset != new.address_offset {
+                    changes.push(change(&*path,
+                                        ChangeKind::AddressChanged {
+                                            old: old.address_offset.0,
+                                            new: new.address_offset.0,
+                                        }));
+                }
+                diff_registers(&path, &old.registers, &new.registers, changes);
+            }
+            (old, new) => {
+                if let Some(old) = old {
+                    changes.push(change(format!("{}.{}", prefix, old.name()),
+                                        ChangeKind::Removed));
+                }
+                if let Some(new) = new {
+                    changes.push(change(format!("{}.{}", prefix, new.name()), ChangeKind::Added));
+                }
+            }
+        }
+    }
+}
+
+fn diff_register(path: &str, old: &Register, new: &Register, changes: &mut Vec<Change>) {
+    if old.address_offset != new.address_offset {
+        changes.push(change(path,
+                            ChangeKind::AddressChanged {
+                                old: old.address_offset.0,
+                                new: new.address_offset.0,
+                            }));
+    }
+    if old.size() != new.size() {
+        changes.push(change(path,
+                            ChangeKind::SizeChanged {
+                                old: old.size(),
+                                new: new.size(),
+                            }));
+    }
+    if old.register_properties.access != new.register_properties.access {
+        changes.push(change(path,
+                            ChangeKind::AccessChanged {
+                                old: old.register_properties.access,
+                                new: new.register_properties.access,
+                            }));
+    }
+    let old_reset_value = old.register_properties.reset_value.map(|v| v.0);
+    let new_reset_value = new.register_properties.reset_value.map(|v| v.0);
+    if old_reset_value != new_reset_value {
+        changes.push(change(path,
+                            ChangeKind::ResetValueChanged {
+                                old: old_reset_value,
+                                new: new_reset_value,
+                            }));
+    }
+
+    let empty = vec![];
+    let old_fields = old.fields.as_ref().unwrap_or(&empty);
+    let new_fields = new.fields.as_ref().unwrap_or(&empty);
+    for pair in match_elements(old_fields, new_fields, |f| &*f.name, |f| f.bit_range) {
+        match pair {
+            (Some(old), Some(new)) => {
+                let path = format!("{}.{}", path, new.name);
+                diff_names(&path, &old.name, &new.name, changes);
+                diff_field(&path, old, new, changes);
+            }
+            (Some(old), None) => {
+                changes.push(change(format!("{}.{}", path, old.name), ChangeKind::Removed))
+            }
+            (None, Some(new)) => {
+                changes.push(change(format!("{}.{}", path, new.name), ChangeKind::Added))
+            }
+            (None, None) => {}
+        }
+    }
+}
+
+fn diff_field(path: &str, old: &Field, new: &Field, changes: &mut Vec<Change>) {
+    let (old_range, new_range) = (old.bit_range, new.bit_range);
+    let kind = if new_range.width() > old_range.width() {
+        Some(ChangeKind::FieldWidened {
+            old: old_range,
+            new: new_range,
+        })
+    } else if new_range.width() < old_range.width() {
+        Some(ChangeKind::FieldNarrowed {
+            old: old_range,
+            new: new_range,
+        })
+    } else if new_range != old_range {
+        Some(ChangeKind::FieldMoved {
+            old: old_range,
+            new: new_range,
+        })
+    } else {
+        None
+    };
+    if let Some(kind) = kind {
+        changes.push(change(path, kind));
+    }
+
+    if old.access != new.access {
+        changes.push(change(path,
+                            ChangeKind::AccessChanged {
+                                old: old.access,
+                                new: new.access,
+                            }));
+    }
+
+    let old_values: Vec<EnumeratedValue> = old.enumerated_values
+        .iter()
+        .flat_map(|e| e.enumerated_values().iter().cloned())
+        .collect();
+    let new_values: Vec<EnumeratedValue> = new.enumerated_values
+        .iter()
+        .flat_map(|e| e.enumerated_values().iter().cloned())
+        .collect();
+    for pair in match_elements(&old_values, &new_values, |v| &*v.name, |v| v.value.clone()) {
+        match pair {
+            (Some(old), Some(new)) => {
+                let path = format!("{}.{}", path, new.name);
+                diff_names(&path, &old.name, &new.name, changes);
+                if old.value != new.value {
+                    changes.push(change(path,
+                                        ChangeKind::ValueChanged {
+                                            old: old.value.clone(),
+                                            new: new.value.clone(),
+                                        }));
+                }
+            }
+            (Some(old), None) => {
+                changes.push(change(format!("{}.{}", path, old.name), ChangeKind::Removed))
+            }
+            (None, Some(new)) => {
+                changes.push(change(format!("{}.{}", path, new.name), ChangeKind::Added))
+            }
+            (None, None) => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use enumerated_values::EnumeratedValues;
+    use super::*;
+    use xml_fragment::parse_element;
+
+    fn device(peripherals: Vec<Peripheral>) -> Device {
+        peripherals.into_iter()
+            .fold(Device::builder("STM32X").version("1.0").description("An example device"),
+                  |d, p| d.peripheral(p))
+            .build()
+            .unwrap()
+    }
+
+    fn peripheral(name: &str, base_address: u64, registers: Vec<Register>) -> Peripheral {
+        registers.into_iter()
+            .fold(Peripheral::builder(name, base_address), |p, r| p.register(r))
+            .build()
+            .unwrap()
+    }
+
+    fn register(name: &str, offset: u64, fields: Vec<Field>) -> Register {
+        fields.into_iter()
+            .fold(Register::builder(name, offset), |r, f| r.field(f))
+            .build()
+            .unwrap()
+    }
+
+    fn changes(old: &[Peripheral], new: &[Peripheral]) -> Vec<String> {
+        diff(&device(old.to_vec()), &device(new.to_vec())).iter().map(|c| c.to_string()).collect()
+    }
+
+    #[test]
+    fn match_by_name_then_key() {
+        let old = [("A", 0), ("B", 1), ("C", 2), ("D", 3), ("D", 4)];
+        let new = [("X", 9), ("C", 5), ("E", 1), ("D", 4), ("F", 1)];
+        let pairs: Vec<_> = match_elements(&old, &new, |e| e.0, |e| e.1)
+            .into_iter()
+            .map(|(o, n)| (o.map(|o| o.0), n.map(|n| n.0)))
+            .collect();
+        // C and the first D are matched by name, B and the second D by key, and a new element
+        // matches a single old element
+        assert_eq!(vec![(Some("A"), None),
+                        (Some("B"), Some("E")),
+                        (Some("C"), Some("C")),
+                        (Some("D"), Some("D")),
+                        (Some("D"), None),
+                        (None, Some("X")),
+                        (None, Some("F"))],
+                   pairs);
+    }
+
+    #[test]
+    fn peripherals() {
+        let old = [peripheral("GPIOA", 0x4002_0000, vec![]),
+                   peripheral("GPIOB", 0x4002_0400, vec![]),
+                   peripheral("GPIOC", 0x4002_0800, vec![])];
+        let new = [peripheral("GPIOA", 0x4002_1000, vec![]),
+                   peripheral("PORTB", 0x4002_0400, vec![]),
+                   peripheral("GPIOD", 0x4002_0c00, vec![])];
+        assert!(changes(&old, &old).is_empty());
+        assert_eq!(vec!["GPIOA: address moved from 0x40020000 to 0x40021000",
+                        "PORTB: renamed from GPIOB",
+                        "GPIOC: removed",
+                        "GPIOD: added"],
+                   changes(&old, &new));
+    }
+
+    #[test]
+    fn registers() {
+        use access::Access;
+        use cluster::Cluster;
+
+        let cluster = |name: &str, offset: u64, register: &str| {
+            let xml = format!("<cluster><name>{}</name><addressOffset>{}</addressOffset>\
+                               <register><name>{}</name><addressOffset>0x0</addressOffset>\
+                               </register></cluster>",
+                              name,
+                              offset,
+                              register);
+            Cluster::from_element(&parse_element(xml.as_bytes()).unwrap()).unwrap()
+        };
+
+        let old = Peripheral::builder("DMA", 0x4002_0000)
+            .register(Register::builder("ISR", 0x0)
+                .access(Access::ReadOnly)
+                .reset_value(0)
+                .build()
+                .unwrap())
+            .register(register("IFCR", 0x4, vec![]))
+            .register(register("CH", 0x8, vec![]))
+            .cluster(cluster("CH1", 0x10, "CCR"))
+            .build()
+            .unwrap();
+        let new = Peripheral::builder("DMA", 0x4002_0000)
+            .register(Register::builder("ISR", 0x0).size(16).build().unwrap())
+            .register(register("CLEAR", 0x4, vec![]))
+            .cluster(cluster("CH", 0x8, "CCR"))
+            .cluster(cluster("CH1", 0x20, "CPAR"))
+            .build()
+            .unwrap();
+        // A register and a cluster are never matched together
+        assert_eq!(vec!["DMA.ISR: size changed from 32 to 16 bits",
+                        "DMA.ISR: access changed from read-only to unspecified",
+                        "DMA.ISR: reset value changed from 0x0 to unspecified",
+                        "DMA.CLEAR: renamed from IFCR",
+                        "DMA.CH: removed",
+                        "DMA.CH: added",
+                        "DMA.CH1: address moved from 0x10 to 0x20",
+                        "DMA.CH1.CPAR: renamed from CCR"],
+                   changes(&[old], &[new]));
+    }
+
+    #[test]
+    fn fields() {
+        use access::Access;
+
+        let field = |name: &str, bits| Field::builder(name, bits).build().unwrap();
+        let old = peripheral("GPIOA",
+                             0x4002_0000,
+                             vec![register("MODER",
+                                           0x0,
+                                           vec![field("MODER0", 0..=1),
+                                                field("MODER1", 2..=3),
+                                                field("MODER2", 4..=5),
+                                                field("LOCK", 31..=31),
+                                                field("OLD", 8..=8)])]);
+        let new = peripheral("GPIOA",
+                             0x4002_0000,
+                             vec![register("MODER",
+                                           0x0,
+                                           vec![field("MODER0", 0..=2),
+                                                field("MODER1", 3..=3),
+                                                Field::builder("MODER2", 6..=7)
+                                                    .access(Access::WriteOnly)
+                                                    .build()
+                                                    .unwrap(),
+                                                field("KEY", 31..=31),
+                                                field("NEW", 9..=9)])]);
+        assert_eq!(vec!["GPIOA.MODER.MODER0: field widened from [1:0] to [2:0]",
+                        "GPIOA.MODER.MODER1: field narrowed from [3:2] to [3:3]",
+                        "GPIOA.MODER.MODER2: field moved from [5:4] to [7:6]",
+                        "GPIOA.MODER.MODER2: access changed from unspecified to write-only",
+                        "GPIOA.MODER.KEY: renamed from LOCK",
+                        "GPIOA.MODER.OLD: removed",
+                        "GPIOA.MODER.NEW: added"],
+                   changes(&[old], &[new]));
+    }
+
+    #[test]
+    fn enumerated_values() {
+        let values = |values: &[(&str, EnumeratedValueData)]| {
+            let values = values.iter()
+                .map(|&(name, ref value)| {
+                    EnumeratedValue {
+                        name: name.to_string(),
+                        description: None,
+                        value: value.clone(),
+                        unknown_elements: vec![],
+                    }
+                })
+                .collect();
+            EnumeratedValues::new(None, None, values).unwrap()
+        };
+        let value = |value| {
+            EnumeratedValueData::Value {
+                value: value,
+                do_not_care: 0,
+            }
+        };
+        let mode = |enumerated_values| {
+            let field = Field::builder("MODE", 0..=1)
+                .enumerated_values(enumerated_values)
+                .build()
+                .unwrap();
+            peripheral("GPIOA", 0x4002_0000, vec![register("MODER", 0x0, vec![field])])
+        };
+        let old = mode(values(&[("Input", value(0)),
+                                ("Output", value(1)),
+                                ("Alternate", value(2)),
+                                ("Other", EnumeratedValueData::IsDefault(true))]));
+        let new = mode(values(&[("Input", value(0)),
+                                ("Output", value(3)),
+                                ("Function", value(2)),
+                                ("Other",
+                                 EnumeratedValueData::Value {
+                                     value: 0b10,
+                                     do_not_care: 0b01,
+                                 })]));
+        assert_eq!(vec!["GPIOA.MODER.MODE.Output: value changed from 1 to 3",
+                        "GPIOA.MODER.MODE.Function: renamed from Alternate",
+                        "GPIOA.MODER.MODE.Other: value changed from isDefault true to #1x"],
+                   changes(&[old], &[new]));
+    }
+}
@@ -1,10 +1,15 @@
 use errors::*;
+use std::fmt;
 use std::str::FromStr;
 use types::*;
-use utils::get_child_text;
+use utils::{add_child_text, add_optional_child_text, get_child_text};
 use xmltree;
 
-#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+/// The names of the elements of this group.
+pub const ELEMENT_NAMES: &'static [&'static str] = &["dim", "dimIncrement", "dimIndex"];
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
 pub struct DimElementGroup {
     pub dim: Option<ScaledNonNegativeInteger>,
     pub dim_increment: Option<ScaledNonNegativeInteger>,
@@ -35,6 +40,50 @@ impl DimElementGroup {
         })
     }
 
+    /// Appends the elements of this group to `element`.
+    pub fn add_to_element(&self, element: &mut xmltree::Element) {
+        add_optional_child_text(element, "dim", &self.dim);
+        if let Some(dim_increment) = self.dim_increment {
+            add_child_text(element, "dimIncrement", format!("0x{:X}", dim_increment));
+        }
+        add_optional_child_text(element, "dimIndex", &self.dim_index);
+    }
+
+    /// Returns the index and the offset from the first element of each element of the array
+    /// described by this group. If `dim` is not set, there is a single element without index.
+    pub fn elements(&self) -> Vec<(Option<String>, u64)> {
+        let dim = match self.dim {
+            Some(dim) => dim.0,
+            None => return vec![(None, 0)],
+        };
+        // The indices are generated lazily, as the ranges of `dimIndex` can be much larger than
+        // `dim`
+        let indices: Box<Iterator<Item = String>> = match self.dim_index {
+            Some(DimIndexType::List(ref list)) => Box::new(list.iter().cloned()),
+            Some(DimIndexType::CharRange { start, end }) => {
+                Box::new((start as u8..=end as u8).map(|c| (c as char).to_string()))
+            }
+            Some(DimIndexType::DecimalRange { start, end }) => {
+                Box::new((start..=end).map(|i| i.to_string()))
+            }
+            None => Box::new((0..dim).map(|i| i.to_string())),
+        };
+        let dim_increment = self.dim_increment.map_or(0, |i| i.0);
+
+        indices.take(dim as usize)
+            .enumerate()
+            .map(|(i, index)| (Some(index), (i as u64).wrapping_mul(dim_increment)))
+            .collect()
+    }
+
+    /// Returns the name of the element with the index `index` of an array named `name`.
+    pub fn element_name(name: &str, index: &Option<String>) -> String {
+        match *index {
+            Some(ref index) => name.replace("%s", index),
+            None => name.to_string(),
+        }
+    }
+
     pub fn merge_derived_from(&mut self, derived_from: &DimElementGroup) {
         merge_option_field!(self.dim, derived_from.dim);
         merge_option_field!(self.dim_increment, derived_from.dim_increment);
@@ -42,6 +91,7 @@ impl DimElementGroup {
     }
 }
 
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub enum DimIndexType {
     // [_0-9a-zA-Z]+(,\s*[_0-9a-zA-Z]+)+
@@ -52,6 +102,22 @@ pub enum DimIndexType {
     DecimalRange { start: u64, end: u64 },
 }
 
+impl DimIndexType {
+    /// Returns the number of indices.
+    pub fn len(&self) -> u64 {
+        match *self {
+            DimIndexType::List(ref list) => list.len() as u64,
+            DimIndexType::CharRange { start, end } => {
+                (end as u64 + 1).saturating_sub(start as u64)
+            }
+            DimIndexType::DecimalRange { start, end } if start <= end => {
+                (end - start).saturating_add(1)
+            }
+            DimIndexType::DecimalRange { .. } => 0,
+        }
+    }
+}
+
 fn is_dim_index_char_valid(c: char) -> bool {
     match c {
         '_' | '0'...'9' | 'a'...'z' | 'A'...'Z' => true,
@@ -63,12 +129,21 @@ fn is_dim_index_str_valid(s: &str) -> bool {
     s.chars().all(is_dim_index_char_valid)
 }
 
+/// Parses a bound of a decimal range, which is only made of decimal digits.
+fn parse_decimal(s: &str) -> Option<u64> {
+    if !s.is_empty() && s.chars().all(|c| c.is_digit(10)) {
+        s.parse().ok()
+    } else {
+        None
+    }
+}
+
 impl FromStr for DimIndexType {
     type Err = Error;
 
     fn from_str(s: &str) -> Result<DimIndexType> {
         if s.contains('-') {
-            let mut splitted = s.split('-');
+            let mut splitted = s.splitn(2, '-');
             let left = splitted.next().unwrap();
             let right = splitted.next().unwrap();
             if left.len() == 1 && right.len() == 1 {
@@ -82,7 +157,7 @@ impl FromStr for DimIndexType {
                 }
             }
 
-            if let (Ok(start), Ok(end)) = (left.parse(), right.parse()) {
+            if let (Some(start), Some(end)) = (parse_decimal(left), parse_decimal(right)) {
                 Ok(DimIndexType::DecimalRange {
                     start: start,
                     end: end,
@@ -100,3 +175,56 @@ impl FromStr for DimIndexType {
         }
     }
 }
+
+impl fmt::Display for DimIndexType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            DimIndexType::List(ref list) => write!(f, "{}", list.join(",")),
+            DimIndexType::CharRange { start, end } => write!(f, "{}-{}", start, end),
+            DimIndexType::DecimalRange { start, end } => write!(f, "{}-{}", start, end),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dim_index_ranges() {
+        assert_eq!(Some(DimIndexType::DecimalRange { start: 0, end: 3 }), "0-3".parse().ok());
+        assert_eq!(Some(DimIndexType::DecimalRange { start: 16, end: 31 }), "16-31".parse().ok());
+        assert_eq!(Some(DimIndexType::CharRange { start: 'A', end: 'D' }), "A-D".parse().ok());
+        // The bounds of decimal ranges are only made of decimal digits
+        for s in &["0-0x+3", "0x10-0x1F", "1k-2k", "+1-3", "1-", "-3", " 0 - 3 ", "0-3-5",
+                   "0-18446744073709551616"] {
+            assert!(s.parse::<DimIndexType>().is_err(), "{}", s);
+        }
+    }
+
+    #[test]
+    fn large_ranges() {
+        let dim_index: DimIndexType = "0-4294967295".parse().unwrap();
+        assert_eq!(1 << 32, dim_index.len());
+        let dim_element = DimElementGroup {
+            dim: Some(ScaledNonNegativeInteger(2)),
+            dim_increment: Some(ScaledNonNegativeInteger(4)),
+            dim_index: Some(dim_index),
+        };
+        assert_eq!(vec![(Some("0".to_string()), 0), (Some("1".to_string()), 4)],
+                   dim_element.elements());
+
+        let max = u64::max_value();
+        let dim_index = DimIndexType::DecimalRange { start: max - 1, end: max };
+        assert_eq!(2, dim_index.len());
+        assert_eq!(max, DimIndexType::DecimalRange { start: 0, end: max }.len());
+        assert_eq!(0, DimIndexType::DecimalRange { start: 3, end: 2 }.len());
+        let dim_element = DimElementGroup {
+            dim: Some(ScaledNonNegativeInteger(3)),
+            dim_increment: None,
+            dim_index: Some(dim_index),
+        };
+        assert_eq!(vec![(Some((max - 1).to_string()), 0), (Some(max.to_string()), 0)],
+                   dim_element.elements());
+    }
+}
@@ -1,7 +1,10 @@
 use errors::*;
+use is_similar::{Difference, ExplainDifferences, IsSimilar, IsSimilarOptions};
 use std::str::FromStr;
 use types::*;
-use utils::get_child_text;
+use utils::{add_child_text, add_optional_child_text, element_path_segment, get_child_text,
+            new_element};
+use xml_fragment::XmlFragment;
 use xmltree;
 
 pub type EnumerationName = String;
@@ -13,6 +16,7 @@ str_enum!{EnumUsage,
     "read-write" => ReadWrite,
 }
 
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub enum EnumeratedValueData {
     IsDefault(bool),
@@ -51,6 +55,27 @@ impl EnumeratedValueData {
         }
     }
 
+    /// Returns the string used for this value in a `value` element. Do not care bits are written
+    /// with the binary form.
+    pub fn value_str(value: u64, do_not_care: u64) -> String {
+        if do_not_care == 0 {
+            value.to_string()
+        } else {
+            let bits = 64 - (value | do_not_care).leading_zeros();
+            let mut s = "#".to_string();
+            for i in (0..bits).rev() {
+                if do_not_care & (1 << i) != 0 {
+                    s.push('x');
+                } else if value & (1 << i) != 0 {
+                    s.push('1');
+                } else {
+                    s.push('0');
+                }
+            }
+            s
+        }
+    }
+
     pub fn from_element(element: &xmltree::Element) -> Result<EnumeratedValueData> {
         if let Some(is_default) = get_child_text(element, "isDefault") {
             match &*is_default {
@@ -67,13 +92,39 @@ impl EnumeratedValueData {
             Err(ErrorKind::MissingField("enumeratedValue", "isDefault or value").into())
         }
     }
+
+    /// Appends the element describing this value to `element`.
+    pub fn add_to_element(&self, element: &mut xmltree::Element) {
+        match *self {
+            EnumeratedValueData::IsDefault(is_default) => {
+                add_child_text(element, "isDefault", is_default)
+            }
+            EnumeratedValueData::Value { value, do_not_care } => {
+                add_child_text(element,
+                               "value",
+                               EnumeratedValueData::value_str(value, do_not_care))
+            }
+        }
+    }
 }
 
+/// The names of the child elements read by `EnumeratedValue::from_element`.
+const ENUMERATED_VALUE_ELEMENT_NAMES: &'static [&'static str] = &["name",
+                                                                   "description",
+                                                                   "isDefault",
+                                                                   "value"];
+
+/// The names of the child elements in the order required by the schema.
+const ENUMERATED_VALUE_SCHEMA_ORDER: &'static [&'static str] = &["name", "description", "value",
+                                                                 "isDefault"];
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub struct EnumeratedValue {
     pub name: EnumerationName,
     pub description: Option<String>,
     pub value: EnumeratedValueData,
+    pub unknown_elements: Vec<XmlFragment>,
 }
 
 impl EnumeratedValue {
@@ -81,24 +132,82 @@ impl EnumeratedValue {
         let name = get_mandatory_child_text!(element, "enumeratedValue", "name");
         let description = get_child_text(element, "description");
         let value = try!(EnumeratedValueData::from_element(element));
+        let unknown_elements =
+            XmlFragment::unknown_children(element, &[ENUMERATED_VALUE_ELEMENT_NAMES]);
         Ok(EnumeratedValue {
             name: name,
             description: description,
             value: value,
+            unknown_elements: unknown_elements,
         })
     }
+
+    pub fn to_element(&self) -> xmltree::Element {
+        let mut element = new_element("enumeratedValue");
+        add_child_text(&mut element, "name", &self.name);
+        add_optional_child_text(&mut element, "description", &self.description);
+        self.value.add_to_element(&mut element);
+        XmlFragment::add_to_element(&mut element,
+                                    &self.unknown_elements,
+                                    ENUMERATED_VALUE_SCHEMA_ORDER);
+        element
+    }
+
+    /// Returns true if `value` is the value of this enumerated value, ignoring the do not care
+    /// bits. Always returns false for a default value.
+    pub fn matches(&self, value: u64) -> bool {
+        match self.value {
+            EnumeratedValueData::Value { value: v, do_not_care } => {
+                v & !do_not_care == value & !do_not_care
+            }
+            EnumeratedValueData::IsDefault(_) => false,
+        }
+    }
+
+    pub fn is_default(&self) -> bool {
+        self.value == EnumeratedValueData::IsDefault(true)
+    }
 }
 
 
+/// The names of the child elements read by `EnumeratedValues::from_element`.
+const ENUMERATED_VALUES_ELEMENT_NAMES: &'static [&'static str] = &["name",
+                                                                    "usage",
+                                                                    "enumeratedValue"];
+
+/// The names of the child elements in the order required by the schema.
+const ENUMERATED_VALUES_SCHEMA_ORDER: &'static [&'static str] = &["name", "headerEnumName", "usage",
+                                                                  "enumeratedValue"];
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub struct EnumeratedValues {
     derived_from: Option<IdentifierType>,
     name: Option<EnumerationName>,
     usage: Option<EnumUsage>,
     enumerated_values: Vec<EnumeratedValue>,
+    unknown_elements: Vec<XmlFragment>,
 }
 
 impl EnumeratedValues {
+    /// Returns the enumerated values `enumerated_values`, or an error if there is none.
+    pub fn new(name: Option<EnumerationName>,
+               usage: Option<EnumUsage>,
+               enumerated_values: Vec<EnumeratedValue>)
+               -> Result<EnumeratedValues> {
+        if enumerated_values.is_empty() {
+            Err(ErrorKind::MissingField("enumeratedValues", "enumeratedValue").into())
+        } else {
+            Ok(EnumeratedValues {
+                derived_from: None,
+                name: name,
+                usage: usage,
+                enumerated_values: enumerated_values,
+                unknown_elements: vec![],
+            })
+        }
+    }
+
     pub fn from_element(element: &xmltree::Element) -> Result<EnumeratedValues> {
         let derived_from = element.attributes.get("derivedFrom").cloned();
         let name = get_child_text(element, "name");
@@ -110,9 +219,12 @@ impl EnumeratedValues {
         let enumerated_values: Result<Vec<_>> = element.children
             .iter()
             .filter(|e| e.name == "enumeratedValue")
-            .map(EnumeratedValue::from_element)
+            .enumerate()
+            .map(|(i, e)| EnumeratedValue::from_element(e).in_element(element_path_segment(e, i)))
             .collect();
         let enumerated_values = try!(enumerated_values);
+        let unknown_elements =
+            XmlFragment::unknown_children(element, &[ENUMERATED_VALUES_ELEMENT_NAMES]);
         if enumerated_values.is_empty() {
             Err(ErrorKind::MissingField("enumeratedValues", "enumeratedValue").into())
         } else {
@@ -121,7 +233,107 @@ impl EnumeratedValues {
                 name: name,
                 usage: usage,
                 enumerated_values: enumerated_values,
+                unknown_elements: unknown_elements,
             })
         }
     }
+
+    pub fn to_element(&self) -> xmltree::Element {
+        let mut element = new_element("enumeratedValues");
+        if let Some(ref derived_from) = self.derived_from {
+            element.attributes.insert("derivedFrom".to_string(), derived_from.clone());
+        }
+        add_optional_child_text(&mut element, "name", &self.name);
+        add_optional_child_text(&mut element, "usage", &self.usage);
+        for enumerated_value in &self.enumerated_values {
+            element.children.push(enumerated_value.to_element());
+        }
+        XmlFragment::add_to_element(&mut element,
+                                    &self.unknown_elements,
+                                    ENUMERATED_VALUES_SCHEMA_ORDER);
+        element
+    }
+
+    pub fn derived_from(&self) -> &Option<IdentifierType> {
+        &self.derived_from
+    }
+
+    pub fn name(&self) -> &Option<EnumerationName> {
+        &self.name
+    }
+
+    pub fn usage(&self) -> Option<EnumUsage> {
+        self.usage
+    }
+
+    pub fn enumerated_values(&self) -> &[EnumeratedValue] {
+        &self.enumerated_values
+    }
+
+    pub fn enumerated_values_mut(&mut self) -> &mut [EnumeratedValue] {
+        &mut self.enumerated_values
+    }
+
+    /// Returns true if these values describe what is read from the field. The default usage is
+    /// read-write.
+    pub fn is_read(&self) -> bool {
+        self.usage != Some(EnumUsage::Write)
+    }
+
+    /// Returns the enumerated value matching `value` or, if there is none, the default value.
+    pub fn find(&self, value: u64) -> Option<&EnumeratedValue> {
+        self.enumerated_values
+            .iter()
+            .find(|v| v.matches(value))
+            .or_else(|| self.enumerated_values.iter().find(|v| v.is_default()))
+    }
+}
+
+impl<'a, 'b> IsSimilar<&'a EnumeratedValues> for &'b EnumeratedValues {
+    fn is_similar(self, other: &EnumeratedValues, options: &IsSimilarOptions) -> bool {
+        if options.enumerated_values_by_value() {
+            let values = |e: &EnumeratedValues| {
+                let mut values: Vec<_> =
+                    e.enumerated_values.iter().map(|v| v.value.clone()).collect();
+                values.sort();
+                values
+            };
+            self.usage == other.usage && values(self) == values(other)
+        } else {
+            self == other
+        }
+    }
+}
+
+impl<'a, 'b> ExplainDifferences<&'a EnumeratedValues> for &'b EnumeratedValues {
+    /// The difference has an empty path.
+    fn explain_differences(self,
+                           other: &EnumeratedValues,
+                           options: &IsSimilarOptions)
+                           -> Vec<Difference> {
+        if self.is_similar(other, options) {
+            vec![]
+        } else {
+            vec![Difference::new("", "enumeratedValues")]
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn value_str() {
+        for &(value, do_not_care) in &[(0, 0), (5, 0), (0b10, 0b01), (0, 0b100), (1 << 63, 1)] {
+            let s = EnumeratedValueData::value_str(value, do_not_care);
+            assert_eq!(EnumeratedValueData::Value {
+                           value: value,
+                           do_not_care: do_not_care,
+                       },
+                       EnumeratedValueData::from_value_str(&s).unwrap());
+        }
+        assert_eq!("#1x", EnumeratedValueData::value_str(0b10, 0b01));
+        assert_eq!("#x00", EnumeratedValueData::value_str(0, 0b100));
+    }
 }
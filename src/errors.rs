@@ -4,6 +4,7 @@ use xmltree;
 error_chain! {
     foreign_links {
         xmltree::ParseError, XmlParseError;
+        std::io::Error, IoError;
         std::num::ParseIntError, ParseIntError;
     }
 
@@ -17,5 +18,41 @@ error_chain! {
             description("unexpected value")
             display("expected: {}, got: {}", expected, actual)
         }
+
+        InElement(path: String, kind: Box<ErrorKind>) {
+            description("error in element")
+            display("in '{}': {}", path, kind)
+        }
+    }
+}
+
+impl Error {
+    /// Returns the chain of elements enclosing the element where the error occurred, for example
+    /// `device/peripherals/peripheral[TIM2]/registers/register[CR1]`.
+    pub fn element_path(&self) -> Option<&str> {
+        match self.0 {
+            ErrorKind::InElement(ref path, _) => Some(&*path),
+            _ => None,
+        }
+    }
+}
+
+pub trait InElement<T> {
+    /// Prepends `segment` to the element path of the error.
+    fn in_element<S: Into<String>>(self, segment: S) -> Result<T>;
+}
+
+impl<T> InElement<T> for Result<T> {
+    fn in_element<S: Into<String>>(self, segment: S) -> Result<T> {
+        self.map_err(|e| {
+            let Error(kind, state) = e;
+            let kind = match kind {
+                ErrorKind::InElement(path, kind) => {
+                    ErrorKind::InElement(format!("{}/{}", segment.into(), path), kind)
+                }
+                kind => ErrorKind::InElement(segment.into(), Box::new(kind)),
+            };
+            Error(kind, state)
+        })
     }
 }
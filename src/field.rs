@@ -1,15 +1,32 @@
 use access::Access;
-use bit_range::BitRange;
-use enumerated_values::EnumeratedValues;
+use bit_range::{self, BitRange};
+use enumerated_values::{EnumeratedValue, EnumeratedValues};
 use errors::*;
-use is_similar::{IsSimilar, IsSimilarOptions};
+use is_similar::{Difference, ExplainDifferences, IsSimilar, IsSimilarOptions};
 use modified_write_values::ModifiedWriteValues;
 use read_action::ReadAction;
 use std::collections::HashMap;
+use std::ops::RangeInclusive;
 use types::*;
-use utils::{extract_prefix, get_child_text};
+use utils::{add_child_text, add_optional_child_text, element_path_segment, extract_prefix,
+            get_child_text, new_element};
+use xml_fragment::XmlFragment;
 use xmltree;
 
+/// The names of the child elements read by `from_element`.
+const ELEMENT_NAMES: &'static [&'static str] = &["name", "description", "access",
+                                                 "modifiedWriteValues", "readAction",
+                                                 "enumeratedValues"];
+
+/// The names of the child elements in the order required by the schema, including the ones not
+/// read by `from_element`.
+const SCHEMA_ORDER: &'static [&'static str] = &["dim", "dimIncrement", "dimIndex", "dimName",
+                                                "dimArrayIndex", "name", "description", "bitOffset",
+                                                "bitWidth", "lsb", "msb", "bitRange", "access",
+                                                "modifiedWriteValues", "writeConstraint",
+                                                "readAction", "enumeratedValues"];
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Field {
     pub derived_from: Option<IdentifierType>,
@@ -20,9 +37,31 @@ pub struct Field {
     pub modified_write_values: Option<ModifiedWriteValues>,
     pub read_action: Option<ReadAction>,
     pub enumerated_values: Vec<EnumeratedValues>,
+    pub unknown_elements: Vec<XmlFragment>,
 }
 
 impl Field {
+    /// Returns a builder for a field named `name` made of the bits `bits`, for example `0..=1`
+    /// for the two least significant bits.
+    pub fn builder<S: Into<String>>(name: S, bits: RangeInclusive<u32>) -> FieldBuilder {
+        FieldBuilder {
+            field: Field {
+                derived_from: None,
+                name: name.into(),
+                description: None,
+                bit_range: BitRange {
+                    lsb: *bits.start(),
+                    msb: *bits.end(),
+                },
+                access: None,
+                modified_write_values: None,
+                read_action: None,
+                enumerated_values: vec![],
+                unknown_elements: vec![],
+            },
+        }
+    }
+
     pub fn from_element(element: &xmltree::Element) -> Result<Field> {
         let derived_from = element.attributes.get("derivedFrom").cloned();
         let name = get_mandatory_child_text!(element, "field", "name");
@@ -47,9 +86,12 @@ impl Field {
         let enumerated_values: Result<Vec<_>> = element.children
             .iter()
             .filter(|e| e.name == "enumeratedValues")
-            .map(EnumeratedValues::from_element)
+            .enumerate()
+            .map(|(i, e)| EnumeratedValues::from_element(e).in_element(element_path_segment(e, i)))
             .collect();
         let enumerated_values = try!(enumerated_values);
+        let unknown_elements =
+            XmlFragment::unknown_children(element, &[ELEMENT_NAMES, bit_range::ELEMENT_NAMES]);
 
         Ok(Field {
             derived_from: derived_from,
@@ -60,9 +102,30 @@ impl Field {
             modified_write_values: modified_write_values,
             read_action: read_action,
             enumerated_values: enumerated_values,
+            unknown_elements: unknown_elements,
         })
     }
 
+    pub fn to_element(&self) -> xmltree::Element {
+        let mut element = new_element("field");
+        if let Some(ref derived_from) = self.derived_from {
+            element.attributes.insert("derivedFrom".to_string(), derived_from.clone());
+        }
+        add_child_text(&mut element, "name", &self.name);
+        add_optional_child_text(&mut element, "description", &self.description);
+        self.bit_range.add_to_element(&mut element);
+        add_optional_child_text(&mut element, "access", &self.access);
+        add_optional_child_text(&mut element,
+                                "modifiedWriteValues",
+                                &self.modified_write_values);
+        add_optional_child_text(&mut element, "readAction", &self.read_action);
+        for enumerated_values in &self.enumerated_values {
+            element.children.push(enumerated_values.to_element());
+        }
+        XmlFragment::add_to_element(&mut element, &self.unknown_elements, SCHEMA_ORDER);
+        element
+    }
+
     pub fn is_read(&self) -> bool {
         match self.access {
             Some(ref access) => access.is_read(),
@@ -77,6 +140,34 @@ impl Field {
         }
     }
 
+    /// Returns the value of this field in the register value `raw`.
+    pub fn extract(&self, raw: u64) -> u64 {
+        (raw & self.bit_range.mask()).checked_shr(self.bit_range.lsb).unwrap_or(0)
+    }
+
+    /// Returns the register value `raw` with this field set to `value`. The bits of `value` that
+    /// don't fit in the field are ignored.
+    pub fn insert(&self, raw: u64, value: u64) -> u64 {
+        let mask = self.bit_range.mask();
+        let value = if self.bit_range.lsb >= 64 {
+            0
+        } else {
+            (value << self.bit_range.lsb) & mask
+        };
+        (raw & !mask) | value
+    }
+
+    /// Returns the enumerated value describing the value of this field in the register value
+    /// `raw`. Only the enumerated values used for reading are searched.
+    pub fn decode(&self, raw: u64) -> Option<&EnumeratedValue> {
+        let value = self.extract(raw);
+        self.enumerated_values
+            .iter()
+            .filter(|e| e.is_read())
+            .filter_map(|e| e.find(value))
+            .next()
+    }
+
     pub fn merge_derived_from(&mut self, derived_from: &Field) {
         self.derived_from = derived_from.derived_from.clone();
         merge_option_field!(self.description, derived_from.description);
@@ -97,9 +188,84 @@ impl<'a, 'b> IsSimilar<&'a Field> for &'b Field {
         } else {
             self.name == other.name && self.bit_range == other.bit_range &&
             self.access == other.access &&
-            self.modified_write_values == other.modified_write_values &&
+            (options.ignore_modified_write_values() ||
+             self.modified_write_values == other.modified_write_values) &&
             self.read_action == other.read_action &&
-            self.enumerated_values == other.enumerated_values
+            self.enumerated_values.is_similar(&other.enumerated_values, options)
+        }
+    }
+}
+
+impl<'a, 'b> ExplainDifferences<&'a Field> for &'b Field {
+    fn explain_differences(self, other: &Field, options: &IsSimilarOptions) -> Vec<Difference> {
+        let mut differences = vec![];
+        if options.ignore_fields() {
+            return differences;
+        }
+        let properties = [(self.name != other.name, "name"),
+                          (self.bit_range != other.bit_range, "bitRange"),
+                          (self.access != other.access, "access"),
+                          (!options.ignore_modified_write_values() &&
+                           self.modified_write_values != other.modified_write_values,
+                           "modifiedWriteValues"),
+                          (self.read_action != other.read_action, "readAction"),
+                          (!self.enumerated_values.is_similar(&other.enumerated_values, options),
+                           "enumeratedValues")];
+        for &(differs, property) in &properties {
+            if differs {
+                differences.push(Difference::new(&*self.name, property));
+            }
+        }
+        differences
+    }
+}
+
+/// A builder for a `Field`, returned by `Field::builder`.
+#[derive(Clone, Debug)]
+pub struct FieldBuilder {
+    field: Field,
+}
+
+impl FieldBuilder {
+    pub fn description<S: Into<String>>(mut self, description: S) -> FieldBuilder {
+        self.field.description = Some(description.into());
+        self
+    }
+
+    pub fn access(mut self, access: Access) -> FieldBuilder {
+        self.field.access = Some(access);
+        self
+    }
+
+    pub fn modified_write_values(mut self,
+                                 modified_write_values: ModifiedWriteValues)
+                                 -> FieldBuilder {
+        self.field.modified_write_values = Some(modified_write_values);
+        self
+    }
+
+    pub fn read_action(mut self, read_action: ReadAction) -> FieldBuilder {
+        self.field.read_action = Some(read_action);
+        self
+    }
+
+    pub fn enumerated_values(mut self, enumerated_values: EnumeratedValues) -> FieldBuilder {
+        self.field.enumerated_values.push(enumerated_values);
+        self
+    }
+
+    /// Returns the field, or an error if its name is empty or if its bit range is not in a 64 bits
+    /// register.
+    pub fn build(self) -> Result<Field> {
+        let bit_range = self.field.bit_range;
+        if self.field.name.is_empty() {
+            Err(ErrorKind::MissingField("field", "name").into())
+        } else if bit_range.msb < bit_range.lsb || bit_range.msb >= 64 {
+            Err(ErrorKind::UnexpectedValue("a bit range with lsb <= msb < 64",
+                                           format!("[{}:{}]", bit_range.msb, bit_range.lsb))
+                .into())
+        } else {
+            Ok(self.field)
         }
     }
 }
@@ -215,3 +381,78 @@ fn should_group(fields: &mut [(Field, Option<usize>)]) -> bool {
         false
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use xml_fragment::parse_element;
+
+    #[test]
+    fn builder() {
+        use std::ops::RangeInclusive;
+
+        let field = Field::builder("MODE", 4..=6)
+            .description("Mode")
+            .access(Access::ReadWrite)
+            .read_action(ReadAction::Clear)
+            .build()
+            .unwrap();
+        assert_eq!(BitRange { lsb: 4, msb: 6 }, field.bit_range);
+        assert_eq!(Some("Mode".to_string()), field.description);
+        assert_eq!(field, Field::from_element(&field.to_element()).unwrap());
+
+        assert!(Field::builder("ALL", 0..=63).build().is_ok());
+        assert!(Field::builder("MODE", RangeInclusive::new(6, 4)).build().is_err());
+        assert!(Field::builder("MODE", 63..=64).build().is_err());
+        assert!(Field::builder("", 0..=1).build().is_err());
+    }
+
+    #[test]
+    fn extract_insert() {
+        let field = |bits| Field::builder("F", bits).build().unwrap();
+        let pl = field(12..=13);
+        assert_eq!(0b11, pl.extract(0xffff_3fff));
+        assert_eq!(0b10, pl.extract(0x2000));
+        // The bits of the value that don't fit in the field are ignored
+        assert_eq!(0xffff_efff, pl.insert(0xffff_ffff, 0b110));
+        assert_eq!(0x3000, pl.insert(0, 0b11));
+
+        let all = field(0..=63);
+        assert_eq!(!0, all.extract(!0));
+        assert_eq!(0x1234, all.insert(!0, 0x1234));
+        let top = field(63..=63);
+        assert_eq!(1, top.extract(1 << 63));
+        assert_eq!(1 << 63, top.insert(0, 0b11));
+        // Such a field can only be parsed, the builder rejects it
+        let mut outside = field(0..=1);
+        outside.bit_range = BitRange { lsb: 64, msb: 65 };
+        assert_eq!(0, outside.extract(!0));
+        assert_eq!(0x5, outside.insert(0x5, 0b11));
+    }
+
+    #[test]
+    fn decode() {
+        let xml = r#"<field><name>PL</name><bitRange>[13:12]</bitRange>
+                         <enumeratedValues><usage>write</usage>
+                             <enumeratedValue><name>Set</name><value>1</value></enumeratedValue>
+                         </enumeratedValues>
+                         <enumeratedValues>
+                             <enumeratedValue><name>Low</name><value>0</value></enumeratedValue>
+                             <enumeratedValue><name>High</name><value>#1x</value>
+                             </enumeratedValue>
+                             <enumeratedValue><name>Other</name><isDefault>true</isDefault>
+                             </enumeratedValue>
+                         </enumeratedValues>
+                     </field>"#;
+        let field = Field::from_element(&parse_element(xml.as_bytes()).unwrap()).unwrap();
+        assert_eq!("Low", field.decode(0xffff_cfff).unwrap().name);
+        // The values used for writing are skipped, and the default value is used instead
+        assert_eq!("Other", field.decode(0x1000).unwrap().name);
+        // The do not care bits are ignored
+        assert_eq!("High", field.decode(0x2000).unwrap().name);
+        assert_eq!("High", field.decode(0x3000).unwrap().name);
+
+        let field = Field::builder("EN", 0..=0).build().unwrap();
+        assert!(field.decode(1).is_none());
+    }
+}
@@ -0,0 +1,265 @@
+use device::Device;
+use dim_element_group::DimElementGroup;
+use field::Field;
+use peripheral::Peripheral;
+use register::Register;
+use register_or_cluster::RegisterOrCluster;
+use register_properties_group::RegisterPropertiesGroup;
+use std::vec;
+use utils::offset_address;
+
+/// A register as it exists in the address space of the device: an element of a register array,
+/// or a register that is not an array, inside a given element of each enclosing array.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RegisterInstance<'a> {
+    /// The dotted name of the register, for example `DMA1.CH3.CCR`.
+    pub path: String,
+    pub address: u64,
+    /// The size of the register in bits.
+    pub size: u64,
+    /// The properties of the register merged with the ones of its clusters, peripheral and
+    /// device.
+    pub properties: RegisterPropertiesGroup,
+    pub peripheral: &'a Peripheral,
+    pub register: &'a Register,
+}
+
+/// A field of a `RegisterInstance`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FieldInstance<'a> {
+    /// The dotted name of the field, for example `DMA1.CH3.CCR.PL`.
+    pub path: String,
+    pub register: RegisterInstance<'a>,
+    pub field: &'a Field,
+}
+
+/// An iterator over the register instances of a device, returned by `Device::registers`. The
+/// instances whose address does not fit in 64 bits are skipped.
+pub struct Registers<'a> {
+    instances: vec::IntoIter<RegisterInstance<'a>>,
+}
+
+impl<'a> Registers<'a> {
+    pub fn new(device: &'a Device) -> Registers<'a> {
+        let mut instances = vec![];
+        for peripheral in &device.peripherals {
+            let properties = peripheral.register_properties.merge(&device.register_properties);
+            if let Some(ref registers) = peripheral.registers {
+                for (index, offset) in peripheral.dim_element.elements() {
+                    let base_address = match peripheral.base_address.0.checked_add(offset) {
+                        Some(base_address) => base_address,
+                        None => continue,
+                    };
+                    collect_instances(registers,
+                                      peripheral,
+                                      base_address,
+                                      &DimElementGroup::element_name(&peripheral.name, &index),
+                                      &properties,
+                                      &mut instances);
+                }
+            }
+        }
+
+        Registers { instances: instances.into_iter() }
+    }
+}
+
+impl<'a> Iterator for Registers<'a> {
+    type Item = RegisterInstance<'a>;
+
+    fn next(&mut self) -> Option<RegisterInstance<'a>> {
+        self.instances.next()
+    }
+}
+
+/// An iterator over the fields of the register instances of a device, returned by
+/// `Device::fields`.
+pub struct Fields<'a> {
+    registers: Registers<'a>,
+    current: Option<(RegisterInstance<'a>, usize)>,
+}
+
+impl<'a> Fields<'a> {
+    pub fn new(device: &'a Device) -> Fields<'a> {
+        Fields {
+            registers: Registers::new(device),
+            current: None,
+        }
+    }
+}
+
+impl<'a> Iterator for Fields<'a> {
+    type Item = FieldInstance<'a>;
+
+    fn next(&mut self) -> Option<FieldInstance<'a>> {
+        loop {
+            if let Some((ref register, ref mut i)) = self.current {
+                let fields = register.register.fields.as_ref().map_or(&[][..], |f| &**f);
+                if let Some(field) = fields.get(*i) {
+                    *i += 1;
+                    return Some(FieldInstance {
+                        path: format!("{}.{}", register.path, field.name),
+                        register: register.clone(),
+                        field: field,
+                    });
+                }
+            }
+
+            match self.registers.next() {
+                Some(register) => self.current = Some((register, 0)),
+                None => return None,
+            }
+        }
+    }
+}
+
+fn collect_instances<'a>(registers: &'a [RegisterOrCluster],
+                         peripheral: &'a Peripheral,
+                         base_address: u64,
+                         prefix: &str,
+                         properties: &RegisterPropertiesGroup,
+                         instances: &mut Vec<RegisterInstance<'a>>) {
+    for r_or_c in registers {
+        match *r_or_c {
+            RegisterOrCluster::Register(ref r) => {
+                let properties = r.register_properties.merge(properties);
+                for (index, offset) in r.dim_element.elements() {
+                    let address = match offset_address(base_address, r.address_offset.0, offset) {
+                        Some(address) => address,
+                        None => continue,
+                    };
+                    let name = DimElementGroup::element_name(&r.name, &index);
+                    instances.push(RegisterInstance {
+                        path: format!("{}.{}", prefix, name),
+                        address: address,
+                        size: properties.size.map_or(32, |s| s.0),
+                        properties: properties.clone(),
+                        peripheral: peripheral,
+                        register: r,
+                    });
+                }
+            }
+            RegisterOrCluster::Cluster(ref c) => {
+                let properties = c.register_properties.merge(properties);
+                for (index, offset) in c.dim_element.elements() {
+                    let address = match offset_address(base_address, c.address_offset.0, offset) {
+                        Some(address) => address,
+                        None => continue,
+                    };
+                    collect_instances(&c.registers,
+                                      peripheral,
+                                      address,
+                                      &format!("{}.{}",
+                                               prefix,
+                                               DimElementGroup::element_name(&c.name, &index)),
+                                      &properties,
+                                      instances);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use access::Access;
+    use device::Device;
+
+    const PERIPHERALS: &'static str = r#"
+        <peripheral>
+            <name>DMA</name><baseAddress>0x1000</baseAddress><access>read-only</access>
+            <registers>
+            <register><name>ISR</name><addressOffset>0x0</addressOffset>
+                <fields>
+                    <field><name>GIF1</name><bitRange>[0:0]</bitRange></field>
+                    <field><name>TCIF1</name><bitRange>[1:1]</bitRange></field>
+                </fields>
+            </register>
+            <cluster>
+                <dim>2</dim><dimIncrement>0x10</dimIncrement>
+                <name>CH%s</name><addressOffset>0x8</addressOffset><size>8</size>
+                <register><name>CCR</name><addressOffset>0x0</addressOffset>
+                    <access>read-write</access>
+                </register>
+                <register>
+                    <dim>2</dim><dimIncrement>0x1</dimIncrement><dimIndex>L,H</dimIndex>
+                    <name>CNDTR%s</name><addressOffset>0x4</addressOffset>
+                    <fields><field><name>NDT</name><bitRange>[7:0]</bitRange></field></fields>
+                </register>
+            </cluster>
+        </registers></peripheral>
+        <peripheral><name>RCC</name><baseAddress>0x2000</baseAddress></peripheral>
+        <peripheral>
+            <dim>2</dim><dimIncrement>0x100</dimIncrement><dimIndex>A,B</dimIndex>
+            <name>GPIO%s</name><baseAddress>0x3000</baseAddress><registers>
+            <register><name>IDR</name><addressOffset>0x10</addressOffset></register>
+        </registers></peripheral>"#;
+
+    fn device(size: &str) -> Device {
+        let xml = format!("<device><name>TEST</name><version>1.0</version>\
+                           <description>Test</description>\
+                           <addressUnitBits>8</addressUnitBits><width>32</width>{}\
+                           <peripherals>{}</peripherals></device>",
+                          size,
+                          PERIPHERALS);
+        Device::parse(xml.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn registers() {
+        let d = device("<size>16</size>");
+        let registers: Vec<_> = d.registers()
+            .map(|r| (r.path, r.address, r.size, r.properties.access))
+            .collect();
+        let read_only = Some(Access::ReadOnly);
+        let read_write = Some(Access::ReadWrite);
+        assert_eq!(vec![("DMA.ISR".to_string(), 0x1000, 16, read_only),
+                        ("DMA.CH0.CCR".to_string(), 0x1008, 8, read_write),
+                        ("DMA.CH0.CNDTRL".to_string(), 0x100c, 8, read_only),
+                        ("DMA.CH0.CNDTRH".to_string(), 0x100d, 8, read_only),
+                        ("DMA.CH1.CCR".to_string(), 0x1018, 8, read_write),
+                        ("DMA.CH1.CNDTRL".to_string(), 0x101c, 8, read_only),
+                        ("DMA.CH1.CNDTRH".to_string(), 0x101d, 8, read_only),
+                        ("GPIOA.IDR".to_string(), 0x3010, 16, None),
+                        ("GPIOB.IDR".to_string(), 0x3110, 16, None)],
+                   registers);
+
+        // Without any size, a register has 32 bits
+        assert_eq!(32, device("").registers().next().unwrap().size);
+    }
+
+    #[test]
+    fn fields() {
+        let d = device("");
+        let fields: Vec<_> = d.fields().map(|f| (f.path, f.register.address)).collect();
+        assert_eq!(vec![("DMA.ISR.GIF1".to_string(), 0x1000),
+                        ("DMA.ISR.TCIF1".to_string(), 0x1000),
+                        ("DMA.CH0.CNDTRL.NDT".to_string(), 0x100c),
+                        ("DMA.CH0.CNDTRH.NDT".to_string(), 0x100d),
+                        ("DMA.CH1.CNDTRL.NDT".to_string(), 0x101c),
+                        ("DMA.CH1.CNDTRH.NDT".to_string(), 0x101d)],
+                   fields);
+    }
+
+    #[test]
+    fn address_overflow() {
+        let xml = "<device><name>TEST</name><version>1.0</version>\
+                   <description>Test</description>\
+                   <addressUnitBits>8</addressUnitBits><width>32</width><peripherals>\
+                   <peripheral><name>TOP</name><baseAddress>0xFFFFFFFFFFFFFFF0</baseAddress>\
+                   <registers>\
+                   <register><name>CR</name><addressOffset>0x8</addressOffset></register>\
+                   <register><name>SR</name><addressOffset>0x20</addressOffset></register>\
+                   <cluster><dim>2</dim><dimIncrement>0x8</dimIncrement>\
+                   <name>CH%s</name><addressOffset>0x4</addressOffset>\
+                   <register><name>CCR</name><addressOffset>0x0</addressOffset></register>\
+                   </cluster></registers></peripheral></peripherals></device>";
+        let d = Device::from_reader(xml.as_bytes()).unwrap();
+        // The instances whose address overflows are skipped
+        let registers: Vec<_> = d.registers().map(|r| (r.path, r.address)).collect();
+        assert_eq!(vec![("TOP.CR".to_string(), 0xFFFF_FFFF_FFFF_FFF8),
+                        ("TOP.CH0.CCR".to_string(), 0xFFFF_FFFF_FFFF_FFF4),
+                        ("TOP.CH1.CCR".to_string(), 0xFFFF_FFFF_FFFF_FFFC)],
+                   registers);
+    }
+}
@@ -1,12 +1,23 @@
 use errors::*;
-use utils::get_child_text;
+use types::parse_integer;
+use utils::{add_child_text, add_optional_child_text, get_child_text, new_element};
+use xml_fragment::XmlFragment;
 use xmltree;
 
+/// The names of the child elements read by `from_element`.
+const ELEMENT_NAMES: &'static [&'static str] = &["name", "description", "value"];
+
+/// The names of the child elements in the order required by the schema, including the ones not
+/// read by `from_element`.
+const SCHEMA_ORDER: &'static [&'static str] = &["name", "description", "value"];
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Interrupt {
     pub name: String,
     pub description: Option<String>,
     pub value: i64,
+    pub unknown_elements: Vec<XmlFragment>,
 }
 
 impl Interrupt {
@@ -15,12 +26,24 @@ impl Interrupt {
         let description = get_child_text(element, "description");
         let value = get_mandatory_child_text!(element, "interrupt", "value");
 
-        let value = try!(value.parse());
+        let unknown_elements = XmlFragment::unknown_children(element, &[ELEMENT_NAMES]);
+
+        let value = try!(parse_integer(&value));
         Ok(Interrupt {
             name: name,
             description: description,
             value: value,
+            unknown_elements: unknown_elements,
         })
 
     }
+
+    pub fn to_element(&self) -> xmltree::Element {
+        let mut element = new_element("interrupt");
+        add_child_text(&mut element, "name", &self.name);
+        add_optional_child_text(&mut element, "description", &self.description);
+        add_child_text(&mut element, "value", self.value);
+        XmlFragment::add_to_element(&mut element, &self.unknown_elements, SCHEMA_ORDER);
+        element
+    }
 }
@@ -1,11 +1,18 @@
-#[derive(Default)]
+use std::fmt;
+
+#[derive(Clone, Debug, Default)]
 pub struct IsSimilarOptions {
     ignore_fields: bool,
+    ignore_register_names: bool,
+    ignore_reset_values: bool,
+    ignore_modified_write_values: bool,
+    allow_register_superset: bool,
+    enumerated_values_by_value: bool,
 }
 
 impl IsSimilarOptions {
     pub fn new() -> IsSimilarOptions {
-        IsSimilarOptions { ignore_fields: false }
+        IsSimilarOptions::default()
     }
 
     pub fn ignore_fields(&self) -> bool {
@@ -15,6 +22,103 @@ impl IsSimilarOptions {
     pub fn set_ignore_fields(&mut self, value: bool) {
         self.ignore_fields = value;
     }
+
+    /// If true, the registers and clusters are compared by their address offset and their
+    /// content only.
+    pub fn ignore_register_names(&self) -> bool {
+        self.ignore_register_names
+    }
+
+    pub fn set_ignore_register_names(&mut self, value: bool) {
+        self.ignore_register_names = value;
+    }
+
+    /// If true, the reset values and reset masks of the registers, clusters and peripherals are not
+    /// compared.
+    pub fn ignore_reset_values(&self) -> bool {
+        self.ignore_reset_values
+    }
+
+    pub fn set_ignore_reset_values(&mut self, value: bool) {
+        self.ignore_reset_values = value;
+    }
+
+    /// If true, the `modifiedWriteValues` of the registers and fields are not compared.
+    pub fn ignore_modified_write_values(&self) -> bool {
+        self.ignore_modified_write_values
+    }
+
+    pub fn set_ignore_modified_write_values(&mut self, value: bool) {
+        self.ignore_modified_write_values = value;
+    }
+
+    /// If true, two peripherals or clusters are similar if each register or cluster of the one
+    /// with the fewest is similar to a register or cluster of the other, in any order.
+    pub fn allow_register_superset(&self) -> bool {
+        self.allow_register_superset
+    }
+
+    pub fn set_allow_register_superset(&mut self, value: bool) {
+        self.allow_register_superset = value;
+    }
+
+    /// If true, the enumerated values are compared by their usage and their set of values, and
+    /// their names and descriptions are ignored.
+    pub fn enumerated_values_by_value(&self) -> bool {
+        self.enumerated_values_by_value
+    }
+
+    pub fn set_enumerated_values_by_value(&mut self, value: bool) {
+        self.enumerated_values_by_value = value;
+    }
+}
+
+/// A property that differs between two elements, as returned by `IsSimilar::explain_differences`.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Difference {
+    /// The dotted path of the element, relative to the compared elements. It is empty for the
+    /// compared elements themselves and for the number of elements of compared sequences.
+    pub path: String,
+    /// The name of the property in the SVD file, like `addressOffset`, or `kind` for a register
+    /// compared to a cluster.
+    pub property: &'static str,
+}
+
+impl Difference {
+    pub fn new<S: Into<String>>(path: S, property: &'static str) -> Difference {
+        Difference {
+            path: path.into(),
+            property: property,
+        }
+    }
+
+    /// Returns the differences of the children of the element `name`, with their paths prefixed
+    /// by `name`. A different number of children becomes a difference of the `property` of the
+    /// element.
+    pub fn nested(name: &str,
+                  property: &'static str,
+                  differences: Vec<Difference>)
+                  -> Vec<Difference> {
+        differences.into_iter()
+            .map(|d| if d.path.is_empty() {
+                Difference::new(name, property)
+            } else if name.is_empty() {
+                d
+            } else {
+                Difference::new(format!("{}.{}", name, d.path), d.property)
+            })
+            .collect()
+    }
+}
+
+impl fmt::Display for Difference {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.path.is_empty() {
+            write!(f, "{}", self.property)
+        } else {
+            write!(f, "{}: {}", self.path, self.property)
+        }
+    }
 }
 
 /// This trait is for implementing a relaxed equality test. For example, it ignores descriptions
@@ -23,6 +127,13 @@ pub trait IsSimilar<T> {
     fn is_similar(self, other: T, options: &IsSimilarOptions) -> bool;
 }
 
+/// This trait explains why two elements are not similar.
+pub trait ExplainDifferences<T>: IsSimilar<T> {
+    /// Returns the properties that make `self` and `other` not similar, an empty list if
+    /// `is_similar` returns true.
+    fn explain_differences(self, other: T, options: &IsSimilarOptions) -> Vec<Difference>;
+}
+
 impl<T1: IntoIterator, T2: IntoIterator> IsSimilar<T2> for T1
     where T1::Item: IsSimilar<T2::Item>
 {
@@ -44,3 +155,381 @@ impl<T1: IntoIterator, T2: IntoIterator> IsSimilar<T2> for T1
         }
     }
 }
+
+impl<T1: IntoIterator, T2: IntoIterator> ExplainDifferences<T2> for T1
+    where T1::Item: ExplainDifferences<T2::Item>
+{
+    /// The elements are compared in order. A different number of elements is reported with an
+    /// empty path and the `count` property.
+    fn explain_differences(self, other: T2, options: &IsSimilarOptions) -> Vec<Difference> {
+        let mut differences = vec![];
+        let mut iter1 = self.into_iter();
+        let mut iter2 = other.into_iter();
+        loop {
+            match (iter1.next(), iter2.next()) {
+                (Some(a), Some(b)) => differences.extend(a.explain_differences(b, options)),
+                (None, None) => return differences,
+                (Some(_), None) | (None, Some(_)) => {
+                    differences.push(Difference::new("", "count"));
+                    return differences;
+                }
+            }
+        }
+    }
+}
+
+/// Returns true if each element of the shortest of `a` and `b` is similar to an element of the
+/// other, in any order.
+pub fn is_similar_subset<'a, T>(a: &'a [T], b: &'a [T], options: &IsSimilarOptions) -> bool
+    where &'a T: IsSimilar<&'a T>
+{
+    let (subset, superset) = if a.len() <= b.len() { (a, b) } else { (b, a) };
+    subset.iter().all(|s| superset.iter().any(|e| s.is_similar(e, options)))
+}
+
+/// Returns the differences of the elements of the shortest of `a` and `b` that are not similar to
+/// any element of the other, each with the element of the other that it is the closest to.
+pub fn explain_subset_differences<'a, T>(a: &'a [T],
+                                         b: &'a [T],
+                                         options: &IsSimilarOptions)
+                                         -> Vec<Difference>
+    where &'a T: ExplainDifferences<&'a T>
+{
+    let (subset, superset) = if a.len() <= b.len() { (a, b) } else { (b, a) };
+    let mut differences = vec![];
+    for s in subset {
+        let closest = superset.iter()
+            .map(|e| s.explain_differences(e, options))
+            .min_by_key(|d| d.len());
+        match closest {
+            Some(closest) => differences.extend(closest),
+            None => {
+                differences.push(Difference::new("", "count"));
+                break;
+            }
+        }
+    }
+    differences
+}
+
+#[cfg(test)]
+mod tests {
+    use access::Access;
+    use cluster::Cluster;
+    use enumerated_values::{EnumeratedValue, EnumeratedValueData, EnumeratedValues};
+    use field::Field;
+    use modified_write_values::ModifiedWriteValues;
+    use peripheral::Peripheral;
+    use register::Register;
+    use register_or_cluster::RegisterOrCluster;
+    use super::*;
+    use xml_fragment::parse_element;
+
+    fn field(name: &str, lsb: u32) -> Field {
+        Field::builder(name, lsb..=lsb).build().unwrap()
+    }
+
+    fn register(name: &str, address_offset: u64) -> Register {
+        Register::builder(name, address_offset).build().unwrap()
+    }
+
+    fn values(values: &[(&str, u64)]) -> EnumeratedValues {
+        let values = values.iter()
+            .map(|&(name, value)| {
+                EnumeratedValue {
+                    name: name.to_string(),
+                    description: None,
+                    value: EnumeratedValueData::Value {
+                        value: value,
+                        do_not_care: 0,
+                    },
+                    unknown_elements: vec![],
+                }
+            })
+            .collect();
+        EnumeratedValues::new(None, None, values).unwrap()
+    }
+
+    fn options<F: FnOnce(&mut IsSimilarOptions)>(set: F) -> IsSimilarOptions {
+        let mut options = IsSimilarOptions::new();
+        set(&mut options);
+        options
+    }
+
+    fn strings(differences: &[Difference]) -> Vec<String> {
+        differences.iter().map(|d| d.to_string()).collect()
+    }
+
+    #[test]
+    fn nested() {
+        let differences = vec![Difference::new("", "count"),
+                               Difference::new("CR1", "access"),
+                               Difference::new("CR1.CEN", "bitRange")];
+        assert_eq!(vec!["TIM2: registers", "TIM2.CR1: access", "TIM2.CR1.CEN: bitRange"],
+                   strings(&Difference::nested("TIM2", "registers", differences.clone())));
+        assert_eq!(vec!["registers", "CR1: access", "CR1.CEN: bitRange"],
+                   strings(&Difference::nested("", "registers", differences)));
+    }
+
+    #[test]
+    fn sequences() {
+        let options = IsSimilarOptions::new();
+        let a = [field("A", 0), field("B", 1)];
+        let b = [field("A", 0), field("B", 2)];
+        assert!(a.is_similar(&a, &options));
+        assert!(a.explain_differences(&a, &options).is_empty());
+        assert!(!a.is_similar(&b, &options));
+        assert_eq!(vec!["B: bitRange"], strings(&a.explain_differences(&b, &options)));
+        // The elements are compared in order, and the extra elements are not explained
+        assert_eq!(vec!["A: name", "count"],
+                   strings(&a.explain_differences(&[field("B", 0)], &options)));
+        assert!(!a.is_similar(&a[..1], &options));
+        assert_eq!(vec!["count"], strings(&a[..0].explain_differences(&a, &options)));
+    }
+
+    #[test]
+    fn subsets() {
+        let options = IsSimilarOptions::new();
+        let a = [field("A", 0), field("B", 1), field("C", 2)];
+        let b = [field("C", 2), field("A", 0)];
+        assert!(is_similar_subset(&a, &b, &options));
+        assert!(is_similar_subset(&b, &a, &options));
+        assert!(is_similar_subset(&a[..0], &a, &options));
+        assert!(explain_subset_differences(&b, &a, &options).is_empty());
+
+        // D is compared with C, the closest field
+        let c = [field("A", 0), field("D", 2)];
+        assert!(!is_similar_subset(&a, &c, &options));
+        assert_eq!(vec!["D: name"], strings(&explain_subset_differences(&a, &c, &options)));
+    }
+
+    #[test]
+    fn peripherals() {
+        let timer = |name: &str, base_address, arr_access, ckd_msb| {
+            Peripheral::builder(name, base_address)
+                .register(Register::builder("CR1", 0x0)
+                    .field(field("CEN", 0))
+                    .field(Field::builder("CKD", 8..=ckd_msb).build().unwrap())
+                    .build()
+                    .unwrap())
+                .register(Register::builder("ARR", 0x2c).access(arr_access).build().unwrap())
+                .build()
+                .unwrap()
+        };
+        let tim2 = timer("TIM2", 0x4000_0000, Access::ReadWrite, 9);
+        let tim3 = timer("TIM3", 0x4000_0400, Access::ReadWrite, 9);
+        let mut tim5 = timer("TIM5", 0x4000_0c00, Access::ReadOnly, 10);
+        tim5.registers
+            .as_mut()
+            .unwrap()
+            .push(RegisterOrCluster::Register(Register::builder("CCR1", 0x34).build().unwrap()));
+
+        // The names and base addresses of the peripherals are not compared
+        let options = IsSimilarOptions::new();
+        assert!(tim2.is_similar(&tim3, &options));
+        assert!(tim2.explain_differences(&tim3, &options).is_empty());
+        assert!(!tim2.is_similar(&tim5, &options));
+        assert_eq!(vec!["CR1.CKD: bitRange", "ARR: access", "registers"],
+                   strings(&tim2.explain_differences(&tim5, &options)));
+        assert_eq!(strings(&tim2.explain_differences(&tim5, &options)),
+                   strings(&tim5.explain_differences(&tim2, &options)));
+    }
+
+    #[test]
+    fn ignore_fields() {
+        let cr = |msb| Register::builder("CR", 0x0).field(field("EN", 0)).field(field("MODE", msb));
+        let a = cr(1).build().unwrap();
+        let b = cr(2).build().unwrap();
+        let c = cr(2).field(field("START", 3)).build().unwrap();
+        let d = Register::builder("CR", 0x0).access(Access::ReadOnly).build().unwrap();
+
+        let default = IsSimilarOptions::new();
+        assert_eq!(vec!["CR.MODE: bitRange", "CR: fields"],
+                   strings(&a.explain_differences(&c, &default)));
+        assert!(!a.is_similar(&b, &default));
+        let options = options(|o| o.set_ignore_fields(true));
+        for other in &[&b, &c] {
+            assert!(a.is_similar(other, &options));
+            assert!(a.explain_differences(other, &options).is_empty());
+        }
+        // The other properties of the registers are still compared
+        assert_eq!(vec!["CR: access"], strings(&a.explain_differences(&d, &options)));
+    }
+
+    #[test]
+    fn ignore_register_names() {
+        let sr = register("SR", 0x0);
+        let isr = register("ISR", 0x0);
+        let cluster = |name: &str, register: &str| {
+            let xml = format!("<cluster><name>{}</name><addressOffset>0x10</addressOffset>\
+                               <register><name>{}</name><addressOffset>0x0</addressOffset>\
+                               </register></cluster>",
+                              name,
+                              register);
+            Cluster::from_element(&parse_element(xml.as_bytes()).unwrap()).unwrap()
+        };
+
+        let default = IsSimilarOptions::new();
+        assert_eq!(vec!["SR: name"], strings(&sr.explain_differences(&isr, &default)));
+        assert_eq!(vec!["CH1: name", "CH1.CCR: name"],
+                   strings(&cluster("CH1", "CCR").explain_differences(&cluster("CH2", "CMR"),
+                                                                      &default)));
+        let options = options(|o| o.set_ignore_register_names(true));
+        assert!(sr.is_similar(&isr, &options));
+        assert!(cluster("CH1", "CCR").is_similar(&cluster("CH2", "CMR"), &options));
+        // The registers are still compared by address offset
+        assert_eq!(vec!["SR: addressOffset"],
+                   strings(&sr.explain_differences(&register("ISR", 0x4), &options)));
+    }
+
+    #[test]
+    fn ignore_reset_values() {
+        let cr = |reset_value, reset_mask| {
+            Register::builder("CR", 0x0)
+                .reset_value(reset_value)
+                .reset_mask(reset_mask)
+                .build()
+                .unwrap()
+        };
+        let cluster = |reset_value: &str| {
+            let xml = format!("<cluster><name>CH</name><addressOffset>0x10</addressOffset>\
+                               <resetValue>{}</resetValue></cluster>",
+                              reset_value);
+            Cluster::from_element(&parse_element(xml.as_bytes()).unwrap()).unwrap()
+        };
+        let tim = |name: &str, reset_value| {
+            Peripheral::builder(name, 0x4000_0000)
+                .register(cr(reset_value, 0xffff))
+                .build()
+                .unwrap()
+        };
+
+        let default = IsSimilarOptions::new();
+        assert!(cr(0x0, 0xffff).is_similar(&cr(0x0, 0xffff), &default));
+        assert_eq!(vec!["CR: resetValue"],
+                   strings(&cr(0x0, 0xffff).explain_differences(&cr(0x1, 0xffff), &default)));
+        assert_eq!(vec!["CR: resetValue", "CR: resetMask"],
+                   strings(&cr(0x0, 0xffff).explain_differences(&cr(0x1, 0xff), &default)));
+        assert_eq!(vec!["CH: resetValue"],
+                   strings(&cluster("0x0").explain_differences(&cluster("0x1"), &default)));
+        assert_eq!(vec!["CR: resetValue"],
+                   strings(&tim("TIM2", 0x0).explain_differences(&tim("TIM3", 0x1), &default)));
+
+        let options = options(|o| o.set_ignore_reset_values(true));
+        assert!(cr(0x0, 0xffff).is_similar(&cr(0x1, 0xff), &options));
+        assert!(cluster("0x0").is_similar(&cluster("0x1"), &options));
+        assert!(cluster("0x0").explain_differences(&cluster("0x1"), &options).is_empty());
+        assert!(tim("TIM2", 0x0).is_similar(&tim("TIM3", 0x1), &options));
+        // The other properties are still compared
+        let cr16 = Register::builder("CR", 0x0).size(16).build().unwrap();
+        assert_eq!(vec!["CR: size"],
+                   strings(&cr(0x0, 0xffff).explain_differences(&cr16, &options)));
+    }
+
+    #[test]
+    fn ignore_modified_write_values() {
+        let sr = |register_mwv, field_mwv| {
+            Register::builder("SR", 0x0)
+                .modified_write_values(register_mwv)
+                .field(Field::builder("TC", 6..=6)
+                    .modified_write_values(field_mwv)
+                    .build()
+                    .unwrap())
+                .build()
+                .unwrap()
+        };
+        let a = sr(ModifiedWriteValues::OneToClear, ModifiedWriteValues::OneToClear);
+        let b = sr(ModifiedWriteValues::ZeroToClear, ModifiedWriteValues::ZeroToClear);
+
+        let default = IsSimilarOptions::new();
+        assert_eq!(vec!["SR: modifiedWriteValues", "SR.TC: modifiedWriteValues"],
+                   strings(&a.explain_differences(&b, &default)));
+        let options = options(|o| o.set_ignore_modified_write_values(true));
+        assert!(a.is_similar(&b, &options));
+        assert!(a.explain_differences(&b, &options).is_empty());
+    }
+
+    #[test]
+    fn enumerated_values_by_value() {
+        let parity = values(&[("Even", 0), ("Odd", 1)]);
+        let renamed = values(&[("ODD", 1), ("EVEN", 0)]);
+        let other = values(&[("Even", 0), ("Odd", 2)]);
+        let fewer = values(&[("Even", 0)]);
+
+        let default = IsSimilarOptions::new();
+        assert!(parity.is_similar(&parity, &default));
+        assert!(!parity.is_similar(&renamed, &default));
+        assert_eq!(vec!["enumeratedValues"],
+                   strings(&parity.explain_differences(&renamed, &default)));
+        let options = options(|o| o.set_enumerated_values_by_value(true));
+        assert!(parity.is_similar(&renamed, &options));
+        assert!(parity.explain_differences(&renamed, &options).is_empty());
+        assert!(!parity.is_similar(&other, &options));
+        assert!(!parity.is_similar(&fewer, &options));
+
+        let ps = |values| Field::builder("PS", 9..=9).enumerated_values(values).build().unwrap();
+        assert_eq!(vec!["PS: enumeratedValues"],
+                   strings(&ps(parity.clone()).explain_differences(&ps(other), &options)));
+        assert!(ps(parity).is_similar(&ps(renamed), &options));
+    }
+
+    #[test]
+    fn allow_register_superset() {
+        let uart = |registers: &[(&str, u64)]| {
+            let mut peripheral = Peripheral::builder("UART", 0x4001_1000).build().unwrap();
+            peripheral.registers = Some(registers.iter()
+                .map(|&(name, offset)| RegisterOrCluster::Register(register(name, offset)))
+                .collect());
+            peripheral
+        };
+        let usart = uart(&[("SR", 0x0), ("DR", 0x4), ("CR1", 0xc), ("GTPR", 0x18)]);
+        let uart4 = uart(&[("CR1", 0xc), ("SR", 0x0)]);
+        let uart5 = uart(&[("SR", 0x0), ("CR1", 0x10)]);
+
+        let default = IsSimilarOptions::new();
+        assert!(!usart.is_similar(&uart4, &default));
+        let options = options(|o| o.set_allow_register_superset(true));
+        assert!(usart.is_similar(&uart4, &options));
+        assert!(uart4.is_similar(&usart, &options));
+        assert!(uart4.explain_differences(&usart, &options).is_empty());
+        assert!(!usart.is_similar(&uart5, &options));
+        assert_eq!(vec!["CR1: addressOffset"],
+                   strings(&uart5.explain_differences(&usart, &options)));
+    }
+
+    #[test]
+    fn combined_options() {
+        let uart = |name: &str, sr: &str, parity: &[(&str, u64)], mwv, cr2| {
+            let ps = Field::builder("PS", 9..=9).enumerated_values(values(parity)).build().unwrap();
+            let mut builder = Peripheral::builder(name, 0x4001_1000)
+                .register(Register::builder(sr, 0x0)
+                    .modified_write_values(mwv)
+                    .build()
+                    .unwrap())
+                .register(Register::builder("CR1", 0xc).field(ps).build().unwrap());
+            if cr2 {
+                builder = builder.register(register("CR2", 0x10));
+            }
+            builder.build().unwrap()
+        };
+        let usart1 = uart("USART1",
+                          "SR",
+                          &[("Even", 0), ("Odd", 1)],
+                          ModifiedWriteValues::OneToClear,
+                          true);
+        let usart2 = uart("USART2",
+                          "ISR",
+                          &[("EVEN", 0), ("ODD", 1)],
+                          ModifiedWriteValues::ZeroToClear,
+                          false);
+
+        let mut options = IsSimilarOptions::new();
+        options.set_ignore_register_names(true);
+        options.set_ignore_modified_write_values(true);
+        options.set_enumerated_values_by_value(true);
+        assert_eq!(vec!["registers"], strings(&usart1.explain_differences(&usart2, &options)));
+        options.set_allow_register_superset(true);
+        assert!(usart1.is_similar(&usart2, &options));
+        assert!(usart2.explain_differences(&usart1, &options).is_empty());
+    }
+}
@@ -0,0 +1,337 @@
+//! Support for parsing SVD files that don't strictly follow the schema.
+//!
+//! Before being parsed, the XML tree is fixed using the following fallbacks. Each applied
+//! fallback produces a `Warning`:
+//!
+//! * leading and trailing whitespace is removed from every value except descriptions,
+//! * an unknown `access`, `protection`, `modifiedWriteValues`, `readAction`, `dataType` or
+//!   enumerated values `usage` is ignored, as if it was absent,
+//! * an unknown address block `usage` is replaced by `registers`,
+//! * an `enumeratedValues` without any `enumeratedValue` is ignored,
+//! * a missing device `version` or `description` is replaced by an empty string,
+//! * a missing device `addressUnitBits` is replaced by 8 and a missing `width` by 32.
+//!
+//! Problems without a fallback are still reported as errors.
+
+use access::Access;
+use address_block::Usage;
+use data_type::DataType;
+use enumerated_values::EnumUsage;
+use modified_write_values::ModifiedWriteValues;
+use protection::Protection;
+use read_action::ReadAction;
+use std::collections::HashMap;
+use std::fmt;
+use std::mem;
+use utils::{add_child_text, element_path_segment};
+use xmltree;
+
+/// The elements that can appear several times in their parent and are identified by their name
+/// or index in element paths.
+const LIST_ELEMENTS: &'static [&'static str] = &["peripheral",
+                                                 "addressBlock",
+                                                 "interrupt",
+                                                 "register",
+                                                 "cluster",
+                                                 "field",
+                                                 "enumeratedValues",
+                                                 "enumeratedValue"];
+
+const DEVICE_DEFAULTS: &'static [(&'static str, &'static str)] = &[("version", ""),
+                                                                    ("description", ""),
+                                                                    ("addressUnitBits", "8"),
+                                                                    ("width", "32")];
+
+/// A recoverable problem found while parsing in lenient mode.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Warning {
+    /// The path of the element where the problem was found.
+    pub path: String,
+    /// What was wrong and which fallback was applied.
+    pub message: String,
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.path, self.message)
+    }
+}
+
+/// Applies the fallbacks to the `device` element and its descendants.
+pub fn sanitize(device: &mut xmltree::Element) -> Vec<Warning> {
+    let mut warnings = vec![];
+    let path = device.name.clone();
+    sanitize_element(device, &path, &mut warnings);
+    warnings
+}
+
+fn sanitize_element(element: &mut xmltree::Element, path: &str, warnings: &mut Vec<Warning>) {
+    let children = mem::replace(&mut element.children, vec![]);
+    let mut indices = HashMap::new();
+    for mut child in children {
+        let index = {
+            let index = indices.entry(child.name.clone()).or_insert(0);
+            *index += 1;
+            *index - 1
+        };
+        let child_path = if LIST_ELEMENTS.contains(&&*child.name) {
+            format!("{}/{}", path, element_path_segment(&child, index))
+        } else {
+            format!("{}/{}", path, child.name)
+        };
+
+        if child.name == "vendorExtensions" {
+            // The content of vendor extensions is not interpreted.
+        } else if child.children.is_empty() && child.name != "enumeratedValues" {
+            if !sanitize_value(&element.name, &mut child, &child_path, warnings) {
+                continue;
+            }
+        } else {
+            sanitize_element(&mut child, &child_path, warnings);
+            if child.name == "enumeratedValues" &&
+               child.get_child("enumeratedValue").is_none() {
+                warnings.push(Warning {
+                    path: child_path,
+                    message: "no 'enumeratedValue', ignored".to_string(),
+                });
+                continue;
+            }
+        }
+        element.children.push(child);
+    }
+
+    if element.name == "device" {
+        for &(name, default) in DEVICE_DEFAULTS {
+            if element.get_child(name).is_none() {
+                warnings.push(Warning {
+                    path: path.to_string(),
+                    message: format!("missing '{}', using '{}'", name, default),
+                });
+                add_child_text(element, name, default);
+            }
+        }
+    }
+}
+
+/// Fixes the value of an element without children. Returns false if the element must be ignored.
+fn sanitize_value(parent_name: &str,
+                  element: &mut xmltree::Element,
+                  path: &str,
+                  warnings: &mut Vec<Warning>)
+                  -> bool {
+    if element.name != "description" && element.name != "licenseText" {
+        if let Some(ref mut text) = element.text {
+            let trimmed = text.trim().to_string();
+            if trimmed != *text {
+                warnings.push(Warning {
+                    path: path.to_string(),
+                    message: "leading or trailing whitespace removed".to_string(),
+                });
+                *text = trimmed;
+            }
+        }
+    }
+
+    let value = element.text.clone().unwrap_or_else(String::new);
+    let is_valid = match (parent_name, &*element.name) {
+        ("addressBlock", "usage") => {
+            if value.parse::<Usage>().is_err() {
+                warnings.push(Warning {
+                    path: path.to_string(),
+                    message: format!("unknown value '{}', using 'registers'", value),
+                });
+                element.text = Some("registers".to_string());
+            }
+            true
+        }
+        ("enumeratedValues", "usage") => value.parse::<EnumUsage>().is_ok(),
+        (_, "access") => value.parse::<Access>().is_ok(),
+        (_, "protection") => value.parse::<Protection>().is_ok(),
+        (_, "modifiedWriteValues") => value.parse::<ModifiedWriteValues>().is_ok(),
+        (_, "readAction") => value.parse::<ReadAction>().is_ok(),
+        (_, "dataType") => value.parse::<DataType>().is_ok(),
+        _ => true,
+    };
+
+    if !is_valid {
+        warnings.push(Warning {
+            path: path.to_string(),
+            message: format!("unknown value '{}', ignored", value),
+        });
+    }
+
+    is_valid
+}
+
+#[cfg(test)]
+mod tests {
+    use access::Access;
+    use address_block::Usage;
+    use device::Device;
+    use register::Register;
+    use register_or_cluster::RegisterOrCluster;
+
+    const NON_CONFORMING: &'static str = r#"
+        <device>
+            <name>TEST</name>
+            <version>1.0</version>
+            <addressUnitBits>8</addressUnitBits>
+            <width>32</width>
+            <peripherals>
+                <peripheral>
+                    <name>TIM2</name>
+                    <baseAddress>0x40000000</baseAddress>
+                    <addressBlock>
+                        <offset>0</offset>
+                        <size>0x400</size>
+                        <usage>regs</usage>
+                    </addressBlock>
+                    <registers>
+                        <register>
+                            <name>CR1</name>
+                            <addressOffset>0x0</addressOffset>
+                            <access>read-write </access>
+                        </register>
+                    </registers>
+                </peripheral>
+            </peripherals>
+        </device>"#;
+
+    /// Returns a device with a TIM2 peripheral whose registers are `registers`.
+    fn device(registers: &str) -> String {
+        format!("<device><name>TEST</name><version>1.0</version>\
+                 <description>Test</description><addressUnitBits>8</addressUnitBits>\
+                 <width>32</width><peripherals><peripheral><name>TIM2</name>\
+                 <baseAddress>0x40000000</baseAddress><registers>{}</registers></peripheral>\
+                 </peripherals></device>",
+                registers)
+    }
+
+    /// Parses `xml` in lenient mode, and returns the device and the warnings as strings.
+    fn lenient(xml: &str) -> (Device, Vec<String>) {
+        let (d, warnings) = Device::from_reader_lenient(xml.as_bytes()).unwrap();
+        (d, warnings.iter().map(|w| w.to_string()).collect())
+    }
+
+    fn register(d: &Device) -> &Register {
+        match d.peripherals[0].registers.as_ref().unwrap()[0] {
+            RegisterOrCluster::Register(ref r) => r,
+            RegisterOrCluster::Cluster(_) => panic!("expected a register"),
+        }
+    }
+
+    #[test]
+    fn fallbacks() {
+        assert!(Device::from_reader(NON_CONFORMING.as_bytes()).is_err());
+
+        let (d, warnings) = Device::from_reader_lenient(NON_CONFORMING.as_bytes()).unwrap();
+        assert_eq!("", d.description);
+        assert_eq!(Some(Access::ReadWrite), register(&d).register_properties.access);
+        assert_eq!(Usage::Registers, d.peripherals[0].address_blocks[0].usage);
+
+        let paths: Vec<_> = warnings.iter().map(|w| &*w.path).collect();
+        assert_eq!(vec!["device/peripherals/peripheral[TIM2]/addressBlock[0]/usage",
+                        "device/peripherals/peripheral[TIM2]/registers/register[CR1]/access",
+                        "device"],
+                   paths);
+    }
+
+    #[test]
+    fn whitespace() {
+        let xml = device("<register><name> CR1\n</name><description> Control  </description>\
+                          <addressOffset>0x4 </addressOffset></register>");
+        let strict = Device::from_reader(xml.as_bytes()).unwrap();
+        assert_eq!(" CR1\n", register(&strict).name);
+
+        let (d, warnings) = lenient(&xml);
+        let r = register(&d);
+        assert_eq!("CR1", r.name);
+        assert_eq!(0x4, r.address_offset.0);
+        // The descriptions are left untouched
+        assert_eq!(Some(" Control  ".to_string()), r.description);
+        let register = "device/peripherals/peripheral[TIM2]/registers/register[CR1]";
+        assert_eq!(vec![format!("{}/name: leading or trailing whitespace removed", register),
+                        format!("{}/addressOffset: leading or trailing whitespace removed",
+                                register)],
+                   warnings);
+    }
+
+    #[test]
+    fn unknown_values() {
+        let xml = device("<register><name>CR1</name><addressOffset>0x0</addressOffset>\
+                          <access>rw</access><protection>x</protection>\
+                          <dataType>uint128_t</dataType>\
+                          <modifiedWriteValues>toggle</modifiedWriteValues>\
+                          <readAction>erase</readAction>\
+                          <fields><field><name>EN</name><bitRange>[0:0]</bitRange>\
+                          <enumeratedValues><usage>sometimes</usage><enumeratedValue>\
+                          <name>ON</name><value>1</value></enumeratedValue>\
+                          </enumeratedValues></field></fields></register>");
+        assert!(Device::from_reader(xml.as_bytes()).is_err());
+
+        let (d, warnings) = lenient(&xml);
+        let r = register(&d);
+        assert_eq!(None, r.register_properties.access);
+        assert_eq!(None, r.register_properties.protection);
+        assert_eq!(None, r.data_type);
+        assert_eq!(None, r.modified_write_values);
+        assert_eq!(None, r.read_action);
+        let en = &r.fields.as_ref().unwrap()[0];
+        assert_eq!(None, en.enumerated_values[0].usage());
+        assert_eq!("ON", en.enumerated_values[0].enumerated_values()[0].name);
+
+        let register = "device/peripherals/peripheral[TIM2]/registers/register[CR1]";
+        let expected: Vec<_> = ["access: unknown value 'rw', ignored",
+                                "protection: unknown value 'x', ignored",
+                                "dataType: unknown value 'uint128_t', ignored",
+                                "modifiedWriteValues: unknown value 'toggle', ignored",
+                                "readAction: unknown value 'erase', ignored",
+                                "fields/field[EN]/enumeratedValues[0]/usage: unknown value \
+                                 'sometimes', ignored"]
+            .iter()
+            .map(|w| format!("{}/{}", register, w))
+            .collect();
+        assert_eq!(expected, warnings);
+    }
+
+    #[test]
+    fn empty_enumerated_values() {
+        let xml = device("<register><name>CR1</name><addressOffset>0x0</addressOffset>\
+                          <fields><field><name>EN</name><bitRange>[0:0]</bitRange>\
+                          <enumeratedValues/>\
+                          <enumeratedValues><name>MODE</name></enumeratedValues>\
+                          </field></fields></register>");
+        assert!(Device::from_reader(xml.as_bytes()).is_err());
+
+        let (d, warnings) = lenient(&xml);
+        assert!(register(&d).fields.as_ref().unwrap()[0].enumerated_values.is_empty());
+        let field = "device/peripherals/peripheral[TIM2]/registers/register[CR1]/fields/field[EN]";
+        assert_eq!(vec![format!("{}/enumeratedValues[0]: no 'enumeratedValue', ignored", field),
+                        format!("{}/enumeratedValues[MODE]: no 'enumeratedValue', ignored",
+                                field)],
+                   warnings);
+    }
+
+    #[test]
+    fn device_defaults() {
+        let xml = "<device><name>TEST</name><peripherals><peripheral><name>TIM2</name>\
+                   <baseAddress>0x40000000</baseAddress></peripheral></peripherals></device>";
+        assert!(Device::from_reader(xml.as_bytes()).is_err());
+
+        let (d, warnings) = lenient(xml);
+        assert_eq!("", d.version);
+        assert_eq!("", d.description);
+        assert_eq!(8, d.address_unit_bits.0);
+        assert_eq!(32, d.width.0);
+        assert_eq!(vec!["device: missing 'version', using ''",
+                        "device: missing 'description', using ''",
+                        "device: missing 'addressUnitBits', using '8'",
+                        "device: missing 'width', using '32'"],
+                   warnings);
+
+        // The values that are present are kept
+        let (d, warnings) = lenient(&device("").replace("<width>32</width>", "<width>16</width>"));
+        assert_eq!(16, d.width.0);
+        assert!(warnings.is_empty());
+    }
+}
@@ -1,5 +1,11 @@
 #[macro_use]
 extern crate error_chain;
+#[cfg(feature = "serde")]
+#[macro_use]
+extern crate serde;
+#[cfg(all(test, feature = "serde"))]
+extern crate serde_json;
+extern crate xml;
 extern crate xmltree;
 
 #[macro_use]
@@ -9,16 +15,20 @@ mod utils;
 
 pub mod access;
 pub mod address_block;
+pub mod address_lookup;
 pub mod bit_range;
 pub mod cluster;
 pub mod cpu;
 pub mod data_type;
 pub mod device;
+pub mod diff;
 pub mod dim_element_group;
 pub mod enumerated_values;
 pub mod errors;
 pub mod field;
+pub mod instances;
 pub mod interrupt;
+pub mod lenient;
 pub mod modified_write_values;
 pub mod peripheral;
 pub mod protection;
@@ -26,7 +36,12 @@ pub mod read_action;
 pub mod register;
 pub mod register_or_cluster;
 pub mod register_properties_group;
+pub mod resolve;
 pub mod types;
+pub mod validate;
+pub mod visit;
+pub mod writer;
+pub mod xml_fragment;
 
 pub mod codegen;
 pub mod is_similar;
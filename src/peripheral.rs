@@ -1,18 +1,46 @@
-use address_block::AddresBlock;
-use dim_element_group::DimElementGroup;
+use address_block::{AddresBlock, Usage};
+use cluster::Cluster;
+use dim_element_group::{self, DimElementGroup};
 use errors::*;
 use interrupt::Interrupt;
-use is_similar::{IsSimilar, IsSimilarOptions};
+use is_similar::{Difference, ExplainDifferences, IsSimilar, IsSimilarOptions,
+                 explain_subset_differences, is_similar_subset};
+use register::Register;
 use register_or_cluster::RegisterOrCluster;
-use register_properties_group::RegisterPropertiesGroup;
-use std::collections::HashSet;
+use register_properties_group::{self, RegisterPropertiesGroup};
+use std::collections::{HashMap, HashSet};
 use types::*;
-use utils::{extract_prefix, get_child_text};
+use utils::{add_child_text, add_optional_child_text, element_path_segment, extract_prefix,
+            get_child_text, new_element};
+use xml_fragment::XmlFragment;
 use xmltree;
 
+/// The names of the child elements read by `from_element`.
+const ELEMENT_NAMES: &'static [&'static str] = &["name", "version", "description",
+                                                 "alternatePeripheral", "groupName",
+                                                 "prependToName", "appendToName",
+                                                 "headerStructName", "disableCondition",
+                                                 "baseAddress", "addressBlock", "interrupt",
+                                                 "registers", "vendorExtensions"];
+
+/// The names of the child elements in the order required by the schema, including the ones not
+/// read by `from_element`.
+const SCHEMA_ORDER: &'static [&'static str] = &["dim", "dimIncrement", "dimIndex", "dimName",
+                                                "dimArrayIndex", "name", "version", "description",
+                                                "alternatePeripheral", "groupName", "prependToName",
+                                                "appendToName", "headerStructName",
+                                                "disableCondition", "baseAddress", "size", "access",
+                                                "protection", "resetValue", "resetMask",
+                                                "addressBlock", "interrupt", "registers",
+                                                "vendorExtensions"];
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Peripheral {
     pub derived_from: Option<IdentifierType>,
+    /// The `derivedFrom` of the peripheral in the SVD file, kept when `Device::from_reader`
+    /// propagates and clears `derived_from`.
+    pub original_derived_from: Option<IdentifierType>,
     pub dim_element: DimElementGroup,
     pub name: IdentifierType,
     pub version: Option<String>,
@@ -28,13 +56,42 @@ pub struct Peripheral {
     pub address_blocks: Vec<AddresBlock>,
     pub interrupts: Vec<Interrupt>,
     pub registers: Option<Vec<RegisterOrCluster>>,
+    pub vendor_extensions: Option<XmlFragment>,
+    pub unknown_elements: Vec<XmlFragment>,
 }
 
 impl Peripheral {
+    /// Returns a builder for a peripheral named `name` at the address `base_address`.
+    pub fn builder<S: Into<String>>(name: S, base_address: u64) -> PeripheralBuilder {
+        PeripheralBuilder {
+            peripheral: Peripheral {
+                derived_from: None,
+                original_derived_from: None,
+                dim_element: DimElementGroup::default(),
+                name: name.into(),
+                version: None,
+                description: None,
+                alternate_peripheral: None,
+                group_name: None,
+                prepend_to_name: None,
+                append_to_name: None,
+                header_struct_name: None,
+                disable_condition: None,
+                base_address: ScaledNonNegativeInteger(base_address),
+                register_properties: RegisterPropertiesGroup::default(),
+                address_blocks: vec![],
+                interrupts: vec![],
+                registers: None,
+                vendor_extensions: None,
+                unknown_elements: vec![],
+            },
+        }
+    }
+
     pub fn from_element(element: &xmltree::Element) -> Result<Peripheral> {
         let derived_from = element.attributes.get("derivedFrom").cloned();
         let dim_element = try!(DimElementGroup::from_element(element));
-        let name = get_mandatory_child_text!(element, "perpheral", "name");
+        let name = get_mandatory_child_text!(element, "peripheral", "name");
         let version = get_child_text(element, "version");
         let description = get_child_text(element, "description");
         let alternate_peripheral = get_child_text(element, "alternatePeripheral");
@@ -43,33 +100,48 @@ impl Peripheral {
         let append_to_name = get_child_text(element, "appendToName");
         let header_struct_name = get_child_text(element, "headerStructName");
         let disable_condition = get_child_text(element, "disableCondition");
-        let base_address = get_mandatory_child_text!(element, "perpheral", "baseAddress");
+        let base_address = get_mandatory_child_text!(element, "peripheral", "baseAddress");
         let register_properties = try!(RegisterPropertiesGroup::from_element(element));
         let address_blocks: Result<Vec<_>> = element.children
             .iter()
             .filter(|e| e.name == "addressBlock")
-            .map(AddresBlock::from_element)
+            .enumerate()
+            .map(|(i, e)| AddresBlock::from_element(e).in_element(element_path_segment(e, i)))
             .collect();
         let address_blocks = try!(address_blocks);
         let interrupts: Result<Vec<_>> = element.children
             .iter()
             .filter(|e| e.name == "interrupt")
-            .map(Interrupt::from_element)
+            .enumerate()
+            .map(|(i, e)| Interrupt::from_element(e).in_element(element_path_segment(e, i)))
             .collect();
         let interrupts = try!(interrupts);
         let registers = match element.get_child("registers") {
             Some(registers) => {
-                let registers: Result<Vec<_>> =
-                    registers.children.iter().map(RegisterOrCluster::from_element).collect();
+                let registers: Result<Vec<_>> = registers.children
+                    .iter()
+                    .enumerate()
+                    .map(|(i, e)| {
+                        RegisterOrCluster::from_element(e)
+                            .in_element(format!("registers/{}", element_path_segment(e, i)))
+                    })
+                    .collect();
                 let registers = try!(registers);
                 Some(registers)
             }
             None => None,
         };
 
+        let vendor_extensions = XmlFragment::from_child(element, "vendorExtensions");
+        let known_names = [ELEMENT_NAMES,
+                           dim_element_group::ELEMENT_NAMES,
+                           register_properties_group::ELEMENT_NAMES];
+        let unknown_elements = XmlFragment::unknown_children(element, &known_names);
+
         let base_address = try!(base_address.parse());
 
         Ok(Peripheral {
+            original_derived_from: derived_from.clone(),
             derived_from: derived_from,
             dim_element: dim_element,
             name: name,
@@ -86,9 +158,53 @@ impl Peripheral {
             address_blocks: address_blocks,
             interrupts: interrupts,
             registers: registers,
+            vendor_extensions: vendor_extensions,
+            unknown_elements: unknown_elements,
         })
     }
 
+    pub fn to_element(&self) -> xmltree::Element {
+        let mut element = new_element("peripheral");
+        // A propagated peripheral keeps the `derivedFrom` of the SVD file, it has no effect since
+        // it has all the elements of the peripheral it derives from
+        let derived_from = self.derived_from.as_ref().or(self.original_derived_from.as_ref());
+        if let Some(derived_from) = derived_from {
+            element.attributes.insert("derivedFrom".to_string(), derived_from.clone());
+        }
+        self.dim_element.add_to_element(&mut element);
+        add_child_text(&mut element, "name", &self.name);
+        add_optional_child_text(&mut element, "version", &self.version);
+        add_optional_child_text(&mut element, "description", &self.description);
+        add_optional_child_text(&mut element, "alternatePeripheral", &self.alternate_peripheral);
+        add_optional_child_text(&mut element, "groupName", &self.group_name);
+        add_optional_child_text(&mut element, "prependToName", &self.prepend_to_name);
+        add_optional_child_text(&mut element, "appendToName", &self.append_to_name);
+        add_optional_child_text(&mut element, "headerStructName", &self.header_struct_name);
+        add_optional_child_text(&mut element, "disableCondition", &self.disable_condition);
+        add_child_text(&mut element,
+                       "baseAddress",
+                       format!("0x{:08X}", self.base_address));
+        self.register_properties.add_to_element(&mut element);
+        for address_block in &self.address_blocks {
+            element.children.push(address_block.to_element());
+        }
+        for interrupt in &self.interrupts {
+            element.children.push(interrupt.to_element());
+        }
+        if let Some(ref registers) = self.registers {
+            let mut registers_element = new_element("registers");
+            for r_or_c in registers {
+                registers_element.children.push(r_or_c.to_element());
+            }
+            element.children.push(registers_element);
+        }
+        XmlFragment::add_to_element(&mut element, &self.unknown_elements, SCHEMA_ORDER);
+        if let Some(ref vendor_extensions) = self.vendor_extensions {
+            element.children.push(vendor_extensions.0.clone());
+        }
+        element
+    }
+
     pub fn propagate_register_properties(&mut self,
                                          register_properties: &RegisterPropertiesGroup) {
         self.register_properties = self.register_properties.merge(register_properties);
@@ -146,9 +262,142 @@ impl Peripheral {
     }
 }
 
+/// A builder for a `Peripheral`, returned by `Peripheral::builder`.
+#[derive(Clone, Debug)]
+pub struct PeripheralBuilder {
+    peripheral: Peripheral,
+}
+
+impl PeripheralBuilder {
+    pub fn derived_from<S: Into<String>>(mut self, derived_from: S) -> PeripheralBuilder {
+        let derived_from = derived_from.into();
+        self.peripheral.original_derived_from = Some(derived_from.clone());
+        self.peripheral.derived_from = Some(derived_from);
+        self
+    }
+
+    pub fn version<S: Into<String>>(mut self, version: S) -> PeripheralBuilder {
+        self.peripheral.version = Some(version.into());
+        self
+    }
+
+    pub fn description<S: Into<String>>(mut self, description: S) -> PeripheralBuilder {
+        self.peripheral.description = Some(description.into());
+        self
+    }
+
+    pub fn alternate_peripheral<S: Into<String>>(mut self,
+                                                 alternate_peripheral: S)
+                                                 -> PeripheralBuilder {
+        self.peripheral.alternate_peripheral = Some(alternate_peripheral.into());
+        self
+    }
+
+    pub fn group_name<S: Into<String>>(mut self, group_name: S) -> PeripheralBuilder {
+        self.peripheral.group_name = Some(group_name.into());
+        self
+    }
+
+    pub fn prepend_to_name<S: Into<String>>(mut self, prepend_to_name: S) -> PeripheralBuilder {
+        self.peripheral.prepend_to_name = Some(prepend_to_name.into());
+        self
+    }
+
+    pub fn append_to_name<S: Into<String>>(mut self, append_to_name: S) -> PeripheralBuilder {
+        self.peripheral.append_to_name = Some(append_to_name.into());
+        self
+    }
+
+    pub fn header_struct_name<S: Into<String>>(mut self,
+                                               header_struct_name: S)
+                                               -> PeripheralBuilder {
+        self.peripheral.header_struct_name = Some(header_struct_name.into());
+        self
+    }
+
+    pub fn disable_condition<S: Into<String>>(mut self,
+                                              disable_condition: S)
+                                              -> PeripheralBuilder {
+        self.peripheral.disable_condition = Some(disable_condition.into());
+        self
+    }
+
+    register_properties_setters!(PeripheralBuilder, peripheral);
+
+    pub fn address_block(mut self, offset: u64, size: u64, usage: Usage) -> PeripheralBuilder {
+        self.peripheral.address_blocks.push(AddresBlock {
+            offset: ScaledNonNegativeInteger(offset),
+            size: ScaledNonNegativeInteger(size),
+            usage: usage,
+            protection: None,
+            unknown_elements: vec![],
+        });
+        self
+    }
+
+    pub fn interrupt<S: Into<String>>(mut self, name: S, value: i64) -> PeripheralBuilder {
+        self.peripheral.interrupts.push(Interrupt {
+            name: name.into(),
+            description: None,
+            value: value,
+            unknown_elements: vec![],
+        });
+        self
+    }
+
+    pub fn register(mut self, register: Register) -> PeripheralBuilder {
+        self.peripheral
+            .registers
+            .get_or_insert_with(Vec::new)
+            .push(RegisterOrCluster::Register(register));
+        self
+    }
+
+    pub fn cluster(mut self, cluster: Cluster) -> PeripheralBuilder {
+        self.peripheral
+            .registers
+            .get_or_insert_with(Vec::new)
+            .push(RegisterOrCluster::Cluster(cluster));
+        self
+    }
+
+    /// Returns the peripheral, or an error if its name is empty.
+    pub fn build(self) -> Result<Peripheral> {
+        if self.peripheral.name.is_empty() {
+            Err(ErrorKind::MissingField("peripheral", "name").into())
+        } else {
+            Ok(self.peripheral)
+        }
+    }
+}
+
 impl<'a, 'b> IsSimilar<&'a Peripheral> for &'b Peripheral {
     fn is_similar(self, other: &Peripheral, options: &IsSimilarOptions) -> bool {
-        self.registers.is_similar(&other.registers, options)
+        if options.allow_register_superset() {
+            let empty = vec![];
+            is_similar_subset(self.registers.as_ref().unwrap_or(&empty),
+                              other.registers.as_ref().unwrap_or(&empty),
+                              options)
+        } else {
+            self.registers.is_similar(&other.registers, options)
+        }
+    }
+}
+
+impl<'a, 'b> ExplainDifferences<&'a Peripheral> for &'b Peripheral {
+    fn explain_differences(self,
+                           other: &Peripheral,
+                           options: &IsSimilarOptions)
+                           -> Vec<Difference> {
+        let differences = if options.allow_register_superset() {
+            let empty = vec![];
+            explain_subset_differences(self.registers.as_ref().unwrap_or(&empty),
+                                       other.registers.as_ref().unwrap_or(&empty),
+                                       options)
+        } else {
+            self.registers.explain_differences(&other.registers, options)
+        };
+        Difference::nested("", "registers", differences)
     }
 }
 
@@ -157,35 +406,100 @@ pub struct PeripheralsGroup {
     module_name: IdentifierType,
     struct_name: IdentifierType,
     peripherals: Vec<Peripheral>,
+    /// The index of the peripheral whose registers include the registers of all the others
+    superset: usize,
 }
 
 impl PeripheralsGroup {
-    /// Group similar peripherals together.
+    /// Group similar peripherals together. A peripheral is first grouped with the peripheral named
+    /// by its `original_derived_from`, then with the first peripheral with the same
+    /// `header_struct_name`, if they are similar. Each group is finally merged with the first group
+    /// that has the same `header_struct_name`, or none like it, and a peripheral similar to one of
+    /// its peripherals. Only the peripherals with the same registers offsets and fields bit ranges
+    /// are compared in this last step, which takes near linear time. With
+    /// `allow_register_superset`, the peripherals can be similar without having the same registers
+    /// offsets, so all the groups are compared and this step takes quadratic time.
     pub fn from_peripherals<'a, I>(peripherals: I,
                                    options: &IsSimilarOptions)
                                    -> (Vec<PeripheralsGroup>, Vec<Peripheral>)
         where I: IntoIterator<Item = &'a Peripheral>
     {
-        let mut groups: Vec<Vec<Peripheral>> = vec![];
-        for peripheral in peripherals {
-            let mut group_found = false;
-            for group in &mut groups {
-                if should_group(group, peripheral, options) {
-                    group.push(peripheral.clone());
-                    group_found = true;
-                    break;
+        let peripherals: Vec<_> = peripherals.into_iter().collect();
+        let similar = |i: usize, j: usize| peripherals[i].is_similar(peripherals[j], options);
+        let registers_len = |i: usize| peripherals[i].registers.as_ref().map_or(0, |r| r.len());
+        // With `allow_register_superset`, similar only means that the registers of one peripheral
+        // are a subset of the registers of the other, so the groups are only merged when the
+        // registers of one of their peripherals include the registers of all the others
+        let contains = |s: usize, m: usize| registers_len(m) <= registers_len(s) && similar(m, s);
+        let merge = |groups: &mut Groups, i: usize, j: usize| {
+            if options.allow_register_superset() {
+                groups.merge_subsets(i, j, &contains)
+            } else if similar(i, j) {
+                groups.union(i, j);
+                true
+            } else {
+                false
+            }
+        };
+        let mut groups = Groups::new(peripherals.len());
+
+        let indices: HashMap<_, _> =
+            peripherals.iter().enumerate().map(|(i, p)| (&*p.name, i)).collect();
+        for (i, peripheral) in peripherals.iter().enumerate() {
+            if let Some(ref derived_from) = peripheral.original_derived_from {
+                if let Some(&j) = indices.get(&**derived_from) {
+                    merge(&mut groups, i, j);
                 }
             }
+        }
 
-            if !group_found {
-                groups.push(vec![peripheral.clone()])
+        let mut struct_names = HashMap::new();
+        for (i, peripheral) in peripherals.iter().enumerate() {
+            if let Some(ref name) = peripheral.header_struct_name {
+                let j = *struct_names.entry(&**name).or_insert(i);
+                merge(&mut groups, i, j);
+            }
+        }
+
+        let mut group_struct_names = vec![None; peripherals.len()];
+        for (i, peripheral) in peripherals.iter().enumerate() {
+            let root = groups.find(i);
+            if group_struct_names[root].is_none() {
+                group_struct_names[root] = peripheral.header_struct_name.as_ref();
+            }
+        }
+        // The peripherals of the previous groups, by structure key
+        let mut candidates: HashMap<Vec<u64>, Vec<usize>> = HashMap::new();
+        for i in 0..peripherals.len() {
+            if groups.find(i) != i {
+                continue;
+            }
+            let members = groups.members[i].clone();
+            let keys: Vec<_> =
+                members.iter().map(|&m| structure_key(peripherals[m], options)).collect();
+            'merge: for (&m, key) in members.iter().zip(&keys) {
+                for &candidate in candidates.get(key).map_or(&[][..], |c| &c[..]) {
+                    let root = groups.find(candidate);
+                    if group_struct_names[i] == group_struct_names[root] &&
+                       merge(&mut groups, m, candidate) {
+                        break 'merge;
+                    }
+                }
+            }
+            for (m, key) in members.into_iter().zip(keys) {
+                candidates.entry(key).or_insert_with(Vec::new).push(m);
             }
         }
 
         let mut groups2 = vec![];
         let mut individuals = vec![];
 
-        for mut group in groups {
+        for i in 0..peripherals.len() {
+            if groups.find(i) != i {
+                continue;
+            }
+            let members = &groups.members[i];
+            let mut group: Vec<_> = members.iter().map(|&m| peripherals[m].clone()).collect();
             if group.len() == 1 {
                 individuals.append(&mut group);
             } else {
@@ -193,10 +507,12 @@ impl PeripheralsGroup {
                 if struct_name.is_empty() {
                     individuals.append(&mut group);
                 } else {
+                    let superset = groups.supersets[i];
                     groups2.push(PeripheralsGroup {
                         module_name: struct_name.clone(),
                         struct_name: struct_name,
                         peripherals: group,
+                        superset: members.iter().position(|&m| m == superset).unwrap(),
                     });
                 }
             }
@@ -219,6 +535,12 @@ impl PeripheralsGroup {
         &*self.peripherals
     }
 
+    /// Returns the registers of the group: the registers of a peripheral whose registers include
+    /// the registers of all the others, which matters with `allow_register_superset`.
+    pub fn registers(&self) -> Option<&Vec<RegisterOrCluster>> {
+        self.peripherals[self.superset].registers.as_ref()
+    }
+
     pub fn description(&self) -> &Option<String> {
         for peripheral in self.peripherals() {
             if peripheral.description.is_some() {
@@ -256,17 +578,114 @@ fn make_names_unique(groups: &mut [PeripheralsGroup], individuals: &mut [Periphe
     }
 }
 
-fn should_group(group: &[Peripheral],
-                new_peripheral: &Peripheral,
-                options: &IsSimilarOptions)
-                -> bool {
-    for peripheral in group {
-        if peripheral.is_similar(new_peripheral, options) {
+/// The groups of peripherals built by `PeripheralsGroup::from_peripherals`, as a union-find.
+struct Groups {
+    /// The peripherals with the same root are in the same group, the root being the first
+    /// peripheral of the group
+    roots: Vec<usize>,
+    /// The peripherals of each group, in order, indexed by the root of the group
+    members: Vec<Vec<usize>>,
+    /// The peripheral of each group whose registers include the registers of all the others,
+    /// indexed by the root of the group
+    supersets: Vec<usize>,
+}
+
+impl Groups {
+    fn new(len: usize) -> Groups {
+        Groups {
+            roots: (0..len).collect(),
+            members: (0..len).map(|i| vec![i]).collect(),
+            supersets: (0..len).collect(),
+        }
+    }
+
+    /// Returns the root of the group of the peripheral `i`.
+    fn find(&mut self, i: usize) -> usize {
+        let mut i = i;
+        while self.roots[i] != i {
+            self.roots[i] = self.roots[self.roots[i]];
+            i = self.roots[i];
+        }
+        i
+    }
+
+    /// Merges the groups of the peripherals `i` and `j`, keeping the first peripheral as the root
+    /// and the superset of the group of `i`.
+    fn union(&mut self, i: usize, j: usize) {
+        let (root_i, root_j) = (self.find(i), self.find(j));
+        if root_i == root_j {
+            return;
+        }
+        let superset = self.supersets[root_i];
+        let (root, other) = if root_i < root_j { (root_i, root_j) } else { (root_j, root_i) };
+        self.roots[other] = root;
+        let mut members = ::std::mem::replace(&mut self.members[other], vec![]);
+        self.members[root].append(&mut members);
+        self.members[root].sort();
+        self.supersets[root] = superset;
+    }
+
+    /// Merges the groups of the peripherals `i` and `j` if the superset of one of them contains
+    /// all the peripherals of both groups, `contains(s, m)` telling if the peripheral `s` contains
+    /// the peripheral `m`. Returns true if the groups were merged.
+    fn merge_subsets<F>(&mut self, i: usize, j: usize, contains: F) -> bool
+        where F: Fn(usize, usize) -> bool
+    {
+        let (root_i, root_j) = (self.find(i), self.find(j));
+        if root_i == root_j {
             return true;
         }
+        for &(root, other) in &[(root_i, root_j), (root_j, root_i)] {
+            let superset = self.supersets[root];
+            let contains_all = self.members[root_i]
+                .iter()
+                .chain(&self.members[root_j])
+                .all(|&m| m == superset || contains(superset, m));
+            if contains_all {
+                self.union(root, other);
+                return true;
+            }
+        }
+        false
     }
+}
 
-    false
+/// Returns a key that is the same for all the peripherals similar to `peripheral`, which is empty
+/// with `allow_register_superset`.
+fn structure_key(peripheral: &Peripheral, options: &IsSimilarOptions) -> Vec<u64> {
+    let mut key = vec![];
+    if !options.allow_register_superset() {
+        if let Some(ref registers) = peripheral.registers {
+            add_structure_key(registers, options, &mut key);
+        }
+    }
+    key
+}
+
+fn add_structure_key(registers: &[RegisterOrCluster],
+                     options: &IsSimilarOptions,
+                     key: &mut Vec<u64>) {
+    key.push(registers.len() as u64);
+    for r_or_c in registers {
+        match *r_or_c {
+            RegisterOrCluster::Register(ref r) => {
+                key.push(r.address_offset.0);
+                key.push(r.size());
+                if let (false, Some(fields)) = (options.ignore_fields(), r.fields.as_ref()) {
+                    key.push(fields.len() as u64);
+                    for f in fields {
+                        key.push(f.bit_range.lsb as u64);
+                        key.push(f.bit_range.msb as u64);
+                    }
+                }
+            }
+            RegisterOrCluster::Cluster(ref c) => {
+                key.push(u64::max_value());
+                key.push(c.address_offset.0);
+                add_structure_key(&c.registers, options, key);
+            }
+        }
+    }
 }
 
 fn struct_name(peripherals: &[Peripheral]) -> IdentifierType {
@@ -294,3 +713,222 @@ fn struct_name(peripherals: &[Peripheral]) -> IdentifierType {
 
     struct_name
 }
+
+#[cfg(test)]
+mod tests {
+    use field::Field;
+    use is_similar::IsSimilarOptions;
+    use register::Register;
+    use super::*;
+
+    fn peripheral(name: &str, registers: &[(&str, u64)]) -> Peripheral {
+        registers.iter()
+            .fold(Peripheral::builder(name, 0x4000_0000), |p, &(name, offset)| {
+                p.register(Register::builder(name, offset).build().unwrap())
+            })
+            .build()
+            .unwrap()
+    }
+
+    fn names(peripherals: &[Peripheral]) -> Vec<&str> {
+        peripherals.iter().map(|p| &*p.name).collect()
+    }
+
+    fn groups(groups: &[PeripheralsGroup]) -> Vec<(&str, Vec<&str>)> {
+        groups.iter().map(|g| (g.struct_name(), names(g.peripherals()))).collect()
+    }
+
+    fn register_names(group: &PeripheralsGroup) -> Vec<&str> {
+        group.registers().unwrap().iter().map(|r| r.name()).collect()
+    }
+
+    #[test]
+    fn builder() {
+        use address_block::Usage;
+
+        let gpiob = Peripheral::builder("GPIOB", 0x4002_0400)
+            .derived_from("GPIOA")
+            .group_name("GPIO")
+            .address_block(0x0, 0x400, Usage::Registers)
+            .interrupt("EXTI0", 6)
+            .register(Register::builder("MODER", 0x0).build().unwrap())
+            .build()
+            .unwrap();
+        assert_eq!(Some("GPIOA".to_string()), gpiob.derived_from);
+        assert_eq!(ScaledNonNegativeInteger(0x4002_0400), gpiob.base_address);
+        assert_eq!(vec!["EXTI0"], gpiob.interrupts.iter().map(|i| &*i.name).collect::<Vec<_>>());
+        assert_eq!(gpiob, Peripheral::from_element(&gpiob.to_element()).unwrap());
+
+        // A peripheral without registers has no registers element
+        let gpioc = Peripheral::builder("GPIOC", 0x4002_0800).build().unwrap();
+        assert!(gpioc.registers.is_none());
+        assert!(gpioc.to_element().get_child("registers").is_none());
+        assert!(Peripheral::builder("", 0x4002_0800).build().is_err());
+    }
+
+    #[test]
+    fn unknown_elements() {
+        use xml_fragment::parse_element;
+
+        let xml = r#"<peripheral>
+                         <name>DMA2</name>
+                         <baseAddress>0x40020400</baseAddress>
+                         <vendorExtensions>
+                             <acme:secure
+                                 xmlns:acme="http://acme.com">true <b>bold</b></acme:secure>
+                         </vendorExtensions>
+                         <dimName>DMA</dimName>
+                     </peripheral>"#;
+        let p = Peripheral::from_element(&parse_element(xml.as_bytes()).unwrap()).unwrap();
+        assert_eq!(vec!["dimName"],
+                   p.unknown_elements.iter().map(|e| e.name()).collect::<Vec<_>>());
+        let secure = &p.vendor_extensions.as_ref().unwrap().0.children[0];
+        assert_eq!("acme:secure", secure.name);
+        assert_eq!(Some(&"http://acme.com".to_string()), secure.attributes.get("xmlns:acme"));
+        assert_eq!("bold", secure.children[0].text.as_ref().unwrap());
+
+        // The unknown elements are written where the schema expects them, and the vendor
+        // extensions last
+        let element = p.to_element();
+        assert_eq!(vec!["dimName", "name", "baseAddress", "vendorExtensions"],
+                   element.children.iter().map(|c| &*c.name).collect::<Vec<_>>());
+        assert_eq!(p, Peripheral::from_element(&element).unwrap());
+    }
+
+    #[test]
+    fn same_header_struct_name() {
+        let with_struct_name = |name, registers| {
+            let mut peripheral = peripheral(name, registers);
+            peripheral.header_struct_name = Some("TIM".to_string());
+            peripheral
+        };
+        // TIM2 and TIM3 are not similar to TIM1, the first peripheral named TIM, but are similar
+        // to each other. TIM4 is similar to TIM1 but has no struct name.
+        let tim1 = with_struct_name("TIM1", &[("CR", 0x0)]);
+        let tim2 = with_struct_name("TIM2", &[("CR", 0x0), ("ARR", 0x4)]);
+        let tim3 = with_struct_name("TIM3", &[("CR", 0x0), ("ARR", 0x4)]);
+        let tim4 = peripheral("TIM4", &[("CR", 0x0)]);
+        let (groups, individuals) =
+            PeripheralsGroup::from_peripherals(&[tim1, tim2, tim3, tim4], &IsSimilarOptions::new());
+        assert_eq!(1, groups.len());
+        assert_eq!("TIM", groups[0].struct_name());
+        assert_eq!(vec!["TIM2", "TIM3"],
+                   groups[0].peripherals().iter().map(|p| &*p.name).collect::<Vec<_>>());
+        assert_eq!(vec!["TIM1", "TIM4"], individuals.iter().map(|p| &*p.name).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn register_superset() {
+        let mut options = IsSimilarOptions::new();
+        options.set_allow_register_superset(true);
+
+        // TIM2 and TIM3 both contain TIM1 but not each other
+        let tim1 = peripheral("TIM1", &[("CR", 0x0)]);
+        let tim2 = peripheral("TIM2", &[("CR", 0x0), ("ARR", 0x4)]);
+        let tim3 = peripheral("TIM3", &[("CR", 0x0), ("CCR", 0x8), ("DIER", 0xc)]);
+        let (groups, individuals) =
+            PeripheralsGroup::from_peripherals(&[tim1.clone(), tim2.clone(), tim3.clone()],
+                                               &options);
+        assert_eq!(1, groups.len());
+        assert_eq!(vec!["TIM1", "TIM2"],
+                   groups[0].peripherals().iter().map(|p| &*p.name).collect::<Vec<_>>());
+        assert_eq!(vec!["CR", "ARR"], register_names(&groups[0]));
+        assert_eq!(vec!["TIM3"], individuals.iter().map(|p| &*p.name).collect::<Vec<_>>());
+
+        // The superset is not the first peripheral, and is found whatever the order
+        let tim4 = peripheral("TIM4", &[("ARR", 0x4), ("CR", 0x0), ("CCR", 0x8)]);
+        let (groups, individuals) =
+            PeripheralsGroup::from_peripherals(&[tim1, tim2, tim4], &options);
+        assert_eq!(3, groups[0].peripherals().len());
+        assert_eq!(vec!["ARR", "CR", "CCR"], register_names(&groups[0]));
+        assert!(individuals.is_empty());
+    }
+
+    #[test]
+    fn derived_from() {
+        let derived = |name, derived_from: &str, registers| {
+            let mut peripheral = peripheral(name, registers);
+            peripheral.original_derived_from = Some(derived_from.to_string());
+            peripheral
+        };
+        let mut usart1 = peripheral("USART1", &[("CR", 0x0), ("DR", 0x4)]);
+        usart1.header_struct_name = Some("USART".to_string());
+        // USART2 is grouped with USART1 although it has no header struct name
+        let usart2 = derived("USART2", "USART1", &[("CR", 0x0), ("DR", 0x4)]);
+        // SPI2 overrides the registers of SPI1, so they are not similar
+        let spi1 = peripheral("SPI1", &[("CR", 0x0), ("DR", 0x4)]);
+        let spi2 = derived("SPI2", "SPI1", &[("CRCPR", 0x10)]);
+        // The unknown derivedFrom of UART5 is ignored, and it is grouped by similarity
+        let uart4 = peripheral("UART4", &[("SR", 0x8)]);
+        let uart5 = derived("UART5", "UART9", &[("SR", 0x8)]);
+        let (groups, individuals) =
+            PeripheralsGroup::from_peripherals(&[usart1, spi1, uart4, usart2, spi2, uart5],
+                                               &IsSimilarOptions::new());
+        assert_eq!(vec![("USART", vec!["USART1", "USART2"]), ("UART", vec!["UART4", "UART5"])],
+                   self::groups(&groups));
+        assert_eq!(vec!["SPI1", "SPI2"], names(&individuals));
+    }
+
+    #[test]
+    fn different_header_struct_names() {
+        let with_struct_name = |name, struct_name: &str| {
+            let mut peripheral = peripheral(name, &[("CR", 0x0), ("DR", 0x4)]);
+            peripheral.header_struct_name = Some(struct_name.to_string());
+            peripheral
+        };
+        // Similar peripherals are not grouped when their header struct names differ
+        let usart1 = with_struct_name("USART1", "USART");
+        let uart4 = with_struct_name("UART4", "UART");
+        let usart2 = with_struct_name("USART2", "USART");
+        let usart3 = peripheral("USART3", &[("CR", 0x0), ("DR", 0x4)]);
+        let (groups, individuals) =
+            PeripheralsGroup::from_peripherals(&[usart1, uart4, usart2, usart3],
+                                               &IsSimilarOptions::new());
+        assert_eq!(vec![("USART", vec!["USART1", "USART2"])], self::groups(&groups));
+        assert_eq!(vec!["UART4", "USART3"], names(&individuals));
+    }
+
+    #[test]
+    fn structural_similarity() {
+        let gpio = |name, msb| {
+            let mut moder = Register::builder("MODER", 0x0);
+            for i in 0..2 {
+                moder = moder.field(Field::builder(format!("MODER{}", i), 2 * i..=2 * i + msb)
+                    .build()
+                    .unwrap());
+            }
+            Peripheral::builder(name, 0x4002_0000).register(moder.build().unwrap()).build().unwrap()
+        };
+        // GPIOC has the same registers but other fields, and RCC and PWR have no common name
+        let peripherals = [gpio("GPIOA", 1),
+                           gpio("GPIOB", 1),
+                           gpio("GPIOC", 0),
+                           peripheral("RCC", &[("CR", 0x0)]),
+                           peripheral("PWR", &[("CR", 0x0)]),
+                           gpio("GPIOD", 1)];
+        let (groups, individuals) =
+            PeripheralsGroup::from_peripherals(&peripherals, &IsSimilarOptions::new());
+        assert_eq!(vec![("GPIO", vec!["GPIOA", "GPIOB", "GPIOD"])], self::groups(&groups));
+        assert_eq!(vec!["GPIOC", "RCC", "PWR"], names(&individuals));
+
+        // The fields are not part of the structure with `ignore_fields`
+        let mut options = IsSimilarOptions::new();
+        options.set_ignore_fields(true);
+        let (groups, _) = PeripheralsGroup::from_peripherals(&peripherals, &options);
+        assert_eq!(vec![("GPIO", vec!["GPIOA", "GPIOB", "GPIOC", "GPIOD"])],
+                   self::groups(&groups));
+    }
+
+    #[test]
+    fn unique_module_names() {
+        // The module of the GPIOA and GPIOB group has the name of the GPIO peripheral
+        let peripherals = [peripheral("GPIOA", &[("MODER", 0x0)]),
+                           peripheral("GPIO", &[("CR", 0x0)]),
+                           peripheral("GPIOB", &[("MODER", 0x0)])];
+        let (groups, individuals) =
+            PeripheralsGroup::from_peripherals(&peripherals, &IsSimilarOptions::new());
+        assert_eq!("GPIO", groups[0].struct_name());
+        assert_eq!("GPIO_1", groups[0].module_name());
+        assert_eq!(vec!["GPIO_0"], names(&individuals));
+    }
+}
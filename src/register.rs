@@ -1,15 +1,46 @@
 use data_type::DataType;
-use dim_element_group::DimElementGroup;
+use dim_element_group::{self, DimElementGroup};
+use enumerated_values::EnumeratedValue;
 use errors::*;
 use field::Field;
-use is_similar::{IsSimilar, IsSimilarOptions};
+use is_similar::{Difference, ExplainDifferences, IsSimilar, IsSimilarOptions};
 use modified_write_values::ModifiedWriteValues;
 use read_action::ReadAction;
-use register_properties_group::RegisterPropertiesGroup;
+use register_properties_group::{self, RegisterPropertiesGroup};
+use std::collections::{BTreeMap, HashSet};
 use types::*;
-use utils::get_child_text;
+use utils::{add_child_text, add_optional_child_text, element_path_segment, extract_prefix,
+            get_child_text, new_element};
+use xml_fragment::XmlFragment;
 use xmltree;
 
+/// A field of a register value, as returned by `Register::decode`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DecodedField<'a> {
+    pub field: &'a Field,
+    pub value: u64,
+    /// The enumerated value describing `value`, if any.
+    pub enumerated_value: Option<&'a EnumeratedValue>,
+}
+
+/// The names of the child elements read by `from_element`.
+const ELEMENT_NAMES: &'static [&'static str] = &["name", "displayName", "description",
+                                                 "alternateGroup", "alternateRegister",
+                                                 "addressOffset", "dataType", "modifiedWriteValues",
+                                                 "readAction", "fields", "vendorExtensions"];
+
+/// The names of the child elements in the order required by the schema, including the ones not
+/// read by `from_element`.
+const SCHEMA_ORDER: &'static [&'static str] = &["dim", "dimIncrement", "dimIndex", "dimName",
+                                                "dimArrayIndex", "name", "displayName",
+                                                "description", "alternateGroup",
+                                                "alternateRegister", "addressOffset", "size",
+                                                "access", "protection", "resetValue", "resetMask",
+                                                "dataType", "modifiedWriteValues",
+                                                "writeConstraint", "readAction", "fields",
+                                                "vendorExtensions"];
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Register {
     pub derived_from: Option<IdentifierType>,
@@ -25,9 +56,35 @@ pub struct Register {
     pub modified_write_values: Option<ModifiedWriteValues>,
     pub read_action: Option<ReadAction>,
     pub fields: Option<Vec<Field>>,
+    pub vendor_extensions: Option<XmlFragment>,
+    pub unknown_elements: Vec<XmlFragment>,
 }
 
 impl Register {
+    /// Returns a builder for a register named `name` at the offset `address_offset` of its
+    /// peripheral or cluster.
+    pub fn builder<S: Into<String>>(name: S, address_offset: u64) -> RegisterBuilder {
+        RegisterBuilder {
+            register: Register {
+                derived_from: None,
+                dim_element: DimElementGroup::default(),
+                name: name.into(),
+                display_name: None,
+                description: None,
+                alternate_group: None,
+                alternate_register: None,
+                address_offset: ScaledNonNegativeInteger(address_offset),
+                register_properties: RegisterPropertiesGroup::default(),
+                data_type: None,
+                modified_write_values: None,
+                read_action: None,
+                fields: None,
+                vendor_extensions: None,
+                unknown_elements: vec![],
+            },
+        }
+    }
+
     pub fn from_element(element: &xmltree::Element) -> Result<Register> {
         let derived_from = element.attributes.get("derivedFrom").cloned();
         let dim_element = try!(DimElementGroup::from_element(element));
@@ -54,14 +111,26 @@ impl Register {
 
         let fields = match element.get_child("fields") {
             Some(fields) => {
-                let fields: Result<Vec<_>> =
-                    fields.children.iter().map(Field::from_element).collect();
+                let fields: Result<Vec<_>> = fields.children
+                    .iter()
+                    .enumerate()
+                    .map(|(i, e)| {
+                        Field::from_element(e)
+                            .in_element(format!("fields/{}", element_path_segment(e, i)))
+                    })
+                    .collect();
                 let fields = try!(fields);
                 Some(fields)
             }
             None => None,
         };
 
+        let vendor_extensions = XmlFragment::from_child(element, "vendorExtensions");
+        let known_names = [ELEMENT_NAMES,
+                           dim_element_group::ELEMENT_NAMES,
+                           register_properties_group::ELEMENT_NAMES];
+        let unknown_elements = XmlFragment::unknown_children(element, &known_names);
+
         let address_offset = try!(address_offset.parse());
 
         Ok(Register {
@@ -78,9 +147,45 @@ impl Register {
             modified_write_values: modified_write_values,
             read_action: read_action,
             fields: fields,
+            vendor_extensions: vendor_extensions,
+            unknown_elements: unknown_elements,
         })
     }
 
+    pub fn to_element(&self) -> xmltree::Element {
+        let mut element = new_element("register");
+        if let Some(ref derived_from) = self.derived_from {
+            element.attributes.insert("derivedFrom".to_string(), derived_from.clone());
+        }
+        self.dim_element.add_to_element(&mut element);
+        add_child_text(&mut element, "name", &self.name);
+        add_optional_child_text(&mut element, "displayName", &self.display_name);
+        add_optional_child_text(&mut element, "description", &self.description);
+        add_optional_child_text(&mut element, "alternateGroup", &self.alternate_group);
+        add_optional_child_text(&mut element, "alternateRegister", &self.alternate_register);
+        add_child_text(&mut element,
+                       "addressOffset",
+                       format!("0x{:X}", self.address_offset));
+        self.register_properties.add_to_element(&mut element);
+        add_optional_child_text(&mut element, "dataType", &self.data_type);
+        add_optional_child_text(&mut element,
+                                "modifiedWriteValues",
+                                &self.modified_write_values);
+        add_optional_child_text(&mut element, "readAction", &self.read_action);
+        if let Some(ref fields) = self.fields {
+            let mut fields_element = new_element("fields");
+            for field in fields {
+                fields_element.children.push(field.to_element());
+            }
+            element.children.push(fields_element);
+        }
+        XmlFragment::add_to_element(&mut element, &self.unknown_elements, SCHEMA_ORDER);
+        if let Some(ref vendor_extensions) = self.vendor_extensions {
+            element.children.push(vendor_extensions.0.clone());
+        }
+        element
+    }
+
     pub fn is_read(&self) -> bool {
         match self.register_properties.access {
             Some(ref access) => access.is_read(),
@@ -99,6 +204,64 @@ impl Register {
         self.register_properties.size.map_or(32, |s| s.0)
     }
 
+    /// Returns the mask of the bits of the register, according to its size.
+    pub fn size_mask(&self) -> u64 {
+        let size = self.size();
+        if size >= 64 {
+            !0
+        } else {
+            (1 << size) - 1
+        }
+    }
+
+    /// Returns the mask of the bits covered by a field. All the bits of a register without fields
+    /// are defined.
+    pub fn defined_bits_mask(&self) -> u64 {
+        match self.fields {
+            Some(ref fields) => {
+                fields.iter().fold(0, |mask, field| mask | field.bit_range.mask()) &
+                self.size_mask()
+            }
+            None => self.size_mask(),
+        }
+    }
+
+    /// Returns the mask of the bits of the register that are not covered by any field.
+    pub fn reserved_bits_mask(&self) -> u64 {
+        self.size_mask() & !self.defined_bits_mask()
+    }
+
+    /// Returns the value of `field` after a reset, or `None` if the reset value of the register
+    /// is unknown or if some bits of the field are not covered by its reset mask.
+    ///
+    /// Only the properties of the register itself are used, merge the properties of its
+    /// peripheral and device first to take the inherited ones into account.
+    pub fn field_reset_value(&self, field: &Field) -> Option<u64> {
+        let reset_mask = self.register_properties.reset_mask.map_or(!0, |m| m.0);
+        match self.register_properties.reset_value {
+            Some(reset_value) if field.bit_range.mask() & !reset_mask == 0 => {
+                Some(field.extract(reset_value.0))
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the value of each field in the register value `raw`, in the order the fields are
+    /// declared.
+    pub fn decode(&self, raw: u64) -> Vec<DecodedField> {
+        self.fields
+            .iter()
+            .flat_map(|fields| fields)
+            .map(|field| {
+                DecodedField {
+                    field: field,
+                    value: field.extract(raw),
+                    enumerated_value: field.decode(raw),
+                }
+            })
+            .collect()
+    }
+
     pub fn merge_derived_from(&mut self, derived_from: &Register) {
         self.derived_from = derived_from.derived_from.clone();
         self.dim_element.merge_derived_from(&derived_from.dim_element);
@@ -140,12 +303,534 @@ impl Register {
     }
 }
 
+/// A builder for a `Register`, returned by `Register::builder`.
+#[derive(Clone, Debug)]
+pub struct RegisterBuilder {
+    register: Register,
+}
+
+impl RegisterBuilder {
+    pub fn display_name<S: Into<String>>(mut self, display_name: S) -> RegisterBuilder {
+        self.register.display_name = Some(display_name.into());
+        self
+    }
+
+    pub fn description<S: Into<String>>(mut self, description: S) -> RegisterBuilder {
+        self.register.description = Some(description.into());
+        self
+    }
+
+    pub fn alternate_group<S: Into<String>>(mut self, alternate_group: S) -> RegisterBuilder {
+        self.register.alternate_group = Some(alternate_group.into());
+        self
+    }
+
+    pub fn alternate_register<S: Into<String>>(mut self,
+                                               alternate_register: S)
+                                               -> RegisterBuilder {
+        self.register.alternate_register = Some(alternate_register.into());
+        self
+    }
+
+    register_properties_setters!(RegisterBuilder, register);
+
+    pub fn data_type(mut self, data_type: DataType) -> RegisterBuilder {
+        self.register.data_type = Some(data_type);
+        self
+    }
+
+    pub fn modified_write_values(mut self,
+                                 modified_write_values: ModifiedWriteValues)
+                                 -> RegisterBuilder {
+        self.register.modified_write_values = Some(modified_write_values);
+        self
+    }
+
+    pub fn read_action(mut self, read_action: ReadAction) -> RegisterBuilder {
+        self.register.read_action = Some(read_action);
+        self
+    }
+
+    pub fn field(mut self, field: Field) -> RegisterBuilder {
+        self.register.fields.get_or_insert_with(Vec::new).push(field);
+        self
+    }
+
+    /// Returns the register, or an error if its name is empty or, when its size is set, if a
+    /// field doesn't fit in it.
+    pub fn build(self) -> Result<Register> {
+        if self.register.name.is_empty() {
+            return Err(ErrorKind::MissingField("register", "name").into());
+        }
+        if let Some(size) = self.register.register_properties.size {
+            for field in self.register.fields.iter().flat_map(|fields| fields) {
+                if u64::from(field.bit_range.msb) >= size.0 {
+                    return Err(ErrorKind::UnexpectedValue("a field inside the register",
+                                                          format!("{} at [{}:{}]",
+                                                                  field.name,
+                                                                  field.bit_range.msb,
+                                                                  field.bit_range.lsb))
+                        .into());
+                }
+            }
+        }
+        Ok(self.register)
+    }
+}
+
 impl<'a, 'b> IsSimilar<&'a Register> for &'b Register {
     fn is_similar(self, other: &Register, options: &IsSimilarOptions) -> bool {
-        self.name == other.name && self.address_offset == other.address_offset &&
+        (options.ignore_register_names() || self.name == other.name) &&
+        self.address_offset == other.address_offset &&
         self.register_properties.is_similar(&other.register_properties, options) &&
-        self.modified_write_values == other.modified_write_values &&
+        (options.ignore_modified_write_values() ||
+         self.modified_write_values == other.modified_write_values) &&
         self.read_action == other.read_action &&
         (options.ignore_fields() || self.fields.is_similar(&other.fields, options))
     }
 }
+
+impl<'a, 'b> ExplainDifferences<&'a Register> for &'b Register {
+    fn explain_differences(self, other: &Register, options: &IsSimilarOptions) -> Vec<Difference> {
+        let name = &*self.name;
+        let mut differences = vec![];
+        if !options.ignore_register_names() && self.name != other.name {
+            differences.push(Difference::new(name, "name"));
+        }
+        if self.address_offset != other.address_offset {
+            differences.push(Difference::new(name, "addressOffset"));
+        }
+        differences.extend(self.register_properties
+            .explain_differences(&other.register_properties, options)
+            .into_iter()
+            .map(|d| Difference::new(name, d.property)));
+        if !options.ignore_modified_write_values() &&
+           self.modified_write_values != other.modified_write_values {
+            differences.push(Difference::new(name, "modifiedWriteValues"));
+        }
+        if self.read_action != other.read_action {
+            differences.push(Difference::new(name, "readAction"));
+        }
+        if !options.ignore_fields() {
+            differences.extend(Difference::nested(name,
+                                                  "fields",
+                                                  self.fields
+                                                      .explain_differences(&other.fields,
+                                                                           options)));
+        }
+        differences
+    }
+}
+
+/// Registers repeated with a numeric suffix and a constant stride, like `CCR1`, `CNDTR1`, `CCR2`,
+/// `CNDTR2`..., that can be accessed as an array of clusters.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct RegistersGroup {
+    name: String,
+    accessor_name: String,
+    first_index: usize,
+    count: usize,
+    address_offset: u64,
+    increment: u64,
+    registers: Vec<Register>,
+}
+
+impl RegistersGroup {
+    /// Group the registers repeated with consecutive numeric suffixes. The registers with the same
+    /// prefix, similar layouts and a constant stride form a series, and the series with the same
+    /// suffixes and stride that fit together in the stride form a group, which has at least two
+    /// registers in each element. A group is not formed if its name, the name of its accessor or
+    /// one of the names of its registers is already used by a register or another group, or if
+    /// the name of its accessor would be a keyword. Returns
+    /// the groups found and the registers that can't be grouped.
+    pub fn from_registers<'a, I>(registers: I) -> (Vec<RegistersGroup>, Vec<Register>)
+        where I: IntoIterator<Item = &'a Register>
+    {
+        let registers: Vec<_> = registers.into_iter().collect();
+        let mut series: BTreeMap<&str, BTreeMap<usize, &Register>> = BTreeMap::new();
+        for register in &registers {
+            if let (prefix, Some(suffix)) = extract_prefix(&register.name) {
+                if !prefix.is_empty() && register.dim_element.dim.is_none() {
+                    series.entry(prefix).or_insert_with(BTreeMap::new).insert(suffix, register);
+                }
+            }
+        }
+
+        // The series by first suffix, count and stride
+        let mut similar_series: BTreeMap<(usize, usize, u64), Vec<Vec<&Register>>> =
+            BTreeMap::new();
+        for registers in series.values() {
+            let registers: Vec<_> = registers.iter().collect();
+            let mut start = 0;
+            while start + 1 < registers.len() {
+                let (first_suffix, first) = registers[start];
+                let (_, second) = registers[start + 1];
+                if second.address_offset.0 <= first.address_offset.0 {
+                    start += 1;
+                    continue;
+                }
+                let increment = second.address_offset.0 - first.address_offset.0;
+                let count = registers[start..]
+                    .iter()
+                    .enumerate()
+                    .take_while(|&(i, &(&suffix, register))| {
+                        let offset = first.address_offset.0 + i as u64 * increment;
+                        let mut relative = Register::clone(register);
+                        relative.name = first.name.clone();
+                        relative.address_offset = first.address_offset;
+                        suffix == first_suffix + i && register.address_offset.0 == offset &&
+                        relative.is_similar(first, &IsSimilarOptions::new())
+                    })
+                    .count();
+                if count > 1 {
+                    similar_series.entry((*first_suffix, count, increment))
+                        .or_insert_with(Vec::new)
+                        .push(registers[start..start + count].iter().map(|&(_, &r)| r).collect());
+                }
+                start += count;
+            }
+        }
+
+        // The names of the registers and of the groups, their registers and their accessors, which
+        // must all be different
+        let mut names: HashSet<String> = registers.iter().map(|r| r.name.clone()).collect();
+        let mut groups = vec![];
+        let mut grouped = HashSet::new();
+        for ((first_index, count, increment), mut series) in similar_series {
+            // The series are split into elements whose registers all fit in the stride
+            series.sort_by_key(|s| s[0].address_offset.0);
+            let mut elements: Vec<Vec<Vec<&Register>>> = vec![];
+            for s in series {
+                let fits = elements.last().map_or(false, |element| {
+                    let start = element[0][0].address_offset.0;
+                    s[0].address_offset.0 - start + (s[0].size() + 7) / 8 <= increment
+                });
+                if fits {
+                    elements.last_mut().unwrap().push(s);
+                } else {
+                    elements.push(vec![s]);
+                }
+            }
+
+            for element in elements.into_iter().filter(|e| e.len() > 1) {
+                let prefixes: Vec<_> =
+                    element.iter().map(|s| extract_prefix(&s[0].name).0).collect();
+                let name = group_name(&prefixes);
+                let accessor_name = match accessor_name(&prefixes, &name, &names) {
+                    Some(accessor_name) => accessor_name,
+                    None => continue,
+                };
+                let mut new_names: HashSet<_> = prefixes.iter().map(|p| p.to_string()).collect();
+                new_names.insert(name.clone());
+                if new_names.len() != prefixes.len() + 1 || !new_names.is_disjoint(&names) {
+                    continue;
+                }
+                names.extend(new_names);
+                names.insert(accessor_name.clone());
+
+                let address_offset = element[0][0].address_offset.0;
+                let mut group_registers = vec![];
+                for (registers, prefix) in element.iter().zip(&prefixes) {
+                    let mut register = registers[0].clone();
+                    register.name = prefix.to_string();
+                    register.address_offset.0 -= address_offset;
+                    group_registers.push(register);
+                    grouped.extend(registers.iter().map(|r| &*r.name));
+                }
+                groups.push(RegistersGroup {
+                    name: name,
+                    accessor_name: accessor_name,
+                    first_index: first_index,
+                    count: count,
+                    address_offset: address_offset,
+                    increment: increment,
+                    registers: group_registers,
+                });
+            }
+        }
+        groups.sort_by_key(|g| g.address_offset);
+
+        let individuals = registers.into_iter()
+            .filter(|r| !grouped.contains(&*r.name))
+            .cloned()
+            .collect();
+        (groups, individuals)
+    }
+
+    /// The name of the group: the prefix common to the names of its registers if it has at least
+    /// two characters, or these names joined by `_`.
+    pub fn name(&self) -> &str {
+        &*self.name
+    }
+
+    /// The name of the function returning an element from its index: the common prefix of the
+    /// names of the registers in lower case if it has at least two characters, `ch` if it is `C`,
+    /// as for the registers of DMA channels, or else the name of the group in lower case.
+    pub fn accessor_name(&self) -> &str {
+        &*self.accessor_name
+    }
+
+    /// The suffix of the registers of the first element.
+    pub fn first_index(&self) -> usize {
+        self.first_index
+    }
+
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    /// The address offset of the first element.
+    pub fn address_offset(&self) -> u64 {
+        self.address_offset
+    }
+
+    pub fn increment(&self) -> u64 {
+        self.increment
+    }
+
+    /// The registers of an element, named without suffix and with an address offset relative to
+    /// the element.
+    pub fn registers(&self) -> &[Register] {
+        &*self.registers
+    }
+}
+
+/// Returns the prefix common to all the `prefixes`, without trailing `_`.
+fn common_prefix<'a>(prefixes: &[&'a str]) -> &'a str {
+    let mut common = prefixes[0];
+    for prefix in &prefixes[1..] {
+        let len = common.chars().zip(prefix.chars()).take_while(|&(a, b)| a == b).count();
+        common = &common[..len];
+    }
+    common.trim_right_matches('_')
+}
+
+fn group_name(prefixes: &[&str]) -> String {
+    let name = common_prefix(prefixes);
+    if name.len() >= 2 && !prefixes.contains(&name) {
+        name.to_string()
+    } else {
+        prefixes.join("_")
+    }
+}
+
+const KEYWORDS: &'static [&'static str] = &["abstract", "alignof", "as", "become", "box", "break",
+                                            "const", "continue", "crate", "do", "else", "enum",
+                                            "extern", "false", "final", "fn", "for", "if", "impl",
+                                            "in", "let", "loop", "macro", "match", "mod", "move",
+                                            "mut", "offsetof", "override", "priv", "proc", "pub",
+                                            "pure", "ref", "return", "self", "sizeof", "static",
+                                            "struct", "super", "trait", "true", "type", "typeof",
+                                            "unsafe", "unsized", "use", "virtual", "where",
+                                            "while", "yield"];
+
+/// The accessor names of the groups whose registers only share a one-letter prefix, by prefix. The
+/// vendors name the registers of the channels of DMA controllers and timers `CCR`, `CNDTR`...
+const ONE_LETTER_PREFIXES: &'static [(&'static str, &'static str)] = &[("C", "ch")];
+
+/// Returns the name of the accessor of a group named `name` with registers named `prefixes`, or
+/// `None` if the possible names are keywords or are already in `names`.
+fn accessor_name(prefixes: &[&str], name: &str, names: &HashSet<String>) -> Option<String> {
+    let prefix = common_prefix(prefixes);
+    let prefix_name = if prefix.len() >= 2 {
+        Some(prefix.to_lowercase())
+    } else {
+        ONE_LETTER_PREFIXES.iter().find(|&&(p, _)| p == prefix).map(|&(_, n)| n.to_string())
+    };
+    prefix_name.into_iter()
+        .chain(Some(name.to_lowercase()))
+        .find(|c| *c != name && !KEYWORDS.contains(&&**c) && !names.contains(c))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builder() {
+        use access::Access;
+
+        let field = |name: &str, bits| Field::builder(name, bits).build().unwrap();
+        let register = Register::builder("CR", 0x4)
+            .description("Control")
+            .size(8)
+            .access(Access::ReadOnly)
+            .reset_value(0x80)
+            .field(field("EN", 0..=0))
+            .field(field("MODE", 5..=7))
+            .build()
+            .unwrap();
+        assert_eq!(ScaledNonNegativeInteger(4), register.address_offset);
+        assert_eq!(8, register.size());
+        assert_eq!(2, register.fields.as_ref().unwrap().len());
+        assert_eq!(register, Register::from_element(&register.to_element()).unwrap());
+
+        // The fields must fit in the register when its size is set
+        assert!(Register::builder("CR", 0x0).size(8).field(field("MODE", 6..=8)).build().is_err());
+        assert!(Register::builder("CR", 0x0).field(field("MODE", 6..=8)).build().is_ok());
+        assert!(Register::builder("", 0x0).build().is_err());
+    }
+
+    #[test]
+    fn decode() {
+        let field = |name: &str, bits| Field::builder(name, bits).build().unwrap();
+        let register = Register::builder("CCR", 0x0)
+            .field(field("EN", 0..=0))
+            .field(field("PL", 12..=13))
+            .build()
+            .unwrap();
+        let decoded: Vec<_> = register.decode(0x2001)
+            .iter()
+            .map(|d| (&*d.field.name, d.value, d.enumerated_value.is_some()))
+            .collect();
+        assert_eq!(vec![("EN", 1, false), ("PL", 2, false)], decoded);
+        assert!(Register::builder("DR", 0x4).build().unwrap().decode(!0).is_empty());
+    }
+
+    #[test]
+    fn bits_masks() {
+        let field = |name: &str, bits| Field::builder(name, bits).build().unwrap();
+        let isr = Register::builder("ISR", 0x0)
+            .field(field("GIF1", 0..=0))
+            .field(field("TCIF1", 1..=1))
+            .field(field("HTIF1", 4..=5))
+            .build()
+            .unwrap();
+        assert_eq!(0b11_0011, isr.defined_bits_mask());
+        assert_eq!(0xffff_ffcc, isr.reserved_bits_mask());
+
+        // All the bits of a register without fields are defined
+        let dr = Register::builder("DR", 0x4).size(16).build().unwrap();
+        assert_eq!(0xffff, dr.defined_bits_mask());
+        assert_eq!(0, dr.reserved_bits_mask());
+        let dr = Register::builder("DR", 0x4).size(64).build().unwrap();
+        assert_eq!(!0, dr.defined_bits_mask());
+        assert_eq!(0, dr.reserved_bits_mask());
+    }
+
+    #[test]
+    fn field_reset_values() {
+        let field = |name: &str, bits| Field::builder(name, bits).build().unwrap();
+        let register = |reset_value: Option<u64>, reset_mask: Option<u64>| {
+            let mut register = Register::builder("CR", 0x0)
+                .field(field("EN", 0..=0))
+                .field(field("MODE", 4..=6))
+                .build()
+                .unwrap();
+            register.register_properties.reset_value = reset_value.map(ScaledNonNegativeInteger);
+            register.register_properties.reset_mask = reset_mask.map(ScaledNonNegativeInteger);
+            register
+        };
+        let reset_values = |register: &Register| {
+            register.fields
+                .iter()
+                .flat_map(|fields| fields)
+                .map(|f| register.field_reset_value(f))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(vec![Some(1), Some(0b101)], reset_values(&register(Some(0x51), None)));
+        assert_eq!(vec![None, None], reset_values(&register(None, Some(!0))));
+        // MODE is only partly covered by the reset mask
+        assert_eq!(vec![Some(1), None], reset_values(&register(Some(0x51), Some(0x31))));
+        assert_eq!(vec![None, Some(0b101)], reset_values(&register(Some(0x51), Some(0x70))));
+    }
+
+    #[test]
+    fn registers_groups() {
+        let register = |name: &str, offset| Register::builder(name, offset).build().unwrap();
+        let mut registers = vec![register("ISR", 0x0), register("IFCR", 0x4)];
+        for channel in 1..8 {
+            let offset = 0x8 + (channel - 1) * 0x14;
+            registers.push(register(&format!("CCR{}", channel), offset));
+            registers.push(register(&format!("CNDTR{}", channel), offset + 0x4));
+            registers.push(register(&format!("CPAR{}", channel), offset + 0x8));
+        }
+        // Not at the stride of the other channels
+        registers.push(register("CCR8", 0xa0));
+        registers.push(register("CNDTR8", 0xa4));
+        registers.push(register("CPAR8", 0xa8));
+        // Not similar
+        registers.push(Register::builder("DR1", 0xb0).size(16).build().unwrap());
+        registers.push(register("SR1", 0xb4));
+        registers.push(register("DR2", 0xb8));
+        registers.push(register("SR2", 0xbc));
+
+        let (groups, individuals) = RegistersGroup::from_registers(&registers);
+        assert_eq!(1, groups.len());
+        let group = &groups[0];
+        assert_eq!("CCR_CNDTR_CPAR", group.name());
+        assert_eq!("ch", group.accessor_name());
+        assert_eq!((1, 7), (group.first_index(), group.count()));
+        assert_eq!((0x8, 0x14), (group.address_offset(), group.increment()));
+        let registers: Vec<_> = group.registers()
+            .iter()
+            .map(|r| (&*r.name, r.address_offset.0))
+            .collect();
+        assert_eq!(vec![("CCR", 0x0), ("CNDTR", 0x4), ("CPAR", 0x8)], registers);
+        let individuals: Vec<_> = individuals.iter().map(|r| &*r.name).collect();
+        assert_eq!(vec!["ISR", "IFCR", "CCR8", "CNDTR8", "CPAR8", "DR1", "SR1", "DR2", "SR2"],
+                   individuals);
+    }
+
+    #[test]
+    fn registers_groups_fit_in_stride() {
+        let register = |name: &str, offset| Register::builder(name, offset).build().unwrap();
+        // JOFR1-4 and JDR1-4 have the same suffixes and stride but are far apart, SQR1-4 and
+        // SMPR1-4 are interleaved. Their one-letter prefix is not used as accessor name.
+        let mut registers = vec![];
+        for i in 0..4 {
+            registers.push(register(&format!("JOFR{}", i + 1), 0x14 + i * 4));
+            registers.push(register(&format!("JDR{}", i + 1), 0x3c + i * 4));
+            registers.push(register(&format!("SQR{}", i + 1), 0x80 + i * 8));
+            registers.push(register(&format!("SMPR{}", i + 1), 0x84 + i * 8));
+        }
+
+        let (groups, individuals) = RegistersGroup::from_registers(&registers);
+        assert_eq!(1, groups.len());
+        assert_eq!(("SQR_SMPR", "sqr_smpr"), (groups[0].name(), groups[0].accessor_name()));
+        assert_eq!((0x80, 8), (groups[0].address_offset(), groups[0].increment()));
+        assert_eq!(8, individuals.len());
+        assert!(individuals.iter().all(|r| r.name.starts_with('J')));
+    }
+
+    #[test]
+    fn registers_groups_names() {
+        let register = |name: &str, offset| Register::builder(name, offset).build().unwrap();
+        // The group would rename DR1 and DR2 to DR
+        let registers = vec![register("DR", 0x0),
+                             register("DR1", 0x4),
+                             register("SR1", 0x8),
+                             register("DR2", 0xc),
+                             register("SR2", 0x10)];
+        let (groups, individuals) = RegistersGroup::from_registers(&registers);
+        assert!(groups.is_empty());
+        assert_eq!(registers, individuals);
+
+        // Without common prefix, the accessor is named after the group
+        let registers = vec![register("TXDR1", 0x0),
+                             register("RXDR1", 0x4),
+                             register("TXDR2", 0x8),
+                             register("RXDR2", 0xc)];
+        let (groups, _) = RegistersGroup::from_registers(&registers);
+        assert_eq!(("TXDR_RXDR", "txdr_rxdr"), (groups[0].name(), groups[0].accessor_name()));
+
+        // The accessor is named after the group when `ch` is already used
+        let registers = vec![register("CCR1", 0x0),
+                             register("CNDTR1", 0x4),
+                             register("CCR2", 0x8),
+                             register("CNDTR2", 0xc),
+                             register("ch", 0x10)];
+        let (groups, _) = RegistersGroup::from_registers(&registers);
+        assert_eq!(("CCR_CNDTR", "ccr_cndtr"), (groups[0].name(), groups[0].accessor_name()));
+
+        // `in` is a keyword
+        let registers = vec![register("INA1", 0x0),
+                             register("INB1", 0x4),
+                             register("INA2", 0x8),
+                             register("INB2", 0xc)];
+        let (groups, individuals) = RegistersGroup::from_registers(&registers);
+        assert!(groups.is_empty());
+        assert_eq!(4, individuals.len());
+    }
+}
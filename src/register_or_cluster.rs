@@ -1,11 +1,12 @@
 use cluster::Cluster;
 use errors::*;
-use is_similar::{IsSimilar, IsSimilarOptions};
+use is_similar::{Difference, ExplainDifferences, IsSimilar, IsSimilarOptions};
 use register::Register;
 use register_properties_group::RegisterPropertiesGroup;
 use types::*;
 use xmltree;
 
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub enum RegisterOrCluster {
     Register(Register),
@@ -25,6 +26,13 @@ impl RegisterOrCluster {
         }
     }
 
+    pub fn to_element(&self) -> xmltree::Element {
+        match *self {
+            RegisterOrCluster::Register(ref r) => r.to_element(),
+            RegisterOrCluster::Cluster(ref c) => c.to_element(),
+        }
+    }
+
     pub fn derived_from(&self) -> &Option<IdentifierType> {
         match *self {
             RegisterOrCluster::Register(ref r) => &r.derived_from,
@@ -87,3 +95,20 @@ impl<'a, 'b> IsSimilar<&'a RegisterOrCluster> for &'b RegisterOrCluster {
         }
     }
 }
+
+impl<'a, 'b> ExplainDifferences<&'a RegisterOrCluster> for &'b RegisterOrCluster {
+    fn explain_differences(self,
+                           other: &RegisterOrCluster,
+                           options: &IsSimilarOptions)
+                           -> Vec<Difference> {
+        match (self, other) {
+            (&RegisterOrCluster::Register(ref r1), &RegisterOrCluster::Register(ref r2)) => {
+                r1.explain_differences(r2, options)
+            }
+            (&RegisterOrCluster::Cluster(ref c1), &RegisterOrCluster::Cluster(ref c2)) => {
+                c1.explain_differences(c2, options)
+            }
+            _ => vec![Difference::new(self.name(), "kind")],
+        }
+    }
+}
@@ -1,12 +1,17 @@
 use access::Access;
 use errors::*;
-use is_similar::{IsSimilar, IsSimilarOptions};
+use is_similar::{Difference, ExplainDifferences, IsSimilar, IsSimilarOptions};
 use protection::Protection;
 use types::*;
-use utils::get_child_text;
+use utils::{add_child_text, add_optional_child_text, get_child_text};
 use xmltree;
 
-#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+/// The names of the elements of this group.
+pub const ELEMENT_NAMES: &'static [&'static str] = &["size", "access", "protection", "resetValue",
+                                                     "resetMask"];
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
 pub struct RegisterPropertiesGroup {
     pub size: Option<ScaledNonNegativeInteger>,
     pub access: Option<Access>,
@@ -46,6 +51,19 @@ impl RegisterPropertiesGroup {
         })
     }
 
+    /// Appends the elements of this group to `element`.
+    pub fn add_to_element(&self, element: &mut xmltree::Element) {
+        add_optional_child_text(element, "size", &self.size);
+        add_optional_child_text(element, "access", &self.access);
+        add_optional_child_text(element, "protection", &self.protection);
+        if let Some(reset_value) = self.reset_value {
+            add_child_text(element, "resetValue", format!("0x{:08X}", reset_value));
+        }
+        if let Some(reset_mask) = self.reset_mask {
+            add_child_text(element, "resetMask", format!("0x{:08X}", reset_mask));
+        }
+    }
+
     pub fn merge(&self, other: &RegisterPropertiesGroup) -> RegisterPropertiesGroup {
         RegisterPropertiesGroup {
             size: self.size.or(other.size),
@@ -58,8 +76,29 @@ impl RegisterPropertiesGroup {
 }
 
 impl<'a, 'b> IsSimilar<&'a RegisterPropertiesGroup> for &'b RegisterPropertiesGroup {
-    fn is_similar(self, other: &RegisterPropertiesGroup, _options: &IsSimilarOptions) -> bool {
+    fn is_similar(self, other: &RegisterPropertiesGroup, options: &IsSimilarOptions) -> bool {
         self.size == other.size && self.access == other.access &&
-        self.protection == other.protection
+        self.protection == other.protection &&
+        (options.ignore_reset_values() ||
+         self.reset_value == other.reset_value && self.reset_mask == other.reset_mask)
+    }
+}
+
+impl<'a, 'b> ExplainDifferences<&'a RegisterPropertiesGroup> for &'b RegisterPropertiesGroup {
+    /// The differences have an empty path.
+    fn explain_differences(self,
+                           other: &RegisterPropertiesGroup,
+                           options: &IsSimilarOptions)
+                           -> Vec<Difference> {
+        let compare_resets = !options.ignore_reset_values();
+        let properties = [(self.size != other.size, "size"),
+                          (self.access != other.access, "access"),
+                          (self.protection != other.protection, "protection"),
+                          (compare_resets && self.reset_value != other.reset_value, "resetValue"),
+                          (compare_resets && self.reset_mask != other.reset_mask, "resetMask")];
+        properties.iter()
+            .filter(|&&(differs, _)| differs)
+            .map(|&(_, property)| Difference::new("", property))
+            .collect()
     }
 }
@@ -0,0 +1,212 @@
+use address_lookup::ClusterInstance;
+use device::Device;
+use dim_element_group::DimElementGroup;
+use field::Field;
+use peripheral::Peripheral;
+use register::Register;
+use register_or_cluster::RegisterOrCluster;
+
+/// The elements named by a dotted path such as `TIM2.CCMR1.OC1M`, as returned by
+/// `Device::resolve`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ResolvedPath<'a> {
+    pub peripheral: &'a Peripheral,
+    /// The index of the peripheral if it is an element of a peripheral array.
+    pub peripheral_index: Option<String>,
+    /// The clusters named by the path, from the outermost to the innermost.
+    pub clusters: Vec<ClusterInstance<'a>>,
+    /// The register named by the path, with its index if it is an element of a register array.
+    pub register: Option<(&'a Register, Option<String>)>,
+    pub field: Option<&'a Field>,
+}
+
+/// Resolves a dotted path made of a peripheral name, any number of cluster names, a register
+/// name and a field name. The path can stop after any of these names.
+///
+/// The elements of an array can be named either with their expanded name (`CH3` for an array
+/// named `CH%s`) or with their index between brackets (`CH[3]`).
+pub fn resolve<'a>(device: &'a Device, path: &str) -> Option<ResolvedPath<'a>> {
+    let mut segments = path.split('.');
+    let first = match segments.next() {
+        Some(first) => first,
+        None => return None,
+    };
+
+    for peripheral in &device.peripherals {
+        if let Some(peripheral_index) = find_element(&peripheral.name,
+                                                     &peripheral.dim_element,
+                                                     first) {
+            let mut resolved = ResolvedPath {
+                peripheral: peripheral,
+                peripheral_index: peripheral_index,
+                clusters: vec![],
+                register: None,
+                field: None,
+            };
+            let registers = peripheral.registers.as_ref().map_or(&[][..], |r| &**r);
+            return if resolve_in(registers, segments, &mut resolved) {
+                Some(resolved)
+            } else {
+                None
+            };
+        }
+    }
+
+    None
+}
+
+fn resolve_in<'a, 'b, I>(registers: &'a [RegisterOrCluster],
+                         mut segments: I,
+                         resolved: &mut ResolvedPath<'a>)
+                         -> bool
+    where I: Iterator<Item = &'b str>
+{
+    let segment = match segments.next() {
+        Some(segment) => segment,
+        None => return true,
+    };
+
+    for r_or_c in registers {
+        match *r_or_c {
+            RegisterOrCluster::Register(ref r) => {
+                if let Some(index) = find_element(&r.name, &r.dim_element, segment) {
+                    resolved.register = Some((r, index));
+                    return resolve_field(r, segments, resolved);
+                }
+            }
+            RegisterOrCluster::Cluster(ref c) => {
+                if let Some(index) = find_element(&c.name, &c.dim_element, segment) {
+                    resolved.clusters.push(ClusterInstance {
+                        cluster: c,
+                        index: index,
+                    });
+                    return resolve_in(&c.registers, segments, resolved);
+                }
+            }
+        }
+    }
+
+    false
+}
+
+fn resolve_field<'a, 'b, I>(register: &'a Register,
+                            mut segments: I,
+                            resolved: &mut ResolvedPath<'a>)
+                            -> bool
+    where I: Iterator<Item = &'b str>
+{
+    let segment = match segments.next() {
+        Some(segment) => segment,
+        None => return true,
+    };
+
+    let field = register.fields.iter().flat_map(|fields| fields).find(|f| f.name == segment);
+    resolved.field = field;
+    field.is_some() && segments.next().is_none()
+}
+
+/// Returns the index of the element named `segment` of the array `name` or, if the element is not
+/// an array, `Some(None)` if `segment` is `name`.
+fn find_element(name: &str,
+                dim_element: &DimElementGroup,
+                segment: &str)
+                -> Option<Option<String>> {
+    let base_name = name.replace("[%s]", "").replace("%s", "");
+    for (index, _) in dim_element.elements() {
+        if DimElementGroup::element_name(name, &index) == segment {
+            return Some(index);
+        }
+        if let Some(ref index) = index {
+            if segment == format!("{}[{}]", base_name, index) {
+                return Some(Some(index.clone()));
+            }
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use device::Device;
+
+    const PERIPHERALS: &'static str = r#"
+        <peripheral>
+            <dim>2</dim><dimIncrement>0x400</dimIncrement><dimIndex>1-2</dimIndex>
+            <name>DMA%s</name><baseAddress>0x1000</baseAddress><registers>
+            <register><name>ISR</name><addressOffset>0x0</addressOffset>
+                <fields><field><name>GIF1</name><bitRange>[0:0]</bitRange></field></fields>
+            </register>
+            <cluster>
+                <dim>2</dim><dimIncrement>0x40</dimIncrement><dimIndex>A,B</dimIndex>
+                <name>CH%s</name><addressOffset>0x8</addressOffset>
+                <cluster><name>CFG</name><addressOffset>0x0</addressOffset>
+                    <register>
+                        <dim>4</dim><dimIncrement>0x4</dimIncrement>
+                        <name>CCR[%s]</name><addressOffset>0x0</addressOffset>
+                        <fields><field><name>PL</name><bitRange>[1:0]</bitRange></field></fields>
+                    </register>
+                </cluster>
+            </cluster>
+        </registers></peripheral>
+        <peripheral><name>RCC</name><baseAddress>0x2000</baseAddress></peripheral>"#;
+
+    fn device() -> Device {
+        let xml = format!("<device><name>TEST</name><version>1.0</version>\
+                           <description>Test</description>\
+                           <addressUnitBits>8</addressUnitBits><width>32</width>\
+                           <peripherals>{}</peripherals></device>",
+                          PERIPHERALS);
+        Device::from_reader(xml.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn paths() {
+        let d = device();
+
+        let resolved = d.resolve("DMA2.CHB.CFG.CCR[3].PL").unwrap();
+        assert_eq!(Some("2"), resolved.peripheral_index.as_ref().map(|i| &**i));
+        let clusters: Vec<_> = resolved.clusters
+            .iter()
+            .map(|c| (&*c.cluster.name, c.index.as_ref().map(|i| &**i)))
+            .collect();
+        assert_eq!(vec![("CH%s", Some("B")), ("CFG", None)], clusters);
+        let (register, index) = resolved.register.unwrap();
+        assert_eq!(("CCR[%s]", Some("3")), (&*register.name, index.as_ref().map(|i| &**i)));
+        assert_eq!("PL", resolved.field.unwrap().name);
+
+        // The path can stop at any level
+        let resolved = d.resolve("DMA1.CHA").unwrap();
+        assert_eq!(1, resolved.clusters.len());
+        assert!(resolved.register.is_none());
+        assert_eq!("RCC", d.resolve_peripheral("RCC").unwrap().name);
+        assert_eq!("GIF1", d.resolve_field("DMA1.ISR.GIF1").unwrap().name);
+    }
+
+    #[test]
+    fn indices() {
+        let d = device();
+        assert!(d.resolve("DMA[2].CH[A].CFG").is_some());
+        assert_eq!(d.resolve("DMA2.CHB"), d.resolve("DMA[2].CH[B]"));
+        assert!(d.resolve_register("DMA1.CHA.CFG.CCR[0]").is_some());
+        // Out of the range of the array, or the name of the array itself
+        assert!(d.resolve("DMA3").is_none());
+        assert!(d.resolve("DMA1.CHC").is_none());
+        assert!(d.resolve("DMA1.CHA.CFG.CCR[4]").is_none());
+        assert!(d.resolve("DMA%s").is_none());
+    }
+
+    #[test]
+    fn unknown_paths() {
+        let d = device();
+        assert!(d.resolve("").is_none());
+        assert!(d.resolve("GPIOA").is_none());
+        assert!(d.resolve("RCC.CR").is_none());
+        assert!(d.resolve("DMA1.ISR.TCIF1").is_none());
+        assert!(d.resolve("DMA1.ISR.GIF1.FOO").is_none());
+        assert!(d.resolve("DMA1..ISR").is_none());
+        // A register is not a peripheral, and a field is not a register
+        assert!(d.resolve_peripheral("DMA1.ISR").is_none());
+        assert!(d.resolve_register("DMA1.ISR.GIF1").is_none());
+    }
+}
@@ -13,8 +13,12 @@ macro_rules! str_enum {
     };
     ( $name:ident,  $( $str:expr => $variant:ident ),* ) => {
         #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+        #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
         pub enum $name {
-            $($variant),*
+            $(
+                #[cfg_attr(feature = "serde", serde(rename = $str))]
+                $variant
+            ),*
         }
 
         impl FromStr for $name {
@@ -33,5 +37,20 @@ macro_rules! str_enum {
 
             }
         }
+
+        impl $name {
+            /// Returns the string used for this value in SVD files.
+            pub fn as_str(&self) -> &'static str {
+                match *self {
+                    $($name::$variant => $str, )*
+                }
+            }
+        }
+
+        impl ::std::fmt::Display for $name {
+            fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+                f.write_str(self.as_str())
+            }
+        }
     };
 }
@@ -1,32 +1,83 @@
 use errors::*;
+use std::fmt;
 use std::str::FromStr;
 
 pub type IdentifierType = String;
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
 pub struct ScaledNonNegativeInteger(pub u64);
 
 impl FromStr for ScaledNonNegativeInteger {
     type Err = Error;
 
+    /// Parses a value of the form `[+]?(0x|0X|#)?[0-9a-fA-F]+[kmgtKMGT]?`, surrounded by optional
+    /// whitespace. The `k`, `m`, `g` and `t` suffixes multiply the value by 2^10, 2^20, 2^30 and
+    /// 2^40.
     fn from_str(s: &str) -> Result<ScaledNonNegativeInteger> {
-        let s = if s.starts_with('+') { &s[1..] } else { s };
-
-        let parsed = if s.starts_with('#') {
-            let s = &s[1..];
-            u64::from_str_radix(s, 2)
-        } else if s.starts_with("0x") || s.starts_with("0X") {
-            let s = &s[2..];
-            u64::from_str_radix(s, 16)
+        let trimmed = s.trim();
+        let trimmed = if trimmed.starts_with('+') { &trimmed[1..] } else { trimmed };
+
+        let (trimmed, shift) = match trimmed.chars().last() {
+            Some('k') | Some('K') => (&trimmed[..trimmed.len() - 1], 10),
+            Some('m') | Some('M') => (&trimmed[..trimmed.len() - 1], 20),
+            Some('g') | Some('G') => (&trimmed[..trimmed.len() - 1], 30),
+            Some('t') | Some('T') => (&trimmed[..trimmed.len() - 1], 40),
+            _ => (trimmed, 0),
+        };
+
+        let (digits, radix) = if trimmed.starts_with('#') {
+            (&trimmed[1..], 2)
+        } else if trimmed.starts_with("0x") || trimmed.starts_with("0X") {
+            (&trimmed[2..], 16)
         } else {
-            u64::from_str_radix(s, 10)
+            (trimmed, 10)
         };
+        // `from_str_radix` accepts a sign of its own, which would let `++5` or `0x+5` through
+        if digits.starts_with('+') || digits.starts_with('-') {
+            return Err(ErrorKind::UnexpectedValue("an unsigned integer", s.to_string()).into());
+        }
+        let value = try!(u64::from_str_radix(digits, radix));
 
-        match parsed {
-            Ok(v) => Ok(ScaledNonNegativeInteger(v)),
-            Err(e) => Err(e.into()),
+        if value.leading_zeros() < shift {
+            Err(ErrorKind::UnexpectedValue("an integer that fits in 64 bits", s.to_string()).into())
+        } else {
+            Ok(ScaledNonNegativeInteger(value << shift))
         }
+    }
+}
+
+impl fmt::Display for ScaledNonNegativeInteger {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)
+    }
+}
+
+impl fmt::UpperHex for ScaledNonNegativeInteger {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::UpperHex::fmt(&self.0, f)
+    }
+}
 
+/// Parses an integer that can be negative. The syntax is the same as for
+/// `ScaledNonNegativeInteger`, with an optional leading `-`.
+pub fn parse_integer(s: &str) -> Result<i64> {
+    let trimmed = s.trim();
+    let (negative, trimmed) = if trimmed.starts_with('-') {
+        (true, &trimmed[1..])
+    } else {
+        (false, trimmed)
+    };
+
+    let magnitude: ScaledNonNegativeInteger = try!(trimmed.parse());
+    let magnitude = magnitude.0;
+    if negative && magnitude <= i64::max_value() as u64 + 1 {
+        Ok((magnitude as i64).wrapping_neg())
+    } else if !negative && magnitude <= i64::max_value() as u64 {
+        Ok(magnitude as i64)
+    } else {
+        Err(ErrorKind::UnexpectedValue("an integer that fits in 64 bits", s.to_string()).into())
     }
 }
 
@@ -55,11 +106,50 @@ mod tests {
                    ScaledNonNegativeInteger::from_str("#10").ok());
     }
 
+    #[test]
+    fn scaled() {
+        assert_eq!(Some(ScaledNonNegativeInteger(4 * 1024)),
+                   ScaledNonNegativeInteger::from_str("4k").ok());
+        assert_eq!(Some(ScaledNonNegativeInteger(1024 * 1024)),
+                   ScaledNonNegativeInteger::from_str("0x1M").ok());
+        assert_eq!(Some(ScaledNonNegativeInteger(2 << 30)),
+                   ScaledNonNegativeInteger::from_str("#10G").ok());
+        assert_eq!(Some(ScaledNonNegativeInteger(1 << 40)),
+                   ScaledNonNegativeInteger::from_str("1t").ok());
+    }
+
+    #[test]
+    fn misplaced_sign() {
+        assert!(ScaledNonNegativeInteger::from_str("++5").is_err());
+        assert!(ScaledNonNegativeInteger::from_str("0x+5").is_err());
+        assert!(ScaledNonNegativeInteger::from_str("#-1").is_err());
+        assert!(ScaledNonNegativeInteger::from_str("+-5").is_err());
+        assert!(parse_integer("--5").is_err());
+        assert_eq!(-5, parse_integer("-5").unwrap());
+    }
+
+    #[test]
+    fn whitespace() {
+        assert_eq!(Some(ScaledNonNegativeInteger(16)),
+                   ScaledNonNegativeInteger::from_str(" +0x10\n").ok());
+    }
+
     #[test]
     fn invalid() {
         assert!(ScaledNonNegativeInteger::from_str("").is_err());
         assert!(ScaledNonNegativeInteger::from_str("a").is_err());
         assert!(ScaledNonNegativeInteger::from_str("0xg").is_err());
         assert!(ScaledNonNegativeInteger::from_str("#2").is_err());
+        assert!(ScaledNonNegativeInteger::from_str("k").is_err());
+        assert!(ScaledNonNegativeInteger::from_str("0x1000000T").is_err());
+    }
+
+    #[test]
+    fn integer() {
+        assert_eq!(Some(-1), parse_integer("-1").ok());
+        assert_eq!(Some(-16), parse_integer(" -0x10 ").ok());
+        assert_eq!(Some(42), parse_integer("0x2A").ok());
+        assert_eq!(Some(i64::min_value()), parse_integer("-0x8000000000000000").ok());
+        assert!(parse_integer("0x8000000000000000").is_err());
     }
 }
@@ -1,3 +1,5 @@
+use std::collections::HashMap;
+use std::fmt::Display;
 use xmltree;
 
 pub fn extract_prefix(name: &str) -> (&str, Option<usize>) {
@@ -12,6 +14,43 @@ pub fn get_child_text(element: &xmltree::Element, name: &str) -> Option<String>
     element.get_child(name).map(|child| child.text.clone().unwrap_or_else(String::new))
 }
 
+pub fn new_element(name: &str) -> xmltree::Element {
+    xmltree::Element {
+        name: name.to_string(),
+        attributes: HashMap::new(),
+        children: vec![],
+        text: None,
+    }
+}
+
+pub fn add_child_text<T: Display>(element: &mut xmltree::Element, name: &str, text: T) {
+    let mut child = new_element(name);
+    child.text = Some(text.to_string());
+    element.children.push(child);
+}
+
+pub fn add_optional_child_text<T: Display>(element: &mut xmltree::Element,
+                                           name: &str,
+                                           text: &Option<T>) {
+    if let Some(ref text) = *text {
+        add_child_text(element, name, text);
+    }
+}
+
+/// Returns `base_address + address_offset + offset`, or `None` if it overflows.
+pub fn offset_address(base_address: u64, address_offset: u64, offset: u64) -> Option<u64> {
+    base_address.checked_add(address_offset).and_then(|a| a.checked_add(offset))
+}
+
+/// Returns the segment identifying `element` in an element path: its name followed by the value
+/// of its `name` child or, if there is none, by its index among its siblings.
+pub fn element_path_segment(element: &xmltree::Element, index: usize) -> String {
+    match get_child_text(element, "name") {
+        Some(name) => format!("{}[{}]", element.name, name.trim()),
+        None => format!("{}[{}]", element.name, index),
+    }
+}
+
 macro_rules! merge_option_field {
     ( $field:expr,  $field_derived_from:expr) => {
         if $field.is_none() {
@@ -30,6 +69,40 @@ macro_rules! get_mandatory_child_text {
     };
 }
 
+/// Defines the setters of the register properties of a builder. `$value` is the field of the
+/// builder holding the value being built.
+macro_rules! register_properties_setters {
+    ( $builder:ident, $value:ident ) => {
+        pub fn size(mut self, size: u64) -> $builder {
+            self.$value.register_properties.size =
+                Some($crate::types::ScaledNonNegativeInteger(size));
+            self
+        }
+
+        pub fn access(mut self, access: $crate::access::Access) -> $builder {
+            self.$value.register_properties.access = Some(access);
+            self
+        }
+
+        pub fn protection(mut self, protection: $crate::protection::Protection) -> $builder {
+            self.$value.register_properties.protection = Some(protection);
+            self
+        }
+
+        pub fn reset_value(mut self, reset_value: u64) -> $builder {
+            self.$value.register_properties.reset_value =
+                Some($crate::types::ScaledNonNegativeInteger(reset_value));
+            self
+        }
+
+        pub fn reset_mask(mut self, reset_mask: u64) -> $builder {
+            self.$value.register_properties.reset_mask =
+                Some($crate::types::ScaledNonNegativeInteger(reset_mask));
+            self
+        }
+    };
+}
+
 
 #[cfg(test)]
 mod tests {
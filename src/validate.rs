@@ -0,0 +1,964 @@
+//! Checks of the consistency of a device.
+//!
+//! `Device::validate` applies the following rules. Each problem found is reported as a `Finding`
+//! named after the rule that found it:
+//!
+//! * `register-overlap` (error): two registers of a peripheral overlap, unless one of them is an
+//!   alternate of the other (`alternateRegister`) or is part of an alternate group
+//!   (`alternateGroup`),
+//! * `register-outside-address-block` (warning): a register is not entirely inside one of the
+//!   address blocks of its peripheral. Peripherals without address blocks are not checked,
+//! * `address-block-overlap` (error): the address blocks of two peripherals overlap, unless one
+//!   of them is an alternate of the other (`alternatePeripheral`),
+//! * `misaligned-register` (warning): the address of a register is not a multiple of its size,
+//! * `field-overlap` (error): two fields of a register share some bits,
+//! * `field-outside-register` (error): a field has bits beyond the size of its register,
+//! * `bit-range-reversed` (error): the `msb` of a field is lower than its `lsb`,
+//! * `reset-value-too-wide` (error): the reset value of a register doesn't fit in its size,
+//! * `reset-value-reserved-bits` (warning): the reset value of a register sets bits that are not
+//!   covered by any field,
+//! * `enumerated-value-too-wide` (error): an enumerated value doesn't fit in its field,
+//! * `duplicate-enumerated-value-name` (error): two enumerated values of an `enumeratedValues`
+//!   have the same name,
+//! * `duplicate-enumerated-value` (error): two enumerated values of an `enumeratedValues` have
+//!   the same value,
+//! * `dim-index-mismatch` (error): the number of indices in `dimIndex` is not `dim`,
+//! * `invalid-name` (error): the name of an element is not a valid SVD identifier,
+//! * `duplicate-name` (error): two peripherals of the device, two registers or clusters of a
+//!   peripheral or cluster, or two fields of a register have the same name, once the arrays are
+//!   expanded. Registers that are part of an alternate group are not checked,
+//! * `codegen-name-collision` (warning): the code generator would give the same name to two
+//!   items, for example the accessors of a group of fields and of another field, or would rename
+//!   a module to avoid a collision between a group of peripherals and a peripheral. The default
+//!   options of the code generator are assumed.
+//!
+//! The rules about the address map report the paths of register instances, such as
+//! `DMA1.CH3.CCR`. The other rules report the paths of the elements as they are declared, such as
+//! `DMA1.CH%s.CCR`.
+//!
+//! The rules check the device as it is. Use them on a device returned by `Device::from_reader` to
+//! take derived elements and inherited register properties into account.
+
+use device::Device;
+use dim_element_group::DimElementGroup;
+use enumerated_values::EnumeratedValueData;
+use errors::*;
+use field::{Field, FieldsGroup};
+use instances::RegisterInstance;
+use is_similar::IsSimilarOptions;
+use peripheral::{Peripheral, PeripheralsGroup};
+use register::Register;
+use register_or_cluster::RegisterOrCluster;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::str::FromStr;
+use utils::offset_address;
+
+str_enum!{Severity,
+    "warning" => Warning,
+    "error" => Error,
+}
+
+/// The names of all the rules.
+pub const RULES: &'static [&'static str] = &["register-overlap",
+                                             "register-outside-address-block",
+                                             "address-block-overlap",
+                                             "misaligned-register",
+                                             "field-overlap",
+                                             "field-outside-register",
+                                             "bit-range-reversed",
+                                             "reset-value-too-wide",
+                                             "reset-value-reserved-bits",
+                                             "enumerated-value-too-wide",
+                                             "duplicate-enumerated-value-name",
+                                             "duplicate-enumerated-value",
+                                             "dim-index-mismatch",
+                                             "invalid-name",
+                                             "duplicate-name",
+                                             "codegen-name-collision"];
+
+/// A problem found by a rule.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Finding {
+    /// The dotted path of the element with the problem, for example `DMA1.CH3.CCR`.
+    pub path: String,
+    pub severity: Severity,
+    /// The name of the rule that found the problem.
+    pub rule: &'static str,
+    pub message: String,
+}
+
+impl Finding {
+    fn new<S: Into<String>>(path: S,
+                            severity: Severity,
+                            rule: &'static str,
+                            message: String)
+                            -> Finding {
+        Finding {
+            path: path.into(),
+            severity: severity,
+            rule: rule,
+            message: message,
+        }
+    }
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f,
+               "{}: {}: {} [{}]",
+               self.severity,
+               self.path,
+               self.message,
+               self.rule)
+    }
+}
+
+/// Applies all the rules to `device`.
+pub fn validate(device: &Device) -> Vec<Finding> {
+    let mut findings = vec![];
+    check_address_map(device, &mut findings);
+    check_name(&device.name, &device.name, &mut findings);
+    let names: Vec<_> = device.peripherals
+        .iter()
+        .map(|p| (p.name.clone(), element_names(&p.name, &p.dim_element)))
+        .collect();
+    check_duplicates(&names, &mut findings);
+    check_peripherals_groups(device, &mut findings);
+    for peripheral in &device.peripherals {
+        check_name(&peripheral.name, &peripheral.name, &mut findings);
+        check_dim(&peripheral.name, &peripheral.dim_element, &mut findings);
+        if let Some(ref registers) = peripheral.registers {
+            check_declarations(&peripheral.name, registers, &mut findings);
+        }
+    }
+    findings
+}
+
+/// An address block of an element of a peripheral array, or of a peripheral that is not an array.
+struct BlockInstance<'a> {
+    peripheral: &'a Peripheral,
+    path: String,
+    start: u64,
+    end: u64,
+}
+
+fn check_address_map(device: &Device, findings: &mut Vec<Finding>) {
+    let unit_bits = if device.address_unit_bits.0 == 0 {
+        8
+    } else {
+        device.address_unit_bits.0
+    };
+
+    let mut registers = HashMap::new();
+    for register in device.registers() {
+        let peripheral_path = register.path.split('.').next().unwrap_or("").to_string();
+        registers.entry(peripheral_path).or_insert_with(Vec::new).push(register);
+    }
+
+    let mut blocks = vec![];
+    for peripheral in &device.peripherals {
+        for (index, offset) in peripheral.dim_element.elements() {
+            let path = DimElementGroup::element_name(&peripheral.name, &index);
+            // The blocks whose end does not fit in 64 bits are skipped
+            let peripheral_blocks: Vec<_> = peripheral.address_blocks
+                .iter()
+                .filter(|b| b.size.0 > 0)
+                .filter_map(|b| {
+                    let start = offset_address(peripheral.base_address.0, b.offset.0, offset);
+                    start.and_then(|start| start.checked_add(b.size.0).map(|end| (start, end)))
+                })
+                .map(|(start, end)| {
+                    BlockInstance {
+                        peripheral: peripheral,
+                        path: path.clone(),
+                        start: start,
+                        end: end,
+                    }
+                })
+                .collect();
+            if let Some(registers) = registers.get_mut(&path) {
+                check_registers(registers, &peripheral_blocks, unit_bits, findings);
+            }
+            blocks.extend(peripheral_blocks);
+        }
+    }
+
+    check_address_blocks(&mut blocks, findings);
+}
+
+/// Checks the registers of an element of a peripheral array, or of a peripheral that is not an
+/// array.
+fn check_registers(registers: &mut [RegisterInstance],
+                   blocks: &[BlockInstance],
+                   unit_bits: u64,
+                   findings: &mut Vec<Finding>) {
+    registers.sort_by_key(|r| r.address);
+    let size = |r: &RegisterInstance| r.size.saturating_add(unit_bits - 1) / unit_bits;
+
+    for (i, r1) in registers.iter().enumerate() {
+        let end = r1.address.saturating_add(size(r1));
+        for r2 in registers[i + 1..].iter().take_while(|r2| r2.address < end) {
+            if !is_alternate(r1, r2) {
+                findings.push(Finding::new(&*r2.path,
+                                           Severity::Error,
+                                           "register-overlap",
+                                           format!("overlaps {} at 0x{:08X}",
+                                                   r1.path,
+                                                   r1.address)));
+            }
+        }
+
+        if !blocks.is_empty() && !blocks.iter().any(|b| b.start <= r1.address && end <= b.end) {
+            findings.push(Finding::new(&*r1.path,
+                                       Severity::Warning,
+                                       "register-outside-address-block",
+                                       format!("0x{:08X} is not inside an address block of \
+                                                the peripheral",
+                                               r1.address)));
+        }
+
+        if size(r1) > 0 && r1.address % size(r1) != 0 {
+            findings.push(Finding::new(&*r1.path,
+                                       Severity::Warning,
+                                       "misaligned-register",
+                                       format!("0x{:08X} is not aligned on the size of the \
+                                                register ({} bits)",
+                                               r1.address,
+                                               r1.size)));
+        }
+    }
+}
+
+fn is_alternate(r1: &RegisterInstance, r2: &RegisterInstance) -> bool {
+    fn names(r: &RegisterInstance, name: &str) -> bool {
+        r.register.name == name || r.path.rsplit('.').next() == Some(name)
+    }
+
+    let is_alternate_register = |r1: &RegisterInstance, r2: &RegisterInstance| {
+        r1.register.alternate_register.as_ref().map_or(false, |name| names(r2, name))
+    };
+
+    r1.register.alternate_group.is_some() || r2.register.alternate_group.is_some() ||
+    is_alternate_register(r1, r2) || is_alternate_register(r2, r1)
+}
+
+fn check_address_blocks(blocks: &mut [BlockInstance], findings: &mut Vec<Finding>) {
+    blocks.sort_by_key(|b| b.start);
+    let is_alternate = |b1: &BlockInstance, b2: &BlockInstance| {
+        b1.peripheral.alternate_peripheral.as_ref().map_or(false, |name| {
+            *name == b2.peripheral.name || *name == b2.path
+        })
+    };
+
+    for (i, b1) in blocks.iter().enumerate() {
+        for b2 in blocks[i + 1..].iter().take_while(|b2| b2.start < b1.end) {
+            if b1.path != b2.path && !is_alternate(b1, b2) && !is_alternate(b2, b1) {
+                findings.push(Finding::new(&*b2.path,
+                                           Severity::Error,
+                                           "address-block-overlap",
+                                           format!("the address block at 0x{:08X} overlaps an \
+                                                    address block of {}",
+                                                   b2.start,
+                                                   b1.path)));
+            }
+        }
+    }
+}
+
+/// Checks the registers and clusters declared in `registers` and their descendants.
+fn check_declarations(prefix: &str, registers: &[RegisterOrCluster], findings: &mut Vec<Finding>) {
+    let mut names = vec![];
+    let mut generated_names = vec![];
+    for r_or_c in registers {
+        match *r_or_c {
+            RegisterOrCluster::Register(ref r) => {
+                let path = format!("{}.{}", prefix, r.name);
+                check_name(&path, &r.name, findings);
+                check_dim(&path, &r.dim_element, findings);
+                check_fields(&path, r, findings);
+                if r.alternate_group.is_none() {
+                    names.push((path.clone(), element_names(&r.name, &r.dim_element)));
+                }
+                if r.is_read() {
+                    generated_names.push((path.clone(), format!("read_{}", r.name)));
+                }
+                if r.is_write() {
+                    generated_names.push((path.clone(), format!("write_{}", r.name)));
+                }
+                generated_names.push((path, format!("{}_ptr", r.name)));
+            }
+            RegisterOrCluster::Cluster(ref c) => {
+                let path = format!("{}.{}", prefix, c.name);
+                check_name(&path, &c.name, findings);
+                check_dim(&path, &c.dim_element, findings);
+                check_declarations(&path, &c.registers, findings);
+                names.push((path, element_names(&c.name, &c.dim_element)));
+            }
+        }
+    }
+    check_duplicates(&names, findings);
+    check_generated_names(&generated_names, findings);
+}
+
+/// Returns true if `name` is made of letters, digits and underscores, doesn't start with a digit
+/// and contains at most one `%s` placeholder, or ends with `[%s]`.
+fn is_valid_identifier(name: &str) -> bool {
+    let (name, has_placeholder) = if name.ends_with("[%s]") {
+        (name[..name.len() - 4].to_string(), true)
+    } else {
+        (name.replacen("%s", "", 1), name.contains("%s"))
+    };
+
+    name.chars().all(|c| c == '_' || c.is_ascii_alphanumeric()) &&
+    name.chars().next().map_or(has_placeholder, |c| !c.is_digit(10))
+}
+
+fn check_name(path: &str, name: &str, findings: &mut Vec<Finding>) {
+    if !is_valid_identifier(name) {
+        findings.push(Finding::new(path,
+                                   Severity::Error,
+                                   "invalid-name",
+                                   format!("{:?} is not a valid identifier", name)));
+    }
+}
+
+/// Returns the names of the elements of an array, or the name of an element that is not an array.
+fn element_names(name: &str, dim_element: &DimElementGroup) -> Vec<String> {
+    dim_element.elements()
+        .into_iter()
+        .map(|(index, _)| DimElementGroup::element_name(name, &index))
+        .collect()
+}
+
+/// Reports the siblings with the same name. `names` holds the path of each sibling and its names.
+fn check_duplicates(names: &[(String, Vec<String>)], findings: &mut Vec<Finding>) {
+    let mut seen = HashSet::new();
+    for &(ref path, ref element_names) in names {
+        for name in element_names {
+            if !seen.insert(name) {
+                findings.push(Finding::new(&**path,
+                                           Severity::Error,
+                                           "duplicate-name",
+                                           format!("{} is also the name of a sibling element",
+                                                   name)));
+                break;
+            }
+        }
+    }
+}
+
+/// Reports the items that the code generator would declare several times in the same scope.
+/// `generated_names` holds the path of the element generating each item and the name of the item.
+fn check_generated_names(generated_names: &[(String, String)], findings: &mut Vec<Finding>) {
+    let mut seen = HashMap::new();
+    for &(ref path, ref name) in generated_names {
+        match seen.get(name) {
+            Some(other_path) if *other_path != path => {
+                findings.push(Finding::new(&**path,
+                                           Severity::Warning,
+                                           "codegen-name-collision",
+                                           format!("the code generator would also declare {} \
+                                                    for {}",
+                                                   name,
+                                                   other_path)));
+                continue;
+            }
+            _ => {}
+        }
+        seen.insert(name, path);
+    }
+}
+
+/// Reports the modules renamed by the code generator because a group of peripherals has the same
+/// name as a peripheral or another group.
+fn check_peripherals_groups(device: &Device, findings: &mut Vec<Finding>) {
+    let (groups, individuals) = PeripheralsGroup::from_peripherals(&device.peripherals,
+                                                                   &IsSimilarOptions::new());
+    for group in &groups {
+        if group.module_name() != group.struct_name() {
+            let names: Vec<_> = group.peripherals().iter().map(|p| &*p.name).collect();
+            findings.push(Finding::new(names[0],
+                                       Severity::Warning,
+                                       "codegen-name-collision",
+                                       format!("the code generator would group {} in a module \
+                                                named {}, like another module, and rename it {}",
+                                               names.join(", "),
+                                               group.struct_name(),
+                                               group.module_name())));
+        }
+    }
+
+    for peripheral in &individuals {
+        if !device.peripherals.iter().any(|p| p.name == peripheral.name) {
+            let end = peripheral.name.rfind('_').unwrap_or(peripheral.name.len());
+            let name = &peripheral.name[..end];
+            findings.push(Finding::new(name,
+                                       Severity::Warning,
+                                       "codegen-name-collision",
+                                       format!("the code generator would rename the module of \
+                                                the peripheral {}, because a group of \
+                                                peripherals has the same name",
+                                               peripheral.name)));
+        }
+    }
+}
+
+fn check_dim(path: &str, dim_element: &DimElementGroup, findings: &mut Vec<Finding>) {
+    if let (Some(dim), Some(ref dim_index)) = (dim_element.dim, dim_element.dim_index.as_ref()) {
+        if dim_index.len() != dim.0 {
+            findings.push(Finding::new(path,
+                                       Severity::Error,
+                                       "dim-index-mismatch",
+                                       format!("dim is {} but dimIndex {} has {} indices",
+                                               dim,
+                                               dim_index,
+                                               dim_index.len())));
+        }
+    }
+}
+
+fn check_fields(path: &str, register: &Register, findings: &mut Vec<Finding>) {
+    let size = register.size();
+    let fields = register.fields.as_ref().map_or(&[][..], |f| &**f);
+
+    let names: Vec<_> = fields.iter()
+        .map(|f| (format!("{}.{}", path, f.name), vec![f.name.clone()]))
+        .collect();
+    check_duplicates(&names, findings);
+    check_fields_accessors(path, fields, findings);
+
+    for (i, field) in fields.iter().enumerate() {
+        let field_path = format!("{}.{}", path, field.name);
+        check_name(&field_path, &field.name, findings);
+        let bit_range = field.bit_range;
+        if bit_range.msb < bit_range.lsb {
+            findings.push(Finding::new(field_path,
+                                       Severity::Error,
+                                       "bit-range-reversed",
+                                       format!("msb {} is lower than lsb {}",
+                                               bit_range.msb,
+                                               bit_range.lsb)));
+            continue;
+        }
+
+        if u64::from(bit_range.msb) >= size {
+            findings.push(Finding::new(&*field_path,
+                                       Severity::Error,
+                                       "field-outside-register",
+                                       format!("[{}:{}] is beyond the size of the register ({} \
+                                                bits)",
+                                               bit_range.msb,
+                                               bit_range.lsb,
+                                               size)));
+        }
+
+        for other in &fields[..i] {
+            if other.bit_range.mask() & bit_range.mask() != 0 {
+                findings.push(Finding::new(&*field_path,
+                                           Severity::Error,
+                                           "field-overlap",
+                                           format!("[{}:{}] overlaps {} [{}:{}]",
+                                                   bit_range.msb,
+                                                   bit_range.lsb,
+                                                   other.name,
+                                                   other.bit_range.msb,
+                                                   other.bit_range.lsb)));
+            }
+        }
+
+        check_enumerated_values(&field_path, field, findings);
+    }
+
+    if let Some(reset_value) = register.register_properties.reset_value {
+        let reset_mask = register.register_properties.reset_mask.map_or(!0, |m| m.0);
+        if reset_value.0 & !register.size_mask() != 0 {
+            findings.push(Finding::new(path,
+                                       Severity::Error,
+                                       "reset-value-too-wide",
+                                       format!("0x{:X} doesn't fit in {} bits",
+                                               reset_value,
+                                               size)));
+        } else if reset_value.0 & reset_mask & register.reserved_bits_mask() != 0 {
+            findings.push(Finding::new(path,
+                                       Severity::Warning,
+                                       "reset-value-reserved-bits",
+                                       format!("0x{:X} sets bits that are not in any field",
+                                               reset_value)));
+        }
+    }
+}
+
+/// Reports the accessors of the fields that the code generator would declare several times,
+/// taking the groups of fields into account.
+fn check_fields_accessors(path: &str, fields: &[Field], findings: &mut Vec<Finding>) {
+    let (groups, individuals) = FieldsGroup::from_fields(fields);
+    let mut generated_names = vec![];
+    for group in &groups {
+        let group_path = format!("{}.{}%s", path, group.prefix());
+        if group.is_read() {
+            generated_names.push((group_path.clone(), group.prefix().to_string()));
+        }
+        if group.is_write() {
+            generated_names.push((group_path, format!("set_{}", group.prefix())));
+        }
+    }
+    for field in &individuals {
+        let field_path = format!("{}.{}", path, field.name);
+        if field.is_read() {
+            generated_names.push((field_path.clone(), field.name.clone()));
+        }
+        if field.is_write() {
+            generated_names.push((field_path, format!("set_{}", field.name)));
+        }
+    }
+    check_generated_names(&generated_names, findings);
+}
+
+fn check_enumerated_values(path: &str, field: &Field, findings: &mut Vec<Finding>) {
+    let width = field.bit_range.width();
+    let field_mask = if width >= 64 {
+        !0
+    } else {
+        (1 << width) - 1
+    };
+
+    for enumerated_values in &field.enumerated_values {
+        let mut names = HashSet::new();
+        let mut values = HashSet::new();
+        for enumerated_value in enumerated_values.enumerated_values() {
+            let value_path = format!("{}.{}", path, enumerated_value.name);
+            check_name(&value_path, &enumerated_value.name, findings);
+            if !names.insert(&enumerated_value.name) {
+                findings.push(Finding::new(&*value_path,
+                                           Severity::Error,
+                                           "duplicate-enumerated-value-name",
+                                           format!("the name {} is used by several enumerated \
+                                                    values",
+                                                   enumerated_value.name)));
+            }
+
+            if let EnumeratedValueData::Value { value, do_not_care } = enumerated_value.value {
+                let value_str = EnumeratedValueData::value_str(value, do_not_care);
+                if (value | do_not_care) & !field_mask != 0 {
+                    findings.push(Finding::new(&*value_path,
+                                               Severity::Error,
+                                               "enumerated-value-too-wide",
+                                               format!("{} doesn't fit in {} bits",
+                                                       value_str,
+                                                       width)));
+                }
+                if !values.insert((value & !do_not_care, do_not_care)) {
+                    findings.push(Finding::new(&*value_path,
+                                               Severity::Error,
+                                               "duplicate-enumerated-value",
+                                               format!("the value {} is used by several \
+                                                        enumerated values",
+                                                       value_str)));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use address_block::Usage;
+    use enumerated_values::{EnumeratedValue, EnumeratedValues};
+    use super::*;
+    use types::ScaledNonNegativeInteger;
+
+    /// Returns the sorted paths of the findings of `rule`, after checking their severity.
+    fn paths(device: &Device, rule: &str, severity: Severity) -> Vec<String> {
+        let mut paths: Vec<_> = device.validate()
+            .into_iter()
+            .filter(|f| f.rule == rule)
+            .map(|f| {
+                assert_eq!(severity, f.severity);
+                f.path
+            })
+            .collect();
+        paths.sort();
+        paths
+    }
+
+    fn device(peripherals: Vec<Peripheral>) -> Device {
+        peripherals.into_iter()
+            .fold(Device::builder("STM32X").version("1.0").description("An example device"),
+                  |d, p| d.peripheral(p))
+            .build()
+            .unwrap()
+    }
+
+    fn register(name: &str, offset: u64) -> Register {
+        Register::builder(name, offset).build().unwrap()
+    }
+
+    fn array(mut register: Register, dim: u64, dim_increment: u64) -> Register {
+        register.dim_element.dim = Some(ScaledNonNegativeInteger(dim));
+        register.dim_element.dim_increment = Some(ScaledNonNegativeInteger(dim_increment));
+        register
+    }
+
+    fn peripheral(name: &str, base_address: u64, registers: Vec<Register>) -> Peripheral {
+        registers.into_iter()
+            .fold(Peripheral::builder(name, base_address), |p, r| p.register(r))
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn valid_device() {
+        let gpioa = Peripheral::builder("GPIOA", 0x4002_0000)
+            .address_block(0x0, 0x400, Usage::Registers)
+            .register(register("MODER", 0x0))
+            .register(register("OTYPER", 0x4))
+            .build()
+            .unwrap();
+        assert_eq!(Vec::<Finding>::new(), device(vec![gpioa]).validate());
+    }
+
+    #[test]
+    fn register_overlap() {
+        let d = device(vec![peripheral("GPIOA",
+                                       0x4002_0000,
+                                       vec![register("MODER", 0x0),
+                                            register("OTYPER", 0x2),
+                                            register("OSPEEDR", 0x8),
+                                            // Alternates of the registers they overlap
+                                            Register::builder("ALT1", 0x8)
+                                                .alternate_register("OSPEEDR")
+                                                .build()
+                                                .unwrap(),
+                                            Register::builder("PUPDR", 0xc)
+                                                .alternate_register("ALT2")
+                                                .build()
+                                                .unwrap(),
+                                            register("ALT2", 0xc),
+                                            Register::builder("ALT3", 0xc)
+                                                .alternate_group("ALT")
+                                                .build()
+                                                .unwrap(),
+                                            // Its elements overlap each other
+                                            array(register("IDR%s", 0x10), 2, 0x2)])]);
+        assert_eq!(vec!["GPIOA.IDR1", "GPIOA.OTYPER"],
+                   paths(&d, "register-overlap", Severity::Error));
+    }
+
+    #[test]
+    fn register_overlap_address_units() {
+        // Two address units of 16 bits hold a 32 bits register
+        let mut d = device(vec![peripheral("ADC",
+                                           0x4001_2000,
+                                           vec![register("SR", 0x0), register("CR", 0x2)])]);
+        d.address_unit_bits = ScaledNonNegativeInteger(16);
+        assert!(paths(&d, "register-overlap", Severity::Error).is_empty());
+        d.address_unit_bits = ScaledNonNegativeInteger(8);
+        assert_eq!(vec!["ADC.CR"], paths(&d, "register-overlap", Severity::Error));
+    }
+
+    #[test]
+    fn register_outside_address_block() {
+        let gpioa = Peripheral::builder("GPIOA", 0x4002_0000)
+            .address_block(0x0, 0x8, Usage::Registers)
+            .address_block(0x10, 0x4, Usage::Registers)
+            .register(register("MODER", 0x0))
+            .register(register("OTYPER", 0x4))
+            .register(register("ODR", 0x6))
+            .register(register("IDR", 0x10))
+            .register(register("AFR", 0x14))
+            .build()
+            .unwrap();
+        // Peripherals without address blocks are not checked
+        let gpiob = peripheral("GPIOB", 0x4002_0400, vec![register("MODER", 0x1000)]);
+        let d = device(vec![gpioa, gpiob]);
+        assert_eq!(vec!["GPIOA.AFR", "GPIOA.ODR"],
+                   paths(&d, "register-outside-address-block", Severity::Warning));
+    }
+
+    #[test]
+    fn address_block_overlap() {
+        let block = |name: &str, base_address, size| {
+            Peripheral::builder(name, base_address)
+                .address_block(0x0, size, Usage::Registers)
+        };
+        let mut timers = block("TIM%s", 0x4000_0000, 0x800).build().unwrap();
+        timers.dim_element.dim = Some(ScaledNonNegativeInteger(2));
+        timers.dim_element.dim_increment = Some(ScaledNonNegativeInteger(0x400));
+        let d = device(vec![timers,
+                            block("GPIOA", 0x4002_0000, 0x400).build().unwrap(),
+                            block("GPIOB", 0x4002_0400, 0x400)
+                                .address_block(0x200, 0x400, Usage::Registers)
+                                .build()
+                                .unwrap(),
+                            block("GPIOC", 0x4002_09FC, 0x4).build().unwrap(),
+                            block("GPIOD", 0x4002_0000, 0x400)
+                                .alternate_peripheral("GPIOA")
+                                .build()
+                                .unwrap(),
+                            block("GPIOE", 0x4002_0800, 0x0).build().unwrap()]);
+        // The blocks of GPIOB overlap each other, and the last one overlaps GPIOC and GPIOE, but
+        // a block of size 0 is not checked
+        assert_eq!(vec!["GPIOC", "TIM1"], paths(&d, "address-block-overlap", Severity::Error));
+    }
+
+    #[test]
+    fn misaligned_register() {
+        let size = |name: &str, offset, size| Register::builder(name, offset).size(size).build();
+        let d = device(vec![peripheral("USART1",
+                                       0x4001_1000,
+                                       vec![size("SR", 0x0, 32).unwrap(),
+                                            size("DR", 0x6, 32).unwrap(),
+                                            size("BRR", 0xa, 16).unwrap(),
+                                            size("CR1", 0xd, 8).unwrap(),
+                                            size("CR2", 0xe, 64).unwrap()])]);
+        assert_eq!(vec!["USART1.CR2", "USART1.DR"],
+                   paths(&d, "misaligned-register", Severity::Warning));
+    }
+
+    fn field(name: &str, lsb: u32, msb: u32) -> Field {
+        let mut field = Field::builder(name, 0..=0).build().unwrap();
+        field.bit_range.lsb = lsb;
+        field.bit_range.msb = msb;
+        field
+    }
+
+    fn with_fields(mut register: Register, fields: Vec<Field>) -> Register {
+        register.fields = Some(fields);
+        register
+    }
+
+    fn values(values: &[(&str, u64, u64)]) -> EnumeratedValues {
+        let values = values.iter()
+            .map(|&(name, value, do_not_care)| {
+                EnumeratedValue {
+                    name: name.to_string(),
+                    description: None,
+                    value: EnumeratedValueData::Value {
+                        value: value,
+                        do_not_care: do_not_care,
+                    },
+                    unknown_elements: vec![],
+                }
+            })
+            .collect();
+        EnumeratedValues::new(None, None, values).unwrap()
+    }
+
+    fn gpioa(registers: Vec<Register>) -> Device {
+        device(vec![peripheral("GPIOA", 0x4002_0000, registers)])
+    }
+
+    #[test]
+    fn fields_bits() {
+        let moder = Register::builder("MODER", 0x0).size(16).build().unwrap();
+        let d = gpioa(vec![with_fields(moder,
+                                       vec![field("MODER0", 0, 1),
+                                            field("MODER1", 1, 2),
+                                            field("MODER2", 3, 4),
+                                            field("MODER3", 0, 4),
+                                            field("MODER6", 14, 15),
+                                            field("MODER7", 15, 16),
+                                            field("MODER8", 30, 40),
+                                            // Not reported as outside or overlapping
+                                            field("MODER9", 40, 2)])]);
+        // MODER3 overlaps each of the first three fields
+        assert_eq!(vec!["GPIOA.MODER.MODER1",
+                        "GPIOA.MODER.MODER3",
+                        "GPIOA.MODER.MODER3",
+                        "GPIOA.MODER.MODER3",
+                        "GPIOA.MODER.MODER7"],
+                   paths(&d, "field-overlap", Severity::Error));
+        assert_eq!(vec!["GPIOA.MODER.MODER7", "GPIOA.MODER.MODER8"],
+                   paths(&d, "field-outside-register", Severity::Error));
+        assert_eq!(vec!["GPIOA.MODER.MODER9"],
+                   paths(&d, "bit-range-reversed", Severity::Error));
+    }
+
+    #[test]
+    fn reset_values() {
+        let reset = |name: &str, offset, reset_value, reset_mask: Option<u64>| {
+            let register = Register::builder(name, offset).size(8).reset_value(reset_value);
+            let register = match reset_mask {
+                Some(reset_mask) => register.reset_mask(reset_mask),
+                None => register,
+            };
+            with_fields(register.build().unwrap(), vec![field("EN", 0, 3)])
+        };
+        let d = gpioa(vec![reset("CR1", 0x0, 0x0f, None),
+                           reset("CR2", 0x4, 0x1f, None),
+                           reset("CR3", 0x8, 0x1f, Some(0x0f)),
+                           reset("CR4", 0xc, 0x100, None),
+                           // Too wide, and not reported as setting reserved bits
+                           reset("CR5", 0x10, 0x1f0, None),
+                           // All the bits of a register without fields are defined
+                           Register::builder("DR", 0x14)
+                               .size(8)
+                               .reset_value(0xff)
+                               .build()
+                               .unwrap()]);
+        assert_eq!(vec!["GPIOA.CR4", "GPIOA.CR5"],
+                   paths(&d, "reset-value-too-wide", Severity::Error));
+        assert_eq!(vec!["GPIOA.CR2"], paths(&d, "reset-value-reserved-bits", Severity::Warning));
+    }
+
+    #[test]
+    fn enumerated_values() {
+        let mut mode = field("MODE", 0, 1);
+        mode.enumerated_values.push(values(&[("Input", 0, 0),
+                                             ("Output", 1, 0),
+                                             ("Output", 2, 0),
+                                             ("Analog", 2, 0),
+                                             ("Invalid", 4, 0),
+                                             ("Wide", 0, 0b100)]));
+        // The same names and values in different enumeratedValues are not duplicates, and do not
+        // care bits make values equal
+        mode.enumerated_values.push(values(&[("Input", 0, 0),
+                                             ("Any", 0b10, 0b01),
+                                             ("Odd", 0b11, 0b01),
+                                             ("High", 0b11, 0)]));
+        let mut all = field("ALL", 0, 63);
+        all.enumerated_values.push(values(&[("Max", !0, 0)]));
+        let d = gpioa(vec![with_fields(register("MODER", 0x0), vec![mode, all])]);
+        assert_eq!(vec!["GPIOA.MODER.MODE.Invalid", "GPIOA.MODER.MODE.Wide"],
+                   paths(&d, "enumerated-value-too-wide", Severity::Error));
+        assert_eq!(vec!["GPIOA.MODER.MODE.Output"],
+                   paths(&d, "duplicate-enumerated-value-name", Severity::Error));
+        assert_eq!(vec!["GPIOA.MODER.MODE.Analog", "GPIOA.MODER.MODE.Odd"],
+                   paths(&d, "duplicate-enumerated-value", Severity::Error));
+    }
+
+    #[test]
+    fn dim_index_mismatch() {
+        let indexed = |name: &str, offset, dim, dim_index: &str| {
+            let mut register = array(register(name, offset), dim, 0x4);
+            register.dim_element.dim_index = Some(dim_index.parse().unwrap());
+            register
+        };
+        let mut gpio = peripheral("GPIO%s",
+                                  0x4002_0000,
+                                  vec![indexed("AFR%s", 0x0, 2, "L,H"),
+                                       indexed("IDR%s", 0x10, 2, "A,B,C"),
+                                       indexed("ODR%s", 0x20, 4, "1-3"),
+                                       indexed("BSR%s", 0x30, 3, "X-Z"),
+                                       // Without dimIndex, the indices are 0 to dim - 1
+                                       array(register("LCK%s", 0x40), 3, 0x4)]);
+        gpio.dim_element.dim = Some(ScaledNonNegativeInteger(2));
+        gpio.dim_element.dim_increment = Some(ScaledNonNegativeInteger(0x400));
+        gpio.dim_element.dim_index = Some("A".parse().unwrap());
+        let d = device(vec![gpio]);
+        assert_eq!(vec!["GPIO%s", "GPIO%s.IDR%s", "GPIO%s.ODR%s"],
+                   paths(&d, "dim-index-mismatch", Severity::Error));
+    }
+
+    #[test]
+    fn invalid_name() {
+        let mut en = field("EN", 0, 0);
+        en.enumerated_values.push(values(&[("Off", 0, 0), ("1x", 1, 0)]));
+        let cr = with_fields(register("CR", 0x0), vec![en, field("A-B", 1, 1)]);
+        let d = device(vec![peripheral("GPIOA",
+                                       0x4002_0000,
+                                       vec![cr,
+                                            array(register("CH%s", 0x4), 2, 0x4),
+                                            array(register("CCR[%s]", 0x10), 2, 0x4),
+                                            array(register("%s_DR", 0x20), 2, 0x4),
+                                            array(register("%s", 0x30), 2, 0x4),
+                                            array(register("CH%s_%s", 0x40), 2, 0x4),
+                                            array(register("[%s]CCR", 0x50), 2, 0x4),
+                                            register("_SR", 0x60),
+                                            register("2ND", 0x64)]),
+                            peripheral("GPIO B", 0x4002_0400, vec![])]);
+        assert_eq!(vec!["GPIO B",
+                        "GPIOA.2ND",
+                        "GPIOA.CH%s_%s",
+                        "GPIOA.CR.A-B",
+                        "GPIOA.CR.EN.1x",
+                        "GPIOA.[%s]CCR"],
+                   paths(&d, "invalid-name", Severity::Error));
+    }
+
+    #[test]
+    fn duplicate_name() {
+        use cluster::Cluster;
+        use xml_fragment::parse_element;
+
+        let mut channels = array(register("CH%s", 0x8), 2, 0x4);
+        channels.dim_element.dim_index = Some("1-2".parse().unwrap());
+        let cluster = "<cluster><name>CR</name><addressOffset>0x40</addressOffset>\
+                       <register><name>CR</name><addressOffset>0x0</addressOffset></register>\
+                       <register><name>SR</name><addressOffset>0x4</addressOffset></register>\
+                       </cluster>";
+        let cluster = Cluster::from_element(&parse_element(cluster.as_bytes()).unwrap()).unwrap();
+        let gpioa = Peripheral::builder("GPIOA", 0x4002_0000)
+            .register(with_fields(register("CR", 0x0),
+                                  vec![field("EN", 0, 0), field("EN", 1, 1)]))
+            .register(channels)
+            .register(register("CH2", 0x10))
+            // Registers of an alternate group can have the name of another register
+            .register(Register::builder("CH1", 0x8).alternate_group("ALT").build().unwrap())
+            .cluster(cluster)
+            .build()
+            .unwrap();
+        let d = device(vec![gpioa, peripheral("GPIOA", 0x4002_0400, vec![])]);
+        assert_eq!(vec!["GPIOA", "GPIOA.CH2", "GPIOA.CR", "GPIOA.CR.EN"],
+                   paths(&d, "duplicate-name", Severity::Error));
+    }
+
+    #[test]
+    fn codegen_name_collision() {
+        use access::Access;
+
+        let read_only = |name: &str, bit| {
+            let mut field = field(name, bit, bit);
+            field.access = Some(Access::ReadOnly);
+            field
+        };
+        // The accessors of CH0 and CH1 are grouped as CH and set_CH
+        let cr = with_fields(register("CR", 0x0),
+                             vec![field("CH0", 0, 0),
+                                  field("CH1", 1, 1),
+                                  field("set_CH", 2, 2),
+                                  // EN has no set_EN accessor
+                                  read_only("EN", 3),
+                                  field("set_EN", 4, 4)]);
+        let gpio = |name: &str, base_address| {
+            peripheral(name, base_address, vec![register("MODER", 0x0)])
+        };
+        let d = device(vec![gpio("GPIOA", 0x4002_0000),
+                            gpio("GPIOB", 0x4002_0400),
+                            peripheral("GPIO", 0x4002_0800, vec![cr])]);
+        // The module of GPIOA and GPIOB and the one of GPIO would both be named gpio
+        assert_eq!(vec!["GPIO", "GPIO.CR.set_CH", "GPIOA"],
+                   paths(&d, "codegen-name-collision", Severity::Warning));
+    }
+
+    #[test]
+    fn display() {
+        let d = device(vec![peripheral("GPIOA",
+                                       0x4002_0000,
+                                       vec![register("MODER", 0x0), register("OTYPER", 0x2)])]);
+        let findings: Vec<_> = d.validate().iter().map(|f| f.to_string()).collect();
+        assert_eq!(vec!["error: GPIOA.OTYPER: overlaps GPIOA.MODER at 0x40020000 \
+                         [register-overlap]",
+                        "warning: GPIOA.OTYPER: 0x40020002 is not aligned on the size of the \
+                         register (32 bits) [misaligned-register]"],
+                   findings);
+    }
+
+    #[test]
+    fn address_overflow() {
+        let top = Peripheral::builder("TOP", 0xFFFF_FFFF_FFFF_FFF0)
+            .address_block(0x0, 0xc, Usage::Registers)
+            .address_block(0x8, 0x10, Usage::Registers)
+            .register(register("CR", 0x8))
+            .register(register("SR", 0x20))
+            .register(Register::builder("DR", 0xc).size(64).build().unwrap())
+            .build()
+            .unwrap();
+        // SR and the second address block, whose addresses overflow, are not checked. DR
+        // overlaps the end of the address space.
+        let d = device(vec![top]);
+        assert_eq!(vec!["TOP.DR"], paths(&d, "register-outside-address-block", Severity::Warning));
+        assert!(paths(&d, "address-block-overlap", Severity::Error).is_empty());
+    }
+}
@@ -0,0 +1,260 @@
+//! Traversal of the device tree.
+//!
+//! Each `visit_*` method of `Visitor` and `VisitorMut` calls the corresponding `walk_*` function by
+//! default, which visits the children of the element. An implementation that overrides a
+//! `visit_*` method must call the `walk_*` function itself to keep visiting the children.
+
+use cluster::Cluster;
+use device::Device;
+use enumerated_values::EnumeratedValues;
+use field::Field;
+use peripheral::Peripheral;
+use register::Register;
+use register_or_cluster::RegisterOrCluster;
+
+pub trait Visitor {
+    fn visit_device(&mut self, device: &Device) {
+        walk_device(self, device);
+    }
+
+    fn visit_peripheral(&mut self, peripheral: &Peripheral) {
+        walk_peripheral(self, peripheral);
+    }
+
+    fn visit_register_or_cluster(&mut self, r_or_c: &RegisterOrCluster) {
+        walk_register_or_cluster(self, r_or_c);
+    }
+
+    fn visit_cluster(&mut self, cluster: &Cluster) {
+        walk_cluster(self, cluster);
+    }
+
+    fn visit_register(&mut self, register: &Register) {
+        walk_register(self, register);
+    }
+
+    fn visit_field(&mut self, field: &Field) {
+        walk_field(self, field);
+    }
+
+    fn visit_enumerated_values(&mut self, _enumerated_values: &EnumeratedValues) {}
+}
+
+pub fn walk_device<V: Visitor + ?Sized>(visitor: &mut V, device: &Device) {
+    for peripheral in &device.peripherals {
+        visitor.visit_peripheral(peripheral);
+    }
+}
+
+pub fn walk_peripheral<V: Visitor + ?Sized>(visitor: &mut V, peripheral: &Peripheral) {
+    if let Some(ref registers) = peripheral.registers {
+        for r_or_c in registers {
+            visitor.visit_register_or_cluster(r_or_c);
+        }
+    }
+}
+
+pub fn walk_register_or_cluster<V: Visitor + ?Sized>(visitor: &mut V, r_or_c: &RegisterOrCluster) {
+    match *r_or_c {
+        RegisterOrCluster::Register(ref r) => visitor.visit_register(r),
+        RegisterOrCluster::Cluster(ref c) => visitor.visit_cluster(c),
+    }
+}
+
+pub fn walk_cluster<V: Visitor + ?Sized>(visitor: &mut V, cluster: &Cluster) {
+    for r_or_c in &cluster.registers {
+        visitor.visit_register_or_cluster(r_or_c);
+    }
+}
+
+pub fn walk_register<V: Visitor + ?Sized>(visitor: &mut V, register: &Register) {
+    if let Some(ref fields) = register.fields {
+        for field in fields {
+            visitor.visit_field(field);
+        }
+    }
+}
+
+pub fn walk_field<V: Visitor + ?Sized>(visitor: &mut V, field: &Field) {
+    for enumerated_values in &field.enumerated_values {
+        visitor.visit_enumerated_values(enumerated_values);
+    }
+}
+
+pub trait VisitorMut {
+    fn visit_device_mut(&mut self, device: &mut Device) {
+        walk_device_mut(self, device);
+    }
+
+    fn visit_peripheral_mut(&mut self, peripheral: &mut Peripheral) {
+        walk_peripheral_mut(self, peripheral);
+    }
+
+    fn visit_register_or_cluster_mut(&mut self, r_or_c: &mut RegisterOrCluster) {
+        walk_register_or_cluster_mut(self, r_or_c);
+    }
+
+    fn visit_cluster_mut(&mut self, cluster: &mut Cluster) {
+        walk_cluster_mut(self, cluster);
+    }
+
+    fn visit_register_mut(&mut self, register: &mut Register) {
+        walk_register_mut(self, register);
+    }
+
+    fn visit_field_mut(&mut self, field: &mut Field) {
+        walk_field_mut(self, field);
+    }
+
+    fn visit_enumerated_values_mut(&mut self, _enumerated_values: &mut EnumeratedValues) {}
+}
+
+pub fn walk_device_mut<V: VisitorMut + ?Sized>(visitor: &mut V, device: &mut Device) {
+    for peripheral in &mut device.peripherals {
+        visitor.visit_peripheral_mut(peripheral);
+    }
+}
+
+pub fn walk_peripheral_mut<V: VisitorMut + ?Sized>(visitor: &mut V, peripheral: &mut Peripheral) {
+    if let Some(ref mut registers) = peripheral.registers {
+        for r_or_c in registers {
+            visitor.visit_register_or_cluster_mut(r_or_c);
+        }
+    }
+}
+
+pub fn walk_register_or_cluster_mut<V: VisitorMut + ?Sized>(visitor: &mut V,
+                                                            r_or_c: &mut RegisterOrCluster) {
+    match *r_or_c {
+        RegisterOrCluster::Register(ref mut r) => visitor.visit_register_mut(r),
+        RegisterOrCluster::Cluster(ref mut c) => visitor.visit_cluster_mut(c),
+    }
+}
+
+pub fn walk_cluster_mut<V: VisitorMut + ?Sized>(visitor: &mut V, cluster: &mut Cluster) {
+    for r_or_c in &mut cluster.registers {
+        visitor.visit_register_or_cluster_mut(r_or_c);
+    }
+}
+
+pub fn walk_register_mut<V: VisitorMut + ?Sized>(visitor: &mut V, register: &mut Register) {
+    if let Some(ref mut fields) = register.fields {
+        for field in fields {
+            visitor.visit_field_mut(field);
+        }
+    }
+}
+
+pub fn walk_field_mut<V: VisitorMut + ?Sized>(visitor: &mut V, field: &mut Field) {
+    for enumerated_values in &mut field.enumerated_values {
+        visitor.visit_enumerated_values_mut(enumerated_values);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &'static str = r#"
+        <device><name>TEST</name><version>1.0</version><description>Test</description>
+            <addressUnitBits>8</addressUnitBits><width>32</width>
+            <peripherals>
+                <peripheral><name>DMA</name><baseAddress>0x1000</baseAddress><registers>
+                    <register><name>ISR</name><addressOffset>0x0</addressOffset>
+                        <fields><field><name>GIF</name><bitRange>[0:0]</bitRange></field></fields>
+                    </register>
+                    <cluster><name>CH</name><addressOffset>0x8</addressOffset>
+                        <cluster><name>CFG</name><addressOffset>0x0</addressOffset>
+                            <register><name>CCR</name><addressOffset>0x0</addressOffset>
+                                <fields><field><name>PL</name><bitRange>[1:0]</bitRange>
+                                    <enumeratedValues>
+                                        <enumeratedValue><name>Low</name><value>0</value>
+                                        </enumeratedValue>
+                                    </enumeratedValues>
+                                </field></fields>
+                            </register>
+                        </cluster>
+                        <register><name>CNDTR</name><addressOffset>0x4</addressOffset>
+                        </register>
+                    </cluster>
+                </registers></peripheral>
+                <peripheral><name>RCC</name><baseAddress>0x2000</baseAddress></peripheral>
+            </peripherals>
+        </device>"#;
+
+    #[derive(Default)]
+    struct Recorder {
+        names: Vec<String>,
+        skip_clusters: bool,
+    }
+
+    impl Visitor for Recorder {
+        fn visit_peripheral(&mut self, peripheral: &Peripheral) {
+            self.names.push(peripheral.name.clone());
+            walk_peripheral(self, peripheral);
+        }
+
+        fn visit_cluster(&mut self, cluster: &Cluster) {
+            self.names.push(cluster.name.clone());
+            if !self.skip_clusters {
+                walk_cluster(self, cluster);
+            }
+        }
+
+        fn visit_register(&mut self, register: &Register) {
+            self.names.push(register.name.clone());
+            walk_register(self, register);
+        }
+
+        fn visit_field(&mut self, field: &Field) {
+            self.names.push(field.name.clone());
+            walk_field(self, field);
+        }
+
+        fn visit_enumerated_values(&mut self, enumerated_values: &EnumeratedValues) {
+            self.names.push(format!("{} values", enumerated_values.enumerated_values().len()));
+        }
+    }
+
+    #[test]
+    fn visitor() {
+        let d = Device::parse(EXAMPLE.as_bytes()).unwrap();
+
+        // The elements are visited depth first, in the order of the device
+        let mut recorder = Recorder::default();
+        recorder.visit_device(&d);
+        assert_eq!(vec!["DMA", "ISR", "GIF", "CH", "CFG", "CCR", "PL", "1 values", "CNDTR",
+                        "RCC"],
+                   recorder.names);
+
+        // The children are not visited when the walk function is not called
+        let mut recorder = Recorder { skip_clusters: true, ..Recorder::default() };
+        recorder.visit_device(&d);
+        assert_eq!(vec!["DMA", "ISR", "GIF", "CH", "RCC"], recorder.names);
+    }
+
+    struct Renamer;
+
+    impl VisitorMut for Renamer {
+        fn visit_register_mut(&mut self, register: &mut Register) {
+            register.name = format!("{}_REG", register.name);
+            walk_register_mut(self, register);
+        }
+
+        fn visit_field_mut(&mut self, field: &mut Field) {
+            field.name = field.name.to_lowercase();
+        }
+    }
+
+    #[test]
+    fn visitor_mut() {
+        let mut d = Device::parse(EXAMPLE.as_bytes()).unwrap();
+        Renamer.visit_device_mut(&mut d);
+        assert!(d.resolve_field("DMA.ISR_REG.gif").is_some());
+        assert!(d.resolve_field("DMA.CH.CFG.CCR_REG.pl").is_some());
+        assert!(d.resolve_register("DMA.CH.CNDTR_REG").is_some());
+        // The field visitor does not walk the enumerated values, which are left as they are
+        let pl = d.resolve_field("DMA.CH.CFG.CCR_REG.pl").unwrap();
+        assert_eq!("Low", pl.enumerated_values[0].find(0).unwrap().name);
+    }
+}
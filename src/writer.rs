@@ -0,0 +1,121 @@
+use std::io;
+use std::io::Write;
+use xmltree;
+
+const INDENTATION: &'static str = "  ";
+
+/// Writes `root` and its descendants as an XML document. Each element is on its own line, and
+/// attributes are sorted by name so that the output only depends on the content of the tree.
+pub fn write_document<W: Write>(w: &mut W, root: &xmltree::Element) -> io::Result<()> {
+    try!(writeln!(w, "<?xml version=\"1.0\" encoding=\"utf-8\"?>"));
+    write_element(w, root, 0)
+}
+
+fn write_element<W: Write>(w: &mut W, element: &xmltree::Element, depth: usize) -> io::Result<()> {
+    for _ in 0..depth {
+        try!(write!(w, "{}", INDENTATION));
+    }
+
+    let text = element.text.as_ref().map_or("", |t| &**t);
+    if !text.is_empty() || element.children.is_empty() {
+        // The whitespace used for the indentation would become part of the text of an element
+        // that has both text and children, so the children are written inline.
+        try!(write_inline(w, element));
+        return writeln!(w, "");
+    }
+
+    try!(write_start_tag(w, element));
+    try!(writeln!(w, ">"));
+    for child in &element.children {
+        try!(write_element(w, child, depth + 1));
+    }
+    for _ in 0..depth {
+        try!(write!(w, "{}", INDENTATION));
+    }
+    writeln!(w, "</{}>", element.name)
+}
+
+/// Writes `element` and its descendants without any indentation or line break.
+pub fn write_inline<W: Write>(w: &mut W, element: &xmltree::Element) -> io::Result<()> {
+    try!(write_start_tag(w, element));
+    let text = element.text.as_ref().map_or("", |t| &**t);
+    if text.is_empty() && element.children.is_empty() {
+        return write!(w, "/>");
+    }
+
+    try!(write!(w, ">{}", escape(text)));
+    for child in &element.children {
+        try!(write_inline(w, child));
+    }
+    write!(w, "</{}>", element.name)
+}
+
+fn write_start_tag<W: Write>(w: &mut W, element: &xmltree::Element) -> io::Result<()> {
+    try!(write!(w, "<{}", element.name));
+    let mut attributes: Vec<_> = element.attributes.iter().collect();
+    attributes.sort();
+    for (name, value) in attributes {
+        try!(write!(w, " {}=\"{}\"", name, escape(value)));
+    }
+    Ok(())
+}
+
+fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use xml_fragment::parse_element;
+
+    fn written(xml: &str) -> String {
+        let mut w = vec![];
+        write_document(&mut w, &parse_element(xml.as_bytes()).unwrap()).unwrap();
+        String::from_utf8(w).unwrap()
+    }
+
+    #[test]
+    fn layout() {
+        assert_eq!("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n\
+                    <a b=\"2\" z=\"1\">\n  <b>\n    <c/>\n  </b>\n  <d>x</d>\n</a>\n",
+                   written("<a z='1' b='2'><b><c></c></b><d>x</d></a>"));
+    }
+
+    #[test]
+    fn escaping() {
+        assert_eq!("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n\
+                    <a b=\"&quot;&amp;\">&lt;1 &amp; 2&gt;</a>\n",
+                   written("<a b='&quot;&amp;'>&lt;1 &amp; 2&gt;</a>"));
+    }
+
+    #[test]
+    fn mixed_content() {
+        let xml = "<a><b>true <i>bold</i></b></a>";
+        assert_eq!("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n\
+                    <a>\n  <b>true <i>bold</i></b>\n</a>\n",
+                   written(xml));
+
+        let mut w = vec![];
+        write_inline(&mut w, &parse_element(xml.as_bytes()).unwrap()).unwrap();
+        assert_eq!(b"<a><b>true <i>bold</i></b></a>"[..], w[..]);
+
+        // The text after a child is kept, before the children
+        let element = parse_element("<x>a <b/> c<![CDATA[ <d>]]></x>".as_bytes()).unwrap();
+        assert_eq!(Some("a  c <d>"), element.text.as_ref().map(|t| &**t));
+        let mut w = vec![];
+        write_inline(&mut w, &element).unwrap();
+        assert_eq!(b"<x>a  c &lt;d&gt;<b/></x>"[..], w[..]);
+        assert_eq!(element, parse_element(&*w).unwrap());
+    }
+}
@@ -0,0 +1,249 @@
+#[cfg(feature = "serde")]
+use serde::{de, ser, Deserialize, Deserializer, Serialize, Serializer};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::io::Read;
+#[cfg(feature = "serde")]
+use writer;
+use xml::name::OwnedName;
+use xml::reader::{EventReader, XmlEvent};
+use xmltree::{self, ParseError};
+
+/// An XML element kept as is, for example a `vendorExtensions` element or an element unknown to
+/// this crate.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct XmlFragment(pub xmltree::Element);
+
+impl XmlFragment {
+    /// Returns the children of `element` whose name is not in any of the `known_names` lists.
+    pub fn unknown_children(element: &xmltree::Element,
+                            known_names: &[&[&str]])
+                            -> Vec<XmlFragment> {
+        element.children
+            .iter()
+            .filter(|e| !known_names.iter().any(|names| names.contains(&&*e.name)))
+            .map(|e| XmlFragment(e.clone()))
+            .collect()
+    }
+
+    /// Adds `fragments` to the children of `element`. `schema_order` lists the names of the
+    /// children of `element` in the order required by the schema: a fragment named in this list
+    /// is inserted before the first child that comes after it in the list, the other fragments are
+    /// appended.
+    pub fn add_to_element(element: &mut xmltree::Element,
+                          fragments: &[XmlFragment],
+                          schema_order: &[&str]) {
+        let position = |name: &str| schema_order.iter().position(|n| *n == name);
+        for fragment in fragments {
+            let index = position(&*fragment.0.name).and_then(|position_fragment| {
+                element.children
+                    .iter()
+                    .position(|c| position(&*c.name).map_or(false, |p| p > position_fragment))
+            });
+            match index {
+                Some(index) => element.children.insert(index, fragment.0.clone()),
+                None => element.children.push(fragment.0.clone()),
+            }
+        }
+    }
+
+    pub fn from_child(element: &xmltree::Element, name: &str) -> Option<XmlFragment> {
+        element.get_child(name).map(|e| XmlFragment(e.clone()))
+    }
+
+    pub fn name(&self) -> &str {
+        &*self.0.name
+    }
+
+    fn cmp_elements(e1: &xmltree::Element, e2: &xmltree::Element) -> Ordering {
+        let mut attributes1: Vec<_> = e1.attributes.iter().collect();
+        let mut attributes2: Vec<_> = e2.attributes.iter().collect();
+        attributes1.sort();
+        attributes2.sort();
+
+        e1.name
+            .cmp(&e2.name)
+            .then_with(|| attributes1.cmp(&attributes2))
+            .then_with(|| e1.text.cmp(&e2.text))
+            .then_with(|| {
+                for (c1, c2) in e1.children.iter().zip(&e2.children) {
+                    let ordering = XmlFragment::cmp_elements(c1, c2);
+                    if ordering != Ordering::Equal {
+                        return ordering;
+                    }
+                }
+                e1.children.len().cmp(&e2.children.len())
+            })
+    }
+}
+
+/// Parses an XML document like `xmltree::Element::parse`, but keeps the namespace prefixes of the
+/// names of the elements and of the attributes. Each element using a prefix declares it, unless
+/// its parent is itself prefixed and declares it too: as no SVD element is prefixed, this parent
+/// is always part of the same fragment. As an `xmltree::Element` has a single text, the text of
+/// an element is the concatenation of its text nodes: the text after a child is kept, but is
+/// written back before the children.
+pub fn parse_element<R: Read>(r: R) -> Result<xmltree::Element, ParseError> {
+    let mut reader = EventReader::new(r);
+    loop {
+        match reader.next() {
+            Ok(XmlEvent::StartElement { name, attributes, .. }) => {
+                let root = new_element(name, attributes, None);
+                return build(&mut reader, root);
+            }
+            Ok(XmlEvent::Comment(..)) |
+            Ok(XmlEvent::Whitespace(..)) |
+            Ok(XmlEvent::StartDocument { .. }) => continue,
+            Ok(_) => return Err(ParseError::CannotParse),
+            Err(e) => return Err(ParseError::MalformedXml(e)),
+        }
+    }
+}
+
+fn build<R: Read>(reader: &mut EventReader<R>,
+                  mut element: xmltree::Element)
+                  -> Result<xmltree::Element, ParseError> {
+    loop {
+        match reader.next() {
+            Ok(XmlEvent::EndElement { ref name }) if qualified_name(name) == element.name => {
+                return Ok(element);
+            }
+            Ok(XmlEvent::StartElement { name, attributes, .. }) => {
+                let child = new_element(name, attributes, Some(&element));
+                let child = try!(build(reader, child));
+                element.children.push(child);
+            }
+            Ok(XmlEvent::Characters(s)) |
+            Ok(XmlEvent::CData(s)) => element.text.get_or_insert_with(String::new).push_str(&s),
+            Ok(XmlEvent::Whitespace(..)) |
+            Ok(XmlEvent::Comment(..)) => (),
+            Ok(_) => return Err(ParseError::CannotParse),
+            Err(e) => return Err(ParseError::MalformedXml(e)),
+        }
+    }
+}
+
+fn new_element(name: OwnedName,
+               attributes: Vec<xml::attribute::OwnedAttribute>,
+               parent: Option<&xmltree::Element>)
+               -> xmltree::Element {
+    let mut element = xmltree::Element {
+        name: qualified_name(&name),
+        attributes: HashMap::new(),
+        children: vec![],
+        text: None,
+    };
+    let parent_attributes = parent.and_then(|p| if p.name.contains(':') {
+        Some(&p.attributes)
+    } else {
+        None
+    });
+    let names = Some(name).into_iter().chain(attributes.iter().map(|a| a.name.clone()));
+    for name in names {
+        if let (Some(prefix), Some(namespace)) = (name.prefix, name.namespace) {
+            let declaration = format!("xmlns:{}", prefix);
+            if parent_attributes.and_then(|a| a.get(&declaration)) != Some(&namespace) {
+                element.attributes.insert(declaration, namespace);
+            }
+        }
+    }
+    for attribute in attributes {
+        element.attributes.insert(qualified_name(&attribute.name), attribute.value);
+    }
+    element
+}
+
+fn qualified_name(name: &OwnedName) -> String {
+    match name.prefix {
+        Some(ref prefix) => format!("{}:{}", prefix, name.local_name),
+        None => name.local_name.clone(),
+    }
+}
+
+impl PartialOrd for XmlFragment {
+    fn partial_cmp(&self, other: &XmlFragment) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for XmlFragment {
+    fn cmp(&self, other: &XmlFragment) -> Ordering {
+        XmlFragment::cmp_elements(&self.0, &other.0)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl Serialize for XmlFragment {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut xml = vec![];
+        try!(writer::write_inline(&mut xml, &self.0).map_err(ser::Error::custom));
+        let xml = try!(String::from_utf8(xml).map_err(ser::Error::custom));
+        serializer.serialize_str(&xml)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> Deserialize<'de> for XmlFragment {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<XmlFragment, D::Error> {
+        let xml = try!(String::deserialize(deserializer));
+        parse_element(xml.as_bytes()).map(XmlFragment).map_err(de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use writer;
+
+    fn names(element: &xmltree::Element) -> Vec<&str> {
+        element.children.iter().map(|c| &*c.name).collect()
+    }
+
+    #[test]
+    fn namespace_prefixes() {
+        let xml = r#"<ext xmlns:acme="http://acme.com" xmlns:foo="http://foo.com">
+                         <acme:secure acme:level="2"><acme:key foo:id="1"/></acme:secure>
+                         <plain/>
+                     </ext>"#;
+        let element = parse_element(xml.as_bytes()).unwrap();
+        assert_eq!(vec!["acme:secure", "plain"], names(&element));
+
+        let secure = &element.children[0];
+        assert_eq!(Some(&"2".to_string()), secure.attributes.get("acme:level"));
+        assert_eq!(Some(&"http://acme.com".to_string()), secure.attributes.get("xmlns:acme"));
+        // `acme` is declared by the parent, `foo` is not
+        let key = &secure.children[0];
+        assert_eq!(None, key.attributes.get("xmlns:acme"));
+        assert_eq!(Some(&"http://foo.com".to_string()), key.attributes.get("xmlns:foo"));
+        assert!(element.children[1].attributes.is_empty());
+
+        // A fragment can be written and parsed back on its own
+        let mut written = vec![];
+        writer::write_inline(&mut written, secure).unwrap();
+        assert_eq!(*secure, parse_element(&*written).unwrap());
+    }
+
+    #[test]
+    fn malformed() {
+        assert!(parse_element("<a><b></a>".as_bytes()).is_err());
+        assert!(parse_element("<a><undeclared:b/></a>".as_bytes()).is_err());
+        assert!(parse_element("".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn add_to_element() {
+        let mut element = parse_element("<r><name/><addressOffset/><fields/></r>".as_bytes())
+            .unwrap();
+        let fragments: Vec<_> = ["vendor", "description", "dataType", "dim"]
+            .iter()
+            .map(|name| XmlFragment(::utils::new_element(name)))
+            .collect();
+        XmlFragment::add_to_element(&mut element,
+                                    &fragments,
+                                    &["dim", "name", "description", "addressOffset", "dataType",
+                                      "fields"]);
+        assert_eq!(vec!["dim", "name", "description", "addressOffset", "dataType", "fields",
+                        "vendor"],
+                   names(&element));
+    }
+}